@@ -35,18 +35,25 @@ fn main() {
         CliCommand::Completions(_completions_args) => {
             panic!("Completions should have been printed during options parsing, followed by program exit.");
         }
-        CliCommand::Search(search_command_args) => main_search(
-            &search_command_args,
-            &search_command_args.def_args.def_args,
-            &search_command_args
+        CliCommand::Search(search_command_args) => {
+            let experimental_target_patterns = &search_command_args
                 .optional
                 .scramble_and_target_pattern_optional_args
-                .scramble_file,
-            &search_command_args
-                .optional
-                .scramble_and_target_pattern_optional_args
-                .experimental_target_pattern,
-        ),
+                .experimental_target_pattern;
+            if experimental_target_patterns.len() > 1 {
+                eprintln!("Unsupported flag for `twsearch-cpp-wrapper search`: --experimental-target-pattern (more than once)");
+                exit(1);
+            }
+            main_search(
+                &search_command_args,
+                &search_command_args.def_args.def_args,
+                &search_command_args
+                    .optional
+                    .scramble_and_target_pattern_optional_args
+                    .scramble_file,
+                &experimental_target_patterns.first().cloned(),
+            )
+        }
         CliCommand::Serve(serve_command_args) => serve(serve_command_args, true),
         // TODO: consolidate def-only arg implementations.
         CliCommand::SchreierSims(schreier_sims_command_args) => {
@@ -61,6 +61,30 @@ pub trait SetCppArgs {
 
 impl SetCppArgs for CommonSearchArgs {
     fn set_cpp_args(&self) {
+        if self.time_limit_seconds.is_some() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --time-limit-seconds");
+            exit(1);
+        }
+        if self.move_costs.is_some() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --move-costs");
+            exit(1);
+        }
+        if self.symmetry_moves.is_some() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --symmetry-moves");
+            exit(1);
+        }
+        if !self.prune_table_mask.is_empty() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --prune-table-mask");
+            exit(1);
+        }
+        if self.exact_prune_table {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --exact-prune-table");
+            exit(1);
+        }
+        if self.bidirectional {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --bidirectional");
+            exit(1);
+        }
         set_boolean_arg(
             "--checkbeforesolve",
             is_enabled_with_default_true(&self.check_before_solve),
@@ -76,6 +100,10 @@ impl SetCppArgs for CommonSearchArgs {
 
 impl SetCppArgs for SearchCommandArgs {
     fn set_cpp_args(&self) {
+        if self.stats_json.is_some() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --stats-json");
+            exit(1);
+        }
         self.optional.set_cpp_args();
         self.def_args.set_cpp_args();
     }
@@ -109,6 +137,10 @@ impl SetCppArgs for GeneratorArgs {
 
 impl SetCppArgs for SearchPersistenceArgs {
     fn set_cpp_args(&self) {
+        if self.mmap_prune_tables {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --mmap-prune-tables");
+            exit(1);
+        }
         set_optional_arg("--writeprunetables", &self.write_prune_tables);
         if let Some(cache_dir) = &self.cache_dir {
             set_arg(
@@ -141,6 +173,7 @@ impl SetCppArgs for MemoryArgs {
 impl SetCppArgs for SchreierSimsArgs {
     fn set_cpp_args(&self) {
         set_boolean_arg("--schreiersims", true);
+        self.generator_args.set_cpp_args();
         self.performance_args.set_cpp_args();
     }
 }
@@ -163,6 +196,10 @@ impl SetCppArgs for GodsAlgorithmArgs {
 
 impl SetCppArgs for TimingTestArgs {
     fn set_cpp_args(&self) {
+        if self.json {
+            eprintln!("Unsupported flag for `twsearch-cpp-wrapper timing-test`: --json");
+            exit(1);
+        }
         set_boolean_arg("-T", true);
         self.performance_args.set_cpp_args();
         self.metric_args.set_cpp_args();
@@ -183,7 +220,14 @@ impl SetCppArgs for CanonicalAlgsArgs {
             );
             exit(1);
         }
-        set_boolean_arg("-C", true);
+        if self.list_algs {
+            eprintln!("Unsupported flag for `twsearch-cpp-wrapper canonical-algs`: --list-algs");
+            exit(1);
+        }
+        match self.max_depth {
+            Some(max_depth) => set_boolean_arg(&format!("-C{}", max_depth.0), true),
+            None => set_boolean_arg("-C", true),
+        }
         self.performance_args.set_cpp_args();
         self.metric_args.set_cpp_args();
     }
@@ -196,6 +240,13 @@ impl SetCppArgs for MetricArgs {
             MetricEnum::Quantum => {
                 set_boolean_arg("-q", true);
             }
+            MetricEnum::SliceTurn | MetricEnum::Axial => {
+                eprintln!(
+                    "Unsupported metric for `twsearch-cpp-wrapper`: {}",
+                    self.metric
+                );
+                exit(1);
+            }
         }
     }
 }
@@ -232,6 +283,10 @@ impl SetCppArgs for ServeArgsForIndividualSearch<'_> {
 
 impl SetCppArgs for ServeCommandArgs {
     fn set_cpp_args(&self) {
+        if self.time_limit_seconds.is_some() {
+            eprintln!("Unsupported flag for twsearch-cpp-wrapper: --time-limit-seconds");
+            exit(1);
+        }
         self.performance_args.set_cpp_args();
     }
 }
@@ -71,12 +71,13 @@ pub fn wasmTwsearch(
     );
     let mut idfs = idfs.map_err(|e| e.description)?;
 
-    match idfs
-        .search(&search_pattern, options.inidividual_search_options)
-        .next()
-    {
+    let mut solutions = idfs.search(&search_pattern, options.inidividual_search_options);
+    match solutions.next() {
         Some(alg) => Ok(alg.to_string().to_owned()),
-        None => Err("No solution found!".to_owned()),
+        None => Err(match solutions.end_reason() {
+            Some(end_reason) => format!("No solution found ({})!", end_reason),
+            None => "No solution found!".to_owned(),
+        }),
     }
 }
 
@@ -16,12 +16,11 @@ pub fn benchmark(benchmark_args: &BenchmarkArgs) -> Result<(), CommandError> {
         read_to_json(&benchmark_args.def_args.def_file).expect("Invalid definition"); // TODO: automatic error conversion.
     let kpuzzle = KPuzzle::try_new(def).expect("Invalid definition"); // TODO: automatic error conversion.
 
-    let search_generators = SearchGenerators::try_new(
+    let generators = benchmark_args.generator_args.parse();
+    let search_generators = SearchGenerators::try_new_with_alg_generators(
         &kpuzzle,
-        benchmark_args
-            .generator_args
-            .parse()
-            .enumerate_moves_for_kpuzzle(&kpuzzle),
+        generators.enumerate_moves_for_kpuzzle(&kpuzzle),
+        generators.algs(),
         &benchmark_args.metric_args.metric,
         false,
     )
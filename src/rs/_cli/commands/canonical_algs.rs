@@ -1,26 +1,234 @@
-use cubing::kpuzzle::{KPuzzle, KPuzzleDefinition};
+use std::collections::{HashMap, HashSet};
+
+use cubing::{
+    alg::{Alg, AlgNode},
+    kpuzzle::{KPattern, KPuzzle, KPuzzleDefinition},
+};
+use thousands::Separable;
 use twsearch::_internal::{
-    canonical_fsm::{canonical_fsm::CanonicalFSM, search_generators::SearchGenerators},
+    canonical_fsm::{
+        canonical_fsm::{CanonicalFSM, CanonicalFSMState, CANONICAL_FSM_START_STATE},
+        search_generators::SearchGenerators,
+    },
     cli::{args::CanonicalAlgsArgs, io::read_to_json},
     errors::CommandError,
 };
 
+const DEFAULT_MAX_DEPTH: usize = 5;
+
 pub fn canonical_algs(args: &CanonicalAlgsArgs) -> Result<(), CommandError> {
     let def: KPuzzleDefinition = read_to_json(&args.def_args.def_file)?;
     let kpuzzle = KPuzzle::try_new(def).unwrap();
 
-    let search_generators = SearchGenerators::try_new(
+    let generators = args.generator_args.parse();
+    let search_generators = SearchGenerators::try_new_with_alg_generators(
         &kpuzzle,
-        args.generator_args
-            .parse()
-            .enumerate_moves_for_kpuzzle(&kpuzzle),
+        generators.enumerate_moves_for_kpuzzle(&kpuzzle),
+        generators.algs(),
         &args.metric_args.metric,
         false,
     )?;
 
-    let canonical_fsm = CanonicalFSM::try_new(kpuzzle, search_generators, Default::default())
-        .expect("Expected to work!");
-    dbg!(canonical_fsm);
+    let canonical_fsm = CanonicalFSM::try_new(
+        kpuzzle.clone(),
+        search_generators.clone(),
+        Default::default(),
+    )
+    .expect("Expected to work!");
+
+    let max_depth = args.max_depth.map(|d| d.0).unwrap_or(DEFAULT_MAX_DEPTH);
+    if args.list_algs {
+        list_canonical_algs(&search_generators, &canonical_fsm, max_depth);
+    }
+    print_canonical_alg_counts(&kpuzzle, &search_generators, &canonical_fsm, max_depth);
 
     Ok(())
 }
+
+struct CanonicalAlgCounts {
+    num_sequences: u128,
+    num_patterns: usize,
+}
+
+fn print_canonical_alg_counts(
+    kpuzzle: &KPuzzle,
+    search_generators: &SearchGenerators<KPuzzle>,
+    canonical_fsm: &CanonicalFSM<KPuzzle>,
+    max_depth: usize,
+) {
+    println!(
+        "{:>7} {:>24} {:>20} {:>18}",
+        "depth", "canonical sequences", "distinct patterns", "branching factor"
+    );
+    let mut previous_num_sequences: Option<u128> = None;
+    for (depth, counts) in
+        canonical_alg_counts(kpuzzle, search_generators, canonical_fsm, max_depth)
+            .iter()
+            .enumerate()
+    {
+        println!(
+            "{:>7} {:>24} {:>20} {:>18}",
+            depth,
+            counts.num_sequences.separate_with_underscores(),
+            counts.num_patterns.separate_with_underscores(),
+            match previous_num_sequences {
+                Some(previous_num_sequences) if previous_num_sequences > 0 => format!(
+                    "{:.3}",
+                    counts.num_sequences as f64 / previous_num_sequences as f64
+                ),
+                _ => "".to_owned(),
+            }
+        );
+        previous_num_sequences = Some(counts.num_sequences);
+    }
+}
+
+/// Returns the counts for each depth from 0 up to `max_depth` (or up to the first depth with no sequences).
+fn canonical_alg_counts(
+    kpuzzle: &KPuzzle,
+    search_generators: &SearchGenerators<KPuzzle>,
+    canonical_fsm: &CanonicalFSM<KPuzzle>,
+    max_depth: usize,
+) -> Vec<CanonicalAlgCounts> {
+    // The number of canonical sequences ending in each FSM state.
+    let mut num_sequences_by_state =
+        HashMap::<CanonicalFSMState, u128>::from([(CANONICAL_FSM_START_STATE, 1)]);
+    // Sequences that reach the same pattern in the same FSM state have the same continuations,
+    // so it's sufficient to track each such pair once.
+    let mut patterns_with_state = HashSet::<(KPattern, CanonicalFSMState)>::from([(
+        kpuzzle.default_pattern(),
+        CANONICAL_FSM_START_STATE,
+    )]);
+    let mut counts = vec![];
+    for depth in 0..=max_depth {
+        let num_sequences: u128 = num_sequences_by_state.values().sum();
+        let num_patterns = patterns_with_state
+            .iter()
+            .map(|(pattern, _)| pattern)
+            .collect::<HashSet<&KPattern>>()
+            .len();
+        counts.push(CanonicalAlgCounts {
+            num_sequences,
+            num_patterns,
+        });
+        if num_sequences == 0 || depth == max_depth {
+            break;
+        }
+
+        let mut next_num_sequences_by_state = HashMap::<CanonicalFSMState, u128>::new();
+        for (state, num_sequences) in &num_sequences_by_state {
+            for (move_class_index, move_multiples) in search_generators.by_move_class.iter() {
+                let Some(next_state) = canonical_fsm.next_state(*state, move_class_index) else {
+                    continue;
+                };
+                *next_num_sequences_by_state.entry(next_state).or_default() +=
+                    num_sequences * move_multiples.len() as u128;
+            }
+        }
+        num_sequences_by_state = next_num_sequences_by_state;
+
+        let mut next_patterns_with_state = HashSet::<(KPattern, CanonicalFSMState)>::new();
+        for (pattern, state) in &patterns_with_state {
+            for (move_class_index, move_multiples) in search_generators.by_move_class.iter() {
+                let Some(next_state) = canonical_fsm.next_state(*state, move_class_index) else {
+                    continue;
+                };
+                for move_transformation_info in move_multiples {
+                    next_patterns_with_state.insert((
+                        pattern.apply_transformation(&move_transformation_info.transformation),
+                        next_state,
+                    ));
+                }
+            }
+        }
+        patterns_with_state = next_patterns_with_state;
+    }
+    counts
+}
+
+fn list_canonical_algs(
+    search_generators: &SearchGenerators<KPuzzle>,
+    canonical_fsm: &CanonicalFSM<KPuzzle>,
+    max_depth: usize,
+) {
+    for depth in 0..=max_depth {
+        println!("// Depth {}", depth);
+        list_canonical_algs_recursive(
+            search_generators,
+            canonical_fsm,
+            CANONICAL_FSM_START_STATE,
+            &mut vec![],
+            depth,
+        );
+    }
+}
+
+fn list_canonical_algs_recursive(
+    search_generators: &SearchGenerators<KPuzzle>,
+    canonical_fsm: &CanonicalFSM<KPuzzle>,
+    current_state: CanonicalFSMState,
+    alg_nodes: &mut Vec<AlgNode>,
+    remaining_depth: usize,
+) {
+    if remaining_depth == 0 {
+        let alg = Alg {
+            nodes: alg_nodes.clone(),
+        };
+        println!("{}", alg);
+        return;
+    }
+    for (move_class_index, move_multiples) in search_generators.by_move_class.iter() {
+        let Some(next_state) = canonical_fsm.next_state(current_state, move_class_index) else {
+            continue;
+        };
+        for move_transformation_info in move_multiples {
+            alg_nodes.push(move_transformation_info.alg_node.clone());
+            list_canonical_algs_recursive(
+                search_generators,
+                canonical_fsm,
+                next_state,
+                alg_nodes,
+                remaining_depth - 1,
+            );
+            alg_nodes.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::{alg::Move, puzzles::cube3x3x3_kpuzzle};
+    use twsearch::_internal::{
+        canonical_fsm::{canonical_fsm::CanonicalFSM, search_generators::SearchGenerators},
+        cli::args::MetricEnum,
+    };
+
+    use super::canonical_alg_counts;
+
+    #[test]
+    fn canonical_alg_counts_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let generator_moves: Vec<Move> = ["U", "L", "F", "R", "B", "D"]
+            .into_iter()
+            .map(|r#move| r#move.parse().unwrap())
+            .collect();
+        let search_generators =
+            SearchGenerators::try_new(kpuzzle, generator_moves, &MetricEnum::Hand, false).unwrap();
+        let canonical_fsm = CanonicalFSM::try_new(
+            kpuzzle.clone(),
+            search_generators.clone(),
+            Default::default(),
+        )
+        .unwrap();
+
+        let counts = canonical_alg_counts(kpuzzle, &search_generators, &canonical_fsm, 4);
+        assert_eq!(
+            counts
+                .iter()
+                .map(|counts| counts.num_sequences)
+                .collect::<Vec<_>>(),
+            vec![1, 18, 243, 3240, 43254]
+        );
+        assert_eq!(counts[4].num_patterns, 43239);
+    }
+}
@@ -1,5 +1,11 @@
+use std::fs::write;
+
 use twsearch::{
-    _internal::{cli::args::SearchCommandArgs, errors::CommandError},
+    _internal::{
+        cli::args::SearchCommandArgs,
+        errors::{ArgumentError, CommandError, SearchError},
+        search::idf_search::idf_search::SearchEndReason,
+    },
     experimental_lib_api::{search, KPuzzleSource, PatternSource},
 };
 
@@ -13,9 +19,9 @@ pub fn cli_search(search_command_args: SearchCommandArgs) -> Result<(), CommandE
             .scramble_and_target_pattern_optional_args,
     )?
     .pattern(&kpuzzle)?;
-    let solutions = search(&kpuzzle, &search_pattern, search_command_args.optional)?;
+    let mut solutions = search(&kpuzzle, &search_pattern, search_command_args.optional)?;
     let mut solution_index = 0;
-    for solution in solutions {
+    for solution in solutions.by_ref() {
         solution_index += 1;
         println!(
             "{} // solution #{} ({} nodes)",
@@ -24,9 +30,48 @@ pub fn cli_search(search_command_args: SearchCommandArgs) -> Result<(), CommandE
             solution.nodes.len()
         )
     }
+    // These are reported as an error (after everything else), so that scripts can tell that the search did not finish.
+    let unfinished_end_reason = match solutions.end_reason() {
+        Some(SearchEndReason::TimedOut) => {
+            println!("// Search timed out.");
+            None
+        }
+        Some(
+            end_reason @ (SearchEndReason::ExceededMaxSupportedDepth
+            | SearchEndReason::ExceededMemoryLimit
+            | SearchEndReason::InternalError),
+        ) => {
+            println!("// Search ended without finishing: {}.", end_reason);
+            Some(end_reason)
+        }
+        _ => None,
+    };
     println!(
         "// Entire search duration: {:?}",
         instant::Instant::now() - search_start_time
     );
+    if let Some(stats_json_path) = search_command_args.stats_json {
+        let Some(statistics) = solutions.statistics() else {
+            return Err(CommandError::SearchError(
+                "The search did not report any statistics.".into(),
+            ));
+        };
+        let stats_json = serde_json::to_string_pretty(statistics)
+            .expect("Internal error: could not serialize search statistics");
+        write(&stats_json_path, stats_json).map_err(|e| {
+            CommandError::ArgumentError(ArgumentError {
+                description: format!(
+                    "Could not write search statistics to {}: {}",
+                    stats_json_path.display(),
+                    e
+                ),
+            })
+        })?;
+    }
+    if let Some(end_reason) = unfinished_end_reason {
+        return Err(CommandError::SearchError(SearchError {
+            description: format!("Search ended without finishing: {}.", end_reason),
+        }));
+    }
     Ok(())
 }
@@ -3,3 +3,5 @@ pub mod canonical_algs;
 pub mod cli_scramble;
 pub mod cli_search;
 pub mod gods_algorithm;
+pub mod schreier_sims;
+pub mod timing_test;
@@ -0,0 +1,40 @@
+use twsearch::{
+    _internal::{
+        cli::args::SchreierSimsArgs, errors::CommandError,
+        gods_algorithm::factor_number::factor_product,
+    },
+    experimental_lib_api::{schreier_sims, KPuzzleSource},
+};
+
+pub fn cli_schreier_sims(schreier_sims_args: &SchreierSimsArgs) -> Result<(), CommandError> {
+    let start_time = instant::Instant::now();
+    let group = schreier_sims(
+        &KPuzzleSource::from_clap_args(&schreier_sims_args.def_args).kpuzzle()?,
+        &schreier_sims_args.generator_args,
+    )?;
+    if !group.orbits_with_identical_pieces.is_empty() {
+        eprintln!(
+            "Warning: the puzzle definition has identical pieces (or ignored orientations) in the following orbits: {}
+The count below treats all pieces as distinguishable, so it is larger than the number of distinct patterns.
+To count the patterns exactly, give each piece in these orbits a distinct value (and a full orientation) in the definition.",
+            group.orbits_with_identical_pieces.join(", ")
+        );
+    }
+    let order = group.order();
+    let orbit_sizes = group.stabilizer_chain.orbit_sizes();
+    if orbit_sizes.is_empty() {
+        println!("Number of reachable patterns: {}", order);
+    } else {
+        println!(
+            "Number of reachable patterns: {} ({})",
+            order,
+            factor_product(orbit_sizes)
+        );
+    }
+    println!("log₂: {:.3}", order.log2());
+    println!(
+        "Total time elapsed: {:?}",
+        instant::Instant::now() - start_time
+    );
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::{hint::black_box, sync::Arc, time::Duration};
+
+use cubing::kpuzzle::{KPattern, KPatternBuffer, KPuzzle, KTransformation};
+use instant::Instant;
+use rand::{seq::SliceRandom, thread_rng};
+use serde::Serialize;
+use thousands::Separable;
+use twsearch::{
+    _internal::{
+        cli::args::{MetricEnum, PerformanceArgs, TimingTestArgs, VerbosityLevel},
+        errors::CommandError,
+        puzzle_traits::puzzle_traits::GroupActionPuzzle,
+        search::{
+            idf_search::idf_search::{
+                default_num_threads, IDFSearch, IDFSearchConstructionOptions,
+            },
+            prune_table_trait::{Depth, PruneTable},
+            search_logger::SearchLogger,
+        },
+    },
+    experimental_lib_api::KPuzzleSource,
+};
+
+// Each throughput measurement runs for at least this long.
+const MIN_MEASUREMENT_DURATION: Duration = Duration::from_secs(1);
+// Prune table filling stops before any depth that is expected to take the total time past this.
+const PRUNE_TABLE_TIME_BUDGET: Duration = Duration::from_secs(5);
+// Prune table filling also stops once this fraction of the table is filled.
+const PRUNE_TABLE_MAX_FILL_FRACTION: f64 = 0.5;
+const DEFAULT_PRUNE_TABLE_SIZE: usize = 1 << 24;
+
+const MOVE_BATCH_SIZE: usize = 1 << 16;
+const NUM_LOOKUP_PATTERNS: usize = 1 << 10;
+const LOOKUP_PATTERN_SCRAMBLE_LENGTH: usize = 100;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Throughput {
+    count: usize,
+    seconds: f64,
+    per_second: f64,
+}
+
+impl Throughput {
+    fn new(count: usize, duration: Duration) -> Self {
+        let seconds = duration.as_secs_f64();
+        Self {
+            count,
+            seconds,
+            per_second: count as f64 / seconds,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PruneTableDepthTiming {
+    depth: usize,
+    num_filled_entries: usize,
+    fill_fraction: f64,
+    /// Throughput for the entries newly filled at this depth.
+    fill: Throughput,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TimingTestResults {
+    definition_name: String,
+    metric: String,
+    num_generator_moves: usize,
+    move_application: Throughput,
+    prune_table_size: usize,
+    prune_table_fill: Vec<PruneTableDepthTiming>,
+    prune_table_lookups: Throughput,
+}
+
+fn measure_throughput(batch_size: usize, mut run_batch: impl FnMut()) -> Throughput {
+    let start_time = Instant::now();
+    let mut count = 0;
+    loop {
+        run_batch();
+        count += batch_size;
+        let elapsed = Instant::now() - start_time;
+        if elapsed >= MIN_MEASUREMENT_DURATION {
+            return Throughput::new(count, elapsed);
+        }
+    }
+}
+
+pub fn cli_timing_test(timing_test_args: &TimingTestArgs) -> Result<(), CommandError> {
+    let kpuzzle = KPuzzleSource::from_clap_args(&timing_test_args.def_args).kpuzzle()?;
+    let results = timing_test_results(
+        &kpuzzle,
+        &timing_test_args.metric_args.metric,
+        &timing_test_args.performance_args,
+    )?;
+    if timing_test_args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&results).expect("Could not serialize results")
+        );
+    } else {
+        print_results_table(&results);
+    }
+    Ok(())
+}
+
+fn timing_test_results(
+    kpuzzle: &KPuzzle,
+    metric: &MetricEnum,
+    performance_args: &PerformanceArgs,
+) -> Result<TimingTestResults, CommandError> {
+    let memory_bytes = performance_args.memory_args.memory_bytes();
+    let prune_table_size = match memory_bytes {
+        // Entries are packed as nibbles or bytes depending on the pruning depth, so use
+        // a size that fits in the memory limit even when they take a full byte each.
+        Some(memory_bytes) => 1 << usize::max(memory_bytes, 1).ilog2(),
+        None => DEFAULT_PRUNE_TABLE_SIZE,
+    };
+    let idf_search = <IDFSearch<KPuzzle>>::try_new(
+        kpuzzle.clone(),
+        kpuzzle.puzzle_definition_all_moves(),
+        kpuzzle.default_pattern(),
+        IDFSearchConstructionOptions {
+            search_logger: Arc::new(SearchLogger {
+                verbosity: VerbosityLevel::Error,
+                ..Default::default()
+            }),
+            metric: metric.clone(),
+            min_prune_table_size: Some(prune_table_size),
+            max_prune_table_memory_bytes: Some(memory_bytes.unwrap_or(prune_table_size)),
+            num_threads: Some(
+                performance_args
+                    .num_threads
+                    .unwrap_or_else(default_num_threads),
+            ),
+            ..Default::default()
+        },
+    )?;
+    let transformations: Vec<&KTransformation> = idf_search
+        .api_data
+        .search_generators
+        .flat
+        .iter()
+        .map(|(_, move_transformation_info)| &move_transformation_info.transformation)
+        .collect();
+
+    // Move application
+    let mut rng = thread_rng();
+    let random_transformations: Vec<&KTransformation> = (0..MOVE_BATCH_SIZE)
+        .map(|_| *transformations.choose(&mut rng).unwrap())
+        .collect();
+    let mut pattern_buffer = KPatternBuffer::from(kpuzzle.default_pattern());
+    let move_application = measure_throughput(MOVE_BATCH_SIZE, || {
+        for transformation in &random_transformations {
+            pattern_buffer.apply_transformation(transformation);
+        }
+    });
+    black_box(pattern_buffer.current());
+
+    // Prune table fill
+    let mut prune_table = idf_search.prune_table.lock().unwrap();
+    let mut prune_table_fill = vec![];
+    let mut num_filled_entries = prune_table.num_filled_entries();
+    let fill_start_time = Instant::now();
+    let mut previous_depth_duration: Option<Duration> = None;
+    loop {
+        let depth = prune_table.pruning_depth().0 + 1;
+        let depth_start_time = Instant::now();
+        // The prune table is filled to half the search depth.
+        prune_table.extend_for_search_depth(Depth(depth * 2), prune_table_size, &|| false);
+        let depth_duration = Instant::now() - depth_start_time;
+        if prune_table.pruning_depth().0 < depth {
+            break;
+        }
+        let previous_num_filled_entries = num_filled_entries;
+        num_filled_entries = prune_table.num_filled_entries();
+        let fill_fraction = num_filled_entries as f64 / prune_table_size as f64;
+        prune_table_fill.push(PruneTableDepthTiming {
+            depth,
+            num_filled_entries,
+            fill_fraction,
+            fill: Throughput::new(
+                num_filled_entries - previous_num_filled_entries,
+                depth_duration,
+            ),
+        });
+        // Each depth takes roughly (branching factor) times as long as the previous one.
+        let growth_factor = match previous_depth_duration {
+            Some(previous_depth_duration) if !previous_depth_duration.is_zero() => f64::max(
+                1.0,
+                depth_duration.as_secs_f64() / previous_depth_duration.as_secs_f64(),
+            ),
+            _ => 1.0,
+        };
+        let estimated_next_depth_duration = depth_duration.mul_f64(growth_factor);
+        if num_filled_entries == previous_num_filled_entries
+            || fill_fraction >= PRUNE_TABLE_MAX_FILL_FRACTION
+            || (Instant::now() - fill_start_time) + estimated_next_depth_duration
+                > PRUNE_TABLE_TIME_BUDGET
+        {
+            break;
+        }
+        previous_depth_duration = Some(depth_duration);
+    }
+
+    // Prune table lookups
+    let lookup_patterns: Vec<KPattern> = (0..NUM_LOOKUP_PATTERNS)
+        .map(|_| {
+            let mut pattern_buffer = KPatternBuffer::from(kpuzzle.default_pattern());
+            for _ in 0..LOOKUP_PATTERN_SCRAMBLE_LENGTH {
+                pattern_buffer.apply_transformation(transformations.choose(&mut rng).unwrap());
+            }
+            pattern_buffer.current().clone()
+        })
+        .collect();
+    let mut depth_sum = 0;
+    let prune_table_lookups = measure_throughput(NUM_LOOKUP_PATTERNS, || {
+        for pattern in &lookup_patterns {
+            depth_sum += prune_table.lookup(pattern).0;
+        }
+    });
+    black_box(depth_sum);
+
+    Ok(TimingTestResults {
+        definition_name: kpuzzle.definition().name.clone(),
+        metric: metric.to_string(),
+        num_generator_moves: transformations.len(),
+        move_application,
+        prune_table_size,
+        prune_table_fill,
+        prune_table_lookups,
+    })
+}
+
+fn format_rate(per_second: f64, unit: &str) -> String {
+    format!("{:.2}M {}/s", per_second / 1_000_000.0, unit)
+}
+
+fn print_results_table(results: &TimingTestResults) {
+    println!(
+        "Definition: {} (metric: {}, {} generator moves)",
+        results.definition_name, results.metric, results.num_generator_moves
+    );
+    println!();
+    println!(
+        "Move application:    {} ({} moves in {:.2}s)",
+        format_rate(results.move_application.per_second, "moves"),
+        results.move_application.count.separate_with_underscores(),
+        results.move_application.seconds
+    );
+    println!(
+        "Prune table lookups: {} ({} lookups in {:.2}s)",
+        format_rate(results.prune_table_lookups.per_second, "lookups"),
+        results
+            .prune_table_lookups
+            .count
+            .separate_with_underscores(),
+        results.prune_table_lookups.seconds
+    );
+    println!();
+    println!(
+        "Prune table fill ({} entries):",
+        results.prune_table_size.separate_with_underscores()
+    );
+    println!(
+        "{:>7} {:>16} {:>8} {:>10} {:>20}",
+        "depth", "filled entries", "fill %", "time", "fill rate"
+    );
+    for depth_timing in &results.prune_table_fill {
+        println!(
+            "{:>7} {:>16} {:>7.2}% {:>9.3}s {:>20}",
+            depth_timing.depth,
+            depth_timing.num_filled_entries.separate_with_underscores(),
+            depth_timing.fill_fraction * 100.0,
+            depth_timing.fill.seconds,
+            format_rate(depth_timing.fill.per_second, "entries")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::puzzles::cube2x2x2_kpuzzle;
+    use twsearch::_internal::cli::args::{MemoryArgs, MetricEnum, PerformanceArgs};
+
+    use super::timing_test_results;
+
+    #[test]
+    fn timing_test_json_test() {
+        let results = timing_test_results(
+            cube2x2x2_kpuzzle(),
+            &MetricEnum::Hand,
+            &PerformanceArgs {
+                num_threads: Some(2),
+                memory_args: MemoryArgs {
+                    memory_mebibytes: Some(1),
+                },
+            },
+        )
+        .unwrap();
+        let json = serde_json::to_value(&results).unwrap();
+        assert_eq!(json["numGeneratorMoves"], 45);
+        assert_eq!(json["pruneTableSize"], 1 << 20);
+        for throughput_key in ["moveApplication", "pruneTableLookups"] {
+            let throughput = &json[throughput_key];
+            assert!(throughput["count"].as_u64().unwrap() > 0);
+            assert!(throughput["seconds"].is_f64());
+            assert!(throughput["perSecond"].is_f64());
+        }
+        let prune_table_fill = json["pruneTableFill"].as_array().unwrap();
+        assert!(!prune_table_fill.is_empty());
+        for depth_timings in prune_table_fill.windows(2) {
+            assert_eq!(
+                depth_timings[1]["depth"].as_u64(),
+                depth_timings[0]["depth"].as_u64().map(|depth| depth + 1)
+            );
+        }
+        for depth_timing in prune_table_fill {
+            assert!(depth_timing["numFilledEntries"].as_u64().unwrap() > 0);
+            assert!(depth_timing["fillFraction"].is_f64());
+            assert!(depth_timing["fill"]["perSecond"].is_f64());
+        }
+    }
+}
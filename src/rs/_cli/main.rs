@@ -3,7 +3,8 @@ mod serve;
 
 use commands::{
     benchmark::benchmark, canonical_algs::canonical_algs, cli_scramble::cli_scramble,
-    cli_search::cli_search, gods_algorithm::cli_gods_algorithm,
+    cli_search::cli_search, gods_algorithm::cli_gods_algorithm, schreier_sims::cli_schreier_sims,
+    timing_test::cli_timing_test,
 };
 use twsearch::_internal::{
     cli::args::{get_options, CliCommand},
@@ -20,9 +21,11 @@ fn main() -> Result<(), CommandError> {
         CliCommand::Search(search_command_args) => cli_search(search_command_args),
         CliCommand::Serve(serve_command_args) => serve::serve::serve(serve_command_args),
         // TODO: consolidate def-only arg implementations.
-        CliCommand::SchreierSims(_schreier_sims_command_args) => todo!(),
+        CliCommand::SchreierSims(schreier_sims_command_args) => {
+            cli_schreier_sims(&schreier_sims_command_args)
+        }
         CliCommand::GodsAlgorithm(gods_algorithm_args) => cli_gods_algorithm(gods_algorithm_args),
-        CliCommand::TimingTest(_args) => todo!(),
+        CliCommand::TimingTest(timing_test_args) => cli_timing_test(&timing_test_args),
         CliCommand::CanonicalAlgs(args) => canonical_algs(&args),
         CliCommand::Scramble(scramble_args) => cli_scramble(&scramble_args),
         CliCommand::Benchmark(benchmark_args) => benchmark(&benchmark_args),
@@ -4,12 +4,12 @@ use rouille::{router, try_or_400, Request, Response};
 use serde::{Deserialize, Serialize};
 use twsearch::_internal::{
     cli::args::{
-        CustomGenerators, Generators, ServeArgsForIndividualSearch, ServeClientArgs,
-        ServeCommandArgs,
+        parse_time_limit_seconds, CustomGenerators, Generators, ServeArgsForIndividualSearch,
+        ServeClientArgs, ServeCommandArgs,
     },
     errors::CommandError,
     search::idf_search::idf_search::{
-        IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions,
+        default_num_threads, IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions,
     },
     search::search_logger::SearchLogger,
 };
@@ -68,6 +68,7 @@ fn solve_pattern(
             .verbosity_args
             .verbosity
             .unwrap_or_default(),
+        ..Default::default()
     });
     let move_subset = match args_for_individual_search.client_args {
         Some(client_args) => client_args.generator_moves.as_ref().cloned(),
@@ -93,31 +94,61 @@ fn solve_pattern(
                 Some(client_args) => client_args.random_start == Some(true),
                 None => false,
             },
+            max_prune_table_memory_bytes: args_for_individual_search
+                .commandline_args
+                .performance_args
+                .memory_args
+                .memory_bytes(),
+            num_threads: Some(
+                args_for_individual_search
+                    .commandline_args
+                    .performance_args
+                    .num_threads
+                    .unwrap_or_else(default_num_threads),
+            ),
             ..Default::default()
         },
     ) {
         Ok(search) => search,
         Err(e) => return Response::text(e.description).with_status_code(400),
     };
-    if let Some(solution) = search
-        .search(
-            &search_pattern,
-            IndividualSearchOptions {
-                min_num_solutions: None,
-                min_depth: args_for_individual_search
-                    .client_args
-                    .as_ref()
-                    .and_then(|client_args| client_args.min_depth),
-                max_depth: args_for_individual_search
-                    .client_args
-                    .as_ref()
-                    .and_then(|client_args| client_args.max_depth),
-                // TODO: support canonical FSM pre-moves and post-moves.
-                ..Default::default()
-            },
-        )
-        .next()
-    {
+    let time_limit = match (
+        parse_time_limit_seconds(serve_command_args.time_limit_seconds),
+        parse_time_limit_seconds(
+            args_for_individual_search
+                .client_args
+                .as_ref()
+                .and_then(|client_args| client_args.time_limit_seconds),
+        ),
+    ) {
+        (Ok(server_time_limit), Ok(client_time_limit)) => {
+            match (server_time_limit, client_time_limit) {
+                (Some(server_time_limit), Some(client_time_limit)) => {
+                    Some(server_time_limit.min(client_time_limit))
+                }
+                (server_time_limit, client_time_limit) => server_time_limit.or(client_time_limit),
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => return Response::text(e.description).with_status_code(400),
+    };
+    let mut solutions = search.search(
+        &search_pattern,
+        IndividualSearchOptions {
+            min_num_solutions: None,
+            min_depth: args_for_individual_search
+                .client_args
+                .as_ref()
+                .and_then(|client_args| client_args.min_depth),
+            max_depth: args_for_individual_search
+                .client_args
+                .as_ref()
+                .and_then(|client_args| client_args.max_depth),
+            time_limit,
+            // TODO: support canonical FSM pre-moves and post-moves.
+            ..Default::default()
+        },
+    );
+    if let Some(solution) = solutions.next() {
         println!(
             "[Search request #{}] Solution found (in {:?}): {}",
             request_counter,
@@ -128,8 +159,16 @@ fn solve_pattern(
             alg: solution.to_string(),
         }); // TODO: send multiple solutions via socket
     }
-    println!("[Search request #{}] No solution found.", request_counter);
-    Response::text("No solution found").with_status_code(404)
+    // The iterator only returns `None` once the search has finished.
+    let end_reason = match solutions.end_reason() {
+        Some(end_reason) => format!(" ({})", end_reason),
+        None => "".to_owned(),
+    };
+    println!(
+        "[Search request #{}] No solution found{}.",
+        request_counter, end_reason
+    );
+    Response::text(format!("No solution found{}", end_reason)).with_status_code(404)
 }
 
 fn cors(response: Response) -> Response {
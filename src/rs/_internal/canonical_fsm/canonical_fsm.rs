@@ -1,7 +1,6 @@
 use std::{
     collections::{HashMap, HashSet},
     marker::PhantomData,
-    ops::{BitAndAssign, BitOrAssign},
 };
 
 use cubing::alg::QuantumMove;
@@ -16,77 +15,121 @@ use crate::{
 
 use super::search_generators::{MoveTransformationInfo, SearchGenerators};
 
-const MAX_NUM_MOVE_CLASSES: usize = usize::BITS as usize;
+const MOVE_CLASS_MASK_WORD_BITS: usize = u64::BITS as usize;
+// Masks are only used during construction, so this limit is just a sanity check.
+const MAX_NUM_MOVE_CLASS_MASK_WORDS: usize = 16;
+const MAX_NUM_MOVE_CLASSES: usize = MAX_NUM_MOVE_CLASS_MASK_WORDS * MOVE_CLASS_MASK_WORD_BITS;
 
 whole_number_newtype!(MoveClassIndex, usize);
 
 // Bit N is indexed by a `MoveClassIndex` value of N.
-whole_number_newtype!(MoveClassMask, u64);
-
-impl BitAndAssign for MoveClassMask {
-    fn bitand_assign(&mut self, rhs: Self) {
-        self.0 = self.0 & rhs.0
+//
+// The number of words is a const generic so that the common case (at most 64
+// move classes) uses a single `u64` without any heap allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct MoveClassMask<const NUM_WORDS: usize>([u64; NUM_WORDS]);
+
+impl<const NUM_WORDS: usize> Default for MoveClassMask<NUM_WORDS> {
+    fn default() -> Self {
+        Self([0; NUM_WORDS])
     }
 }
 
-impl BitOrAssign for MoveClassMask {
-    fn bitor_assign(&mut self, rhs: Self) {
-        self.0 = self.0 | rhs.0
+impl<const NUM_WORDS: usize> MoveClassMask<NUM_WORDS> {
+    fn all(num_move_classes: usize) -> Self {
+        let mut mask = Self::default();
+        for move_class_index in 0..num_move_classes {
+            mask.insert(MoveClassIndex(move_class_index));
+        }
+        mask
+    }
+
+    fn contains(&self, move_class_index: MoveClassIndex) -> bool {
+        (self.0[*move_class_index / MOVE_CLASS_MASK_WORD_BITS]
+            >> (*move_class_index % MOVE_CLASS_MASK_WORD_BITS))
+            & 1
+            != 0
+    }
+
+    fn insert(&mut self, move_class_index: MoveClassIndex) {
+        self.0[*move_class_index / MOVE_CLASS_MASK_WORD_BITS] |=
+            1 << (*move_class_index % MOVE_CLASS_MASK_WORD_BITS);
+    }
+
+    fn remove(&mut self, move_class_index: MoveClassIndex) {
+        self.0[*move_class_index / MOVE_CLASS_MASK_WORD_BITS] &=
+            !(1 << (*move_class_index % MOVE_CLASS_MASK_WORD_BITS));
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] & other.0[i]))
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        self.0.iter().zip(other.0.iter()).any(|(a, b)| a & b != 0)
+    }
+
+    // Whether any move class with a greater index than `move_class_index` is in the mask.
+    fn contains_any_after(&self, move_class_index: MoveClassIndex) -> bool {
+        let word_index = *move_class_index / MOVE_CLASS_MASK_WORD_BITS;
+        let bit_index = *move_class_index % MOVE_CLASS_MASK_WORD_BITS;
+        // Shift in two steps, since shifting a `u64` by 64 overflows.
+        (self.0[word_index] >> bit_index) >> 1 != 0
+            || self.0[word_index + 1..].iter().any(|word| *word != 0)
     }
 }
 
 whole_number_newtype!(CanonicalFSMState, usize);
 
-pub(crate) const CANONICAL_FSM_START_STATE: CanonicalFSMState = CanonicalFSMState(0);
+pub const CANONICAL_FSM_START_STATE: CanonicalFSMState = CanonicalFSMState(0);
 pub(crate) const ILLEGAL_FSM_STATE: CanonicalFSMState = CanonicalFSMState(0xFFFFFFFF);
 
 #[derive(Default, Debug)]
-struct MaskToState(HashMap<MoveClassMask, CanonicalFSMState>);
+struct MaskToState<const NUM_WORDS: usize>(HashMap<MoveClassMask<NUM_WORDS>, CanonicalFSMState>);
 
-impl MaskToState {
-    pub fn insert(&mut self, mask: MoveClassMask, state: CanonicalFSMState) {
+impl<const NUM_WORDS: usize> MaskToState<NUM_WORDS> {
+    pub fn insert(&mut self, mask: MoveClassMask<NUM_WORDS>, state: CanonicalFSMState) {
         self.0.insert(mask, state);
     }
 
-    pub fn get(&self, mask: MoveClassMask) -> Option<CanonicalFSMState> {
+    pub fn get(&self, mask: MoveClassMask<NUM_WORDS>) -> Option<CanonicalFSMState> {
         self.0.get(&mask).copied() // TODO: figure out how to do this safely and most performantly
     }
 }
 
 #[derive(Default, Debug)]
-struct StateToMask(IndexedVec<CanonicalFSMState, MoveClassMask>);
+struct StateToMask<const NUM_WORDS: usize>(IndexedVec<CanonicalFSMState, MoveClassMask<NUM_WORDS>>);
 
-impl StateToMask {
-    pub fn new(initial_value: MoveClassMask) -> StateToMask {
+impl<const NUM_WORDS: usize> StateToMask<NUM_WORDS> {
+    pub fn new(initial_value: MoveClassMask<NUM_WORDS>) -> Self {
         Self(IndexedVec::new(vec![initial_value]))
     }
 
     // Push the next value (useful while constructing in order.)
-    pub fn push(&mut self, mask: MoveClassMask) {
+    pub fn push(&mut self, mask: MoveClassMask<NUM_WORDS>) {
         self.0.push(mask);
     }
 
-    pub fn set(&mut self, state: CanonicalFSMState, value: MoveClassMask) {
+    pub fn set(&mut self, state: CanonicalFSMState, value: MoveClassMask<NUM_WORDS>) {
         self.0.set(state, value);
     }
 
-    pub fn get(&self, state: CanonicalFSMState) -> MoveClassMask {
+    pub fn get(&self, state: CanonicalFSMState) -> MoveClassMask<NUM_WORDS> {
         *self.0.at(state)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct CanonicalFSM<TPuzzle: SemiGroupActionPuzzle> {
     // disallowed_move_classes, indexed by state ordinal, holds the set of move classes that should
     // not be made from this state.
     // disallowed_move_classes: StateToMask,
-    pub(crate) next_state_lookup:
-        IndexedVec<CanonicalFSMState, IndexedVec<MoveClassIndex, CanonicalFSMState>>,
+    pub(crate) next_state_lookup: NextStateLookup,
 
     phantom_data: PhantomData<TPuzzle>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct CanonicalFSMConstructionOptions {
     pub forbid_transitions_by_quantums_either_direction: HashSet<(QuantumMove, QuantumMove)>,
 }
@@ -111,6 +154,8 @@ impl CanonicalFSMConstructionOptions {
     }
 }
 
+type NextStateLookup = IndexedVec<CanonicalFSMState, IndexedVec<MoveClassIndex, CanonicalFSMState>>;
+
 impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
     // TODO: Return a more specific error.
     /// Pass `Default::default()` as for `options` when no options are needed.
@@ -122,14 +167,41 @@ impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
         let num_move_classes = generators.by_move_class.len();
         if num_move_classes > MAX_NUM_MOVE_CLASSES {
             return Err(SearchError {
-                description: "Too many move classes!".to_owned(),
+                description: format!(
+                    "Too many move classes! ({} move classes, the maximum is {})",
+                    num_move_classes, MAX_NUM_MOVE_CLASSES
+                ),
             });
         }
 
-        let mut commutes: Vec<MoveClassMask> =
-            vec![MoveClassMask((1 << num_move_classes) - 1); num_move_classes];
-        let mut forbidden_transitions: Vec<MoveClassMask> =
-            vec![MoveClassMask(0); num_move_classes];
+        let next_state_lookup = match num_move_classes.div_ceil(MOVE_CLASS_MASK_WORD_BITS) {
+            0 | 1 => Self::build_next_state_lookup::<1>(&tpuzzle, &generators, &options),
+            2 => Self::build_next_state_lookup::<2>(&tpuzzle, &generators, &options),
+            3 | 4 => Self::build_next_state_lookup::<4>(&tpuzzle, &generators, &options),
+            5..=8 => Self::build_next_state_lookup::<8>(&tpuzzle, &generators, &options),
+            _ => Self::build_next_state_lookup::<MAX_NUM_MOVE_CLASS_MASK_WORDS>(
+                &tpuzzle,
+                &generators,
+                &options,
+            ),
+        };
+
+        Ok(Self {
+            next_state_lookup,
+            phantom_data: PhantomData,
+        })
+    }
+
+    fn build_next_state_lookup<const NUM_WORDS: usize>(
+        tpuzzle: &TPuzzle,
+        generators: &SearchGenerators<TPuzzle>,
+        options: &CanonicalFSMConstructionOptions,
+    ) -> NextStateLookup {
+        let num_move_classes = generators.by_move_class.len();
+        let mut commutes: Vec<MoveClassMask<NUM_WORDS>> =
+            vec![MoveClassMask::all(num_move_classes); num_move_classes];
+        let mut forbidden_transitions: Vec<MoveClassMask<NUM_WORDS>> =
+            vec![MoveClassMask::default(); num_move_classes];
 
         // Written this way so if we later iterate over all moves instead of
         // all move classes. This is because multiples can commute differently than their quantum values.
@@ -144,36 +216,33 @@ impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
                 let move1_info = &generators.by_move_class.at(i)[0];
                 let move2_info = &generators.by_move_class.at(j)[0];
                 if !tpuzzle.do_moves_commute(move1_info, move2_info) {
-                    commutes[*i] &= MoveClassMask(!(1 << *j));
-                    commutes[*j] &= MoveClassMask(!(1 << *i));
+                    commutes[*i].remove(j);
+                    commutes[*j].remove(i);
                 }
                 if options.is_transition_forbidden(move1_info, move2_info) {
-                    forbidden_transitions[*i] |= MoveClassMask(1 << *j);
-                    forbidden_transitions[*j] |= MoveClassMask(1 << *i);
+                    forbidden_transitions[*i].insert(j);
+                    forbidden_transitions[*j].insert(i);
                 }
             }
         }
 
-        let mut next_state_lookup: IndexedVec<
-            CanonicalFSMState,
-            IndexedVec<MoveClassIndex, CanonicalFSMState>,
-        > = IndexedVec::default();
+        let mut next_state_lookup = NextStateLookup::default();
 
-        let mut mask_to_state = MaskToState::default();
-        mask_to_state.insert(MoveClassMask(0), CANONICAL_FSM_START_STATE);
-        let mut state_to_mask = StateToMask::new(MoveClassMask(0));
+        let mut mask_to_state = MaskToState::<NUM_WORDS>::default();
+        mask_to_state.insert(MoveClassMask::default(), CANONICAL_FSM_START_STATE);
+        let mut state_to_mask = StateToMask::<NUM_WORDS>::new(MoveClassMask::default());
         // state_to_mask, indexed by state ordinal,  holds the set of move classes in the
         // move sequence so far for which there has not been a subsequent move that does not
         // commute with that move.
-        let mut disallowed_move_classes = StateToMask::new(MoveClassMask(0));
+        let mut disallowed_move_classes = StateToMask::<NUM_WORDS>::new(MoveClassMask::default());
 
         let mut queue_index: CanonicalFSMState = CANONICAL_FSM_START_STATE;
         while Into::<usize>::into(queue_index) < state_to_mask.0.len() {
             let mut next_state: IndexedVec<MoveClassIndex, CanonicalFSMState> =
                 IndexedVec::new(vec![ILLEGAL_FSM_STATE; num_move_classes]);
 
-            let dequeue_move_class_mask: MoveClassMask = state_to_mask.get(queue_index);
-            disallowed_move_classes.push(MoveClassMask(0));
+            let dequeue_move_class_mask = state_to_mask.get(queue_index);
+            disallowed_move_classes.push(MoveClassMask::default());
 
             queue_index += CanonicalFSMState(1);
             let from_state = queue_index;
@@ -183,36 +252,36 @@ impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
                 // If there's a greater move (multiple) in the state that
                 // commutes with this move's `move_class`, we can't move
                 // `move_class`.
-                skip |= (*dequeue_move_class_mask & *commutes[*move_class_index])
-                    >> (*move_class_index + 1)
-                    != 0;
-                skip |= (*dequeue_move_class_mask & *forbidden_transitions[*move_class_index]) != 0;
-                skip |= ((*dequeue_move_class_mask >> *move_class_index) & 1) != 0;
+                skip |= dequeue_move_class_mask
+                    .intersection(&commutes[*move_class_index])
+                    .contains_any_after(move_class_index);
+                skip |=
+                    dequeue_move_class_mask.intersects(&forbidden_transitions[*move_class_index]);
+                skip |= dequeue_move_class_mask.contains(move_class_index);
                 if skip {
-                    let new_value = MoveClassMask(
-                        *disallowed_move_classes.get(from_state) | (1 << *move_class_index),
-                    );
+                    let mut new_value = disallowed_move_classes.get(from_state);
+                    new_value.insert(move_class_index);
                     disallowed_move_classes.set(from_state, new_value);
                     continue;
                 }
-                // TODO implement bit arithmetic for whole number newtypes.
-                let mut next_state_bits = (*dequeue_move_class_mask & *commutes[*move_class_index])
-                    | (1 << *move_class_index);
+                let mut next_move_mask_class =
+                    dequeue_move_class_mask.intersection(&commutes[*move_class_index]);
+                next_move_mask_class.insert(move_class_index);
                 // If a pair of bits are set with the same commutating moves, we
                 // can clear out the higher ones. This optimization keeps the
                 // state count from going exponential for very big cubes.
                 for i in 0..num_move_classes {
-                    if (next_state_bits >> i) & 1 != 0 {
-                        for j in (i + 1)..num_move_classes {
-                            if ((next_state_bits >> j) & 1) != 0 && commutes[i] == commutes[j] {
-                                next_state_bits &= !(1 << i);
+                    let i = MoveClassIndex(i);
+                    if next_move_mask_class.contains(i) {
+                        for j in (*i + 1)..num_move_classes {
+                            let j = MoveClassIndex(j);
+                            if next_move_mask_class.contains(j) && commutes[*i] == commutes[*j] {
+                                next_move_mask_class.remove(i);
                             }
                         }
                     }
                 }
 
-                let next_move_mask_class = MoveClassMask(next_state_bits);
-
                 next_state.set(
                     move_class_index,
                     match mask_to_state.get(next_move_mask_class) {
@@ -229,13 +298,10 @@ impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
             next_state_lookup.push(next_state);
         }
 
-        Ok(Self {
-            next_state_lookup,
-            phantom_data: PhantomData,
-        })
+        next_state_lookup
     }
 
-    pub(crate) fn next_state(
+    pub fn next_state(
         &self,
         current_fsm_state: CanonicalFSMState,
         move_class_index: MoveClassIndex,
@@ -250,3 +316,134 @@ impl<TPuzzle: SemiGroupActionPuzzle> CanonicalFSM<TPuzzle> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cubing::{
+        alg::Move,
+        kpuzzle::{KPuzzle, KPuzzleDefinition},
+        puzzles::cube3x3x3_kpuzzle,
+    };
+    use serde_json::json;
+
+    use crate::_internal::{
+        canonical_fsm::search_generators::SearchGenerators, cli::args::MetricEnum,
+    };
+
+    use super::{CanonicalFSM, CanonicalFSMState, MoveClassIndex, CANONICAL_FSM_START_STATE};
+
+    // Move families can't contain digits (`M1` is the move `M` with amount 1).
+    fn move_family(i: usize) -> String {
+        format!(
+            "X{}{}",
+            (b'a' + (i / 26) as u8) as char,
+            (b'a' + (i % 26) as u8) as char
+        )
+    }
+
+    // Counts the canonical sequences of exactly `depth` moves.
+    fn num_canonical_sequences(
+        canonical_fsm: &CanonicalFSM<KPuzzle>,
+        num_move_classes: usize,
+        state: CanonicalFSMState,
+        depth: usize,
+    ) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        (0..num_move_classes)
+            .filter_map(|i| canonical_fsm.next_state(state, MoveClassIndex(i)))
+            .map(|next_state| {
+                num_canonical_sequences(canonical_fsm, num_move_classes, next_state, depth - 1)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn canonical_fsm_more_than_64_move_classes_test() {
+        // Each move swaps its own pair of pieces, so all moves commute.
+        const NUM_MOVES: usize = 70;
+        let mut moves = serde_json::Map::new();
+        for i in 0..NUM_MOVES {
+            let mut permutation: Vec<usize> = (0..NUM_MOVES * 2).collect();
+            permutation.swap(2 * i, 2 * i + 1);
+            moves.insert(
+                move_family(i),
+                json!({ "PIECES": { "permutation": permutation, "orientationDelta": vec![0; NUM_MOVES * 2] } }),
+            );
+        }
+        let def: KPuzzleDefinition = serde_json::from_value(json!({
+            "name": "many_commuting_moves",
+            "orbits": [{ "orbitName": "PIECES", "numPieces": NUM_MOVES * 2, "numOrientations": 1 }],
+            "defaultPattern": { "PIECES": {
+                "pieces": (0..NUM_MOVES * 2).collect::<Vec<usize>>(),
+                "orientation": vec![0; NUM_MOVES * 2]
+            } },
+            "moves": moves,
+        }))
+        .unwrap();
+        let kpuzzle = KPuzzle::try_new(def).unwrap();
+        let generator_moves: Vec<Move> = (0..NUM_MOVES)
+            .map(|i| move_family(i).parse().unwrap())
+            .collect();
+        let search_generators =
+            SearchGenerators::try_new(&kpuzzle, generator_moves, &MetricEnum::Hand, false).unwrap();
+        let canonical_fsm =
+            CanonicalFSM::try_new(kpuzzle, search_generators, Default::default()).unwrap();
+
+        // Commuting moves must be made in a fixed order, so each canonical sequence is a subset of the moves.
+        for (depth, expected) in [(1, 70), (2, 70 * 69 / 2), (3, 70 * 69 * 68 / 6)] {
+            assert_eq!(
+                num_canonical_sequences(
+                    &canonical_fsm,
+                    NUM_MOVES,
+                    CANONICAL_FSM_START_STATE,
+                    depth
+                ),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_fsm_axial_metric_test() {
+        let kpuzzle = cube3x3x3_kpuzzle().clone();
+        let generator_moves: Vec<Move> = ["U", "L", "F", "R", "B", "D", "x", "Uv"]
+            .into_iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        let search_generators = SearchGenerators::try_new(
+            &kpuzzle,
+            generator_moves.clone(),
+            &MetricEnum::SliceTurn,
+            false,
+        )
+        .unwrap();
+        // Rotations are not used.
+        assert_eq!(search_generators.by_move_class.len(), 6);
+
+        let search_generators =
+            SearchGenerators::try_new(&kpuzzle, generator_moves, &MetricEnum::Axial, false)
+                .unwrap();
+        // Each axis has 4 × 4 - 1 = 15 combinations of turns of its two faces.
+        assert_eq!(search_generators.by_move_class.len(), 3);
+        for (_, move_multiples) in search_generators.by_move_class.iter() {
+            assert_eq!(move_multiples.len(), 15);
+        }
+        assert!(search_generators
+            .flat
+            .iter()
+            .any(|(_, info)| info.alg_node.to_string() == "(U D')"));
+
+        // Each move is a turn of a different axis than the previous one.
+        let canonical_fsm =
+            CanonicalFSM::try_new(kpuzzle, search_generators, Default::default()).unwrap();
+        for (depth, expected) in [(1, 3), (2, 3 * 2), (3, 3 * 2 * 2)] {
+            assert_eq!(
+                num_canonical_sequences(&canonical_fsm, 3, CANONICAL_FSM_START_STATE, depth),
+                expected
+            );
+        }
+    }
+}
@@ -1,7 +1,7 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use cubing::{
-    alg::{Move, QuantumMove},
+    alg::{Alg, AlgNode, Grouping, Move, QuantumMove},
     kpuzzle::InvalidAlgError,
 };
 
@@ -10,7 +10,7 @@ use crate::{
         cli::args::MetricEnum,
         errors::SearchError,
         puzzle_traits::puzzle_traits::SemiGroupActionPuzzle,
-        search::{indexed_vec::IndexedVec, move_count::MoveCount},
+        search::{indexed_vec::IndexedVec, move_count::MoveCount, prune_table_trait::Depth},
     },
     whole_number_newtype,
 };
@@ -23,10 +23,16 @@ whole_number_newtype!(FlatMoveIndex, usize);
 pub struct MoveTransformationInfo<
     TPuzzle: SemiGroupActionPuzzle, // TODO = KPuzzle
 > {
-    #[allow(dead_code)] // TODO
+    /// For alg generators, this is a placeholder move that identifies the alg
+    /// multiple (its family is the alg in parentheses).
     pub r#move: Move,
+    /// How this generator is written in solutions: the move itself, or a
+    /// grouping like `(R U R' U')2` for alg generators.
+    pub alg_node: AlgNode,
     // move_class: MoveClass, // TODO: do we need this?
-    // pub metric_turns: i32,
+    /// The number of depth units that this move uses in a search (1 unless
+    /// custom move costs are set using [`SearchGenerators::set_move_costs`]).
+    pub cost: Depth,
     pub transformation: TPuzzle::Transformation,
     // #[allow(dead_code)] // TODO
     // pub inverse_transformation: TPuzzle::Transformation,
@@ -49,6 +55,147 @@ pub struct SearchGenerators<
     pub by_move: HashMap<Move, MoveTransformationInfo<TPuzzle>>, // TODO: avoid duplicate data
 }
 
+// The amounts of each multiple of a generator with the given order, in the order they are searched.
+fn multiple_amounts(metric: &MetricEnum, order: MoveCount, original_amount: i32) -> Vec<i32> {
+    match (metric, order) {
+        (MetricEnum::Hand | MetricEnum::SliceTurn | MetricEnum::Axial, order) => {
+            let mod_amount = (order.0 as i32) * original_amount;
+            let max_positive_amount = (order.0 as i32) / 2;
+            (original_amount..=mod_amount - original_amount)
+                .step_by(original_amount as usize)
+                .map(|amount| {
+                    if amount > max_positive_amount {
+                        amount - mod_amount
+                    } else {
+                        amount
+                    }
+                })
+                .collect()
+        }
+        (MetricEnum::Quantum, MoveCount(2) | MoveCount(1)) => vec![1],
+        (MetricEnum::Quantum, _) => vec![1, -1],
+    }
+}
+
+// Rotations are written as `x`, `y`, or `z`, or as one or more upper case
+// letters or underscores followed by `v` (e.g. `Uv` or `FRBv`).
+fn is_rotation(r#move: &Move) -> bool {
+    let family = r#move.quantum.family.as_str();
+    matches!(family, "x" | "y" | "z")
+        || family.strip_suffix('v').is_some_and(|prefix| {
+            !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+        })
+}
+
+fn transformation_from_moves<TPuzzle: SemiGroupActionPuzzle>(
+    tpuzzle: &TPuzzle,
+    moves: Vec<AlgNode>,
+) -> Result<TPuzzle::Transformation, SearchError> {
+    let alg = Alg { nodes: moves };
+    tpuzzle
+        .puzzle_transformation_from_alg(&alg)
+        .map_err(|_| SearchError {
+            description: format!("Could not get transformation for axial move: {}", alg),
+        })
+}
+
+// Groups moves into axes, where moves are on the same axis if they commute
+// with exactly the same moves. Since every move commutes with itself, all the
+// moves on an axis commute with each other. Unlike grouping by pairwise
+// commutation, this does not depend on the order of the moves.
+fn group_moves_by_axis<TPuzzle: SemiGroupActionPuzzle>(
+    tpuzzle: &TPuzzle,
+    moves: Vec<Move>,
+) -> Result<Vec<Vec<Move>>, SearchError> {
+    let move_nodes: Vec<AlgNode> = moves
+        .iter()
+        .map(|r#move| AlgNode::MoveNode(r#move.clone()))
+        .collect();
+    let mut commuting_moves = vec![vec![true; moves.len()]; moves.len()];
+    for (i, move_node) in move_nodes.iter().enumerate() {
+        for (j, other_move_node) in move_nodes.iter().enumerate().skip(i + 1) {
+            let commutes = transformation_from_moves(
+                tpuzzle,
+                vec![move_node.clone(), other_move_node.clone()],
+            )? == transformation_from_moves(
+                tpuzzle,
+                vec![other_move_node.clone(), move_node.clone()],
+            )?;
+            commuting_moves[i][j] = commutes;
+            commuting_moves[j][i] = commutes;
+        }
+    }
+    let mut axes: Vec<(Vec<bool>, Vec<Move>)> = vec![];
+    for (r#move, commuting_moves) in moves.into_iter().zip(commuting_moves) {
+        match axes
+            .iter_mut()
+            .find(|(axis_commuting_moves, _)| *axis_commuting_moves == commuting_moves)
+        {
+            Some((_, axis)) => axis.push(r#move),
+            None => axes.push((commuting_moves, vec![r#move])),
+        }
+    }
+    Ok(axes.into_iter().map(|(_, axis)| axis).collect())
+}
+
+// Every distinct (non-identity) transformation that can be made by turning the moves
+// of an axis simultaneously. Combinations of multiple moves use a placeholder move
+// (like alg generators), and are written as a grouping like `(U D')`.
+#[allow(clippy::type_complexity)] // TODO
+fn axial_multiples<TPuzzle: SemiGroupActionPuzzle>(
+    tpuzzle: &TPuzzle,
+    axis: &[Move],
+) -> Result<Vec<(Move, AlgNode, TPuzzle::Transformation)>, SearchError> {
+    let mut combinations: Vec<(Vec<AlgNode>, TPuzzle::Transformation)> =
+        vec![(vec![], transformation_from_moves(tpuzzle, vec![])?)];
+    for r#move in axis {
+        let Ok(order) = tpuzzle.move_order(r#move) else {
+            return Err(SearchError {
+                description: format!("Could not calculate order for move quantum: {}", r#move),
+            });
+        };
+        let mut next_combinations = combinations.clone();
+        for (alg_nodes, _) in &combinations {
+            for amount in multiple_amounts(&MetricEnum::Hand, order, r#move.amount) {
+                let mut alg_nodes = alg_nodes.clone();
+                alg_nodes.push(AlgNode::MoveNode(Move {
+                    quantum: r#move.quantum.clone(),
+                    amount,
+                }));
+                let transformation = transformation_from_moves(tpuzzle, alg_nodes.clone())?;
+                if !next_combinations
+                    .iter()
+                    .any(|(_, existing)| *existing == transformation)
+                {
+                    next_combinations.push((alg_nodes, transformation));
+                }
+            }
+        }
+        combinations = next_combinations;
+    }
+
+    // The first combination is the identity.
+    Ok(combinations
+        .into_iter()
+        .skip(1)
+        .map(|(alg_nodes, transformation)| {
+            if let [AlgNode::MoveNode(r#move)] = alg_nodes.as_slice() {
+                return (r#move.clone(), alg_nodes[0].clone(), transformation);
+            }
+            let alg = Alg { nodes: alg_nodes };
+            let r#move = Move {
+                quantum: Arc::new(QuantumMove::new(format!("({})", alg), None)),
+                amount: 1,
+            };
+            let alg_node = AlgNode::GroupingNode(Grouping {
+                alg: Arc::new(alg),
+                amount: 1,
+            });
+            (r#move, alg_node, transformation)
+        })
+        .collect())
+}
+
 impl<TPuzzle: SemiGroupActionPuzzle> SearchGenerators<TPuzzle> {
     pub fn try_new(
         tpuzzle: &TPuzzle,
@@ -56,15 +203,21 @@ impl<TPuzzle: SemiGroupActionPuzzle> SearchGenerators<TPuzzle> {
         metric: &MetricEnum,
         random_start: bool,
     ) -> Result<SearchGenerators<TPuzzle>, SearchError> {
-        let mut seen_moves = HashMap::<QuantumMove, Move>::new();
+        Self::try_new_with_alg_generators(tpuzzle, moves, vec![], metric, random_start)
+    }
 
-        // TODO: actually calculate GCDs
-        let mut by_move_class =
-            IndexedVec::<MoveClassIndex, MoveTransformationMultiples<TPuzzle>>::default();
-        let mut flat = IndexedVec::<FlatMoveIndex, MoveTransformationInfo<TPuzzle>>::default();
-        let mut by_move = HashMap::<Move, MoveTransformationInfo<TPuzzle>>::default();
-        for (move_class_index, r#move) in moves.into_iter().enumerate() {
-            let move_class_index = MoveClassIndex(move_class_index);
+    /// Each alg is treated as a single (atomic) move, with multiples calculated
+    /// from its order just like for moves. Each alg gets its own move class,
+    /// after the move classes for `moves`.
+    pub fn try_new_with_alg_generators(
+        tpuzzle: &TPuzzle,
+        moves: Vec<Move>,
+        algs: Vec<Alg>,
+        metric: &MetricEnum,
+        random_start: bool,
+    ) -> Result<SearchGenerators<TPuzzle>, SearchError> {
+        let mut seen_moves = HashMap::<QuantumMove, Move>::new();
+        for r#move in &moves {
             if let Some(existing) = seen_moves.get(&r#move.quantum) {
                 // TODO: deduplicate by quantum move.
                 println!(
@@ -74,56 +227,131 @@ impl<TPuzzle: SemiGroupActionPuzzle> SearchGenerators<TPuzzle> {
             } else {
                 seen_moves.insert(r#move.quantum.as_ref().clone(), r#move.clone());
             }
+        }
 
-            let Ok(order) = tpuzzle.move_order(&r#move) else {
-                return Err(SearchError {
-                    description: format!("Could not calculate order for move quantum: {}", r#move),
-                });
-            };
+        let moves: Vec<Move> = match metric {
+            MetricEnum::SliceTurn | MetricEnum::Axial => {
+                let (rotations, moves): (Vec<Move>, Vec<Move>) =
+                    moves.into_iter().partition(is_rotation);
+                if !rotations.is_empty() {
+                    println!(
+                        "Warning: the {} metric does not use rotations, so these generators are ignored: {}",
+                        metric,
+                        rotations
+                            .iter()
+                            .map(|r#move| r#move.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                moves
+            }
+            MetricEnum::Hand | MetricEnum::Quantum => moves,
+        };
+        let move_classes: Vec<Vec<Move>> = match metric {
+            MetricEnum::Axial => group_moves_by_axis(tpuzzle, moves)?,
+            _ => moves.into_iter().map(|r#move| vec![r#move]).collect(),
+        };
 
+        // TODO: actually calculate GCDs
+        let mut by_move_class =
+            IndexedVec::<MoveClassIndex, MoveTransformationMultiples<TPuzzle>>::default();
+        let mut flat = IndexedVec::<FlatMoveIndex, MoveTransformationInfo<TPuzzle>>::default();
+        let mut by_move = HashMap::<Move, MoveTransformationInfo<TPuzzle>>::default();
+        for move_class in move_classes {
+            let move_class_index = MoveClassIndex(by_move_class.len());
             let mut multiples = MoveTransformationMultiples::default(); // TODO: use order to set capacity.
+            if let [r#move] = move_class.as_slice() {
+                let Ok(order) = tpuzzle.move_order(r#move) else {
+                    return Err(SearchError {
+                        description: format!(
+                            "Could not calculate order for move quantum: {}",
+                            r#move
+                        ),
+                    });
+                };
 
-            // TODO: this should be an `Iterator` instead of a `Vec`, but this requires some type wrangling.
-            let amount_iterator: Vec<i32> = match (metric, order) {
-                (MetricEnum::Hand, order) => {
-                    let original_amount = r#move.amount;
-                    let mod_amount = (order.0 as i32) * original_amount;
-                    let max_positive_amount = (order.0 as i32) / 2;
-                    (original_amount..=mod_amount - original_amount)
-                        .step_by(original_amount as usize)
-                        .map(|amount| {
-                            if amount > max_positive_amount {
-                                amount - mod_amount
-                            } else {
-                                amount
-                            }
-                        })
-                        .collect()
+                // TODO: we've given up O(log(average move order)) performance here to make this
+                // generic. If this is ever an issue, we can special-case more efficient
+                // calculations.
+                for amount in multiple_amounts(metric, order, r#move.amount) {
+                    let move_multiple = Move {
+                        quantum: r#move.quantum.clone(),
+                        amount,
+                    };
+                    let Ok(transformation) =
+                        tpuzzle.puzzle_transformation_from_move(&move_multiple)
+                    else {
+                        return Err(SearchError {
+                            description: format!(
+                                "Could not get transformation for move multiple: {}",
+                                move_multiple
+                            ),
+                        });
+                    };
+                    let info = MoveTransformationInfo {
+                        r#move: move_multiple.clone(),
+                        alg_node: AlgNode::MoveNode(move_multiple.clone()),
+                        cost: Depth(1),
+                        transformation,
+                        flat_move_index: FlatMoveIndex(flat.len()),
+                        move_class_index,
+                    };
+                    multiples.push(info.clone());
+                    flat.push(info.clone());
+                    by_move.insert(move_multiple, info);
                 }
-                (MetricEnum::Quantum, MoveCount(2) | MoveCount(1)) => vec![1],
-                (MetricEnum::Quantum, _) => vec![1, -1],
+            } else {
+                for (r#move, alg_node, transformation) in axial_multiples(tpuzzle, &move_class)? {
+                    let info = MoveTransformationInfo {
+                        r#move: r#move.clone(),
+                        alg_node,
+                        cost: Depth(1),
+                        transformation,
+                        flat_move_index: FlatMoveIndex(flat.len()),
+                        move_class_index,
+                    };
+                    multiples.push(info.clone());
+                    flat.push(info.clone());
+                    by_move.insert(r#move, info);
+                }
+            }
+            by_move_class.push(multiples);
+        }
+        for alg in algs {
+            let move_class_index = MoveClassIndex(by_move_class.len());
+            let Ok(order) = tpuzzle.alg_order(&alg) else {
+                return Err(SearchError {
+                    description: format!("Could not calculate order for alg generator: {}", alg),
+                });
             };
+            let alg = Arc::new(alg);
+            let quantum = Arc::new(QuantumMove::new(format!("({})", alg), None));
 
-            // TODO: we've given up O(log(average move order)) performance here to make this
-            // generic. If this is ever an issue, we can special-case more efficient
-            // calculations.
-            for amount in amount_iterator {
-                let move_multiple = Move {
-                    quantum: r#move.quantum.clone(),
+            let mut multiples = MoveTransformationMultiples::default();
+            for amount in multiple_amounts(metric, order, 1) {
+                let alg_node = AlgNode::GroupingNode(Grouping {
+                    alg: alg.clone(),
                     amount,
-                };
-                let Ok(transformation) = tpuzzle.puzzle_transformation_from_move(&move_multiple)
-                else {
+                });
+                let Ok(transformation) = tpuzzle.puzzle_transformation_from_alg(&Alg {
+                    nodes: vec![alg_node.clone()],
+                }) else {
                     return Err(SearchError {
                         description: format!(
-                            "Could not get transformation for move multiple: {}",
-                            move_multiple
+                            "Could not get transformation for alg generator multiple: {}",
+                            alg_node
                         ),
                     });
                 };
+                let move_multiple = Move {
+                    quantum: quantum.clone(),
+                    amount,
+                };
                 let info = MoveTransformationInfo {
                     r#move: move_multiple.clone(),
-                    // metric_turns: 1, // TODO
+                    alg_node,
+                    cost: Depth(1),
                     transformation,
                     flat_move_index: FlatMoveIndex(flat.len()),
                     move_class_index,
@@ -150,6 +378,140 @@ impl<TPuzzle: SemiGroupActionPuzzle> SearchGenerators<TPuzzle> {
         })
     }
 
+    /// Sets the cost of each move multiple. A cost for a move with amount 1
+    /// (like `R`) also applies to any of its other multiples that don't have
+    /// their own cost (like `R'`). Moves without a cost keep a cost of 1.
+    ///
+    /// The costs must be consistent with how the search uses them:
+    ///
+    /// - Prune tables measure distances by applying moves to the target
+    ///   patterns, while solutions undo those moves. So a move and its inverse
+    ///   must have the same cost (e.g. `R=1,R'=3` is rejected).
+    /// - Solutions never use two consecutive moves from the same move class. So
+    ///   a multiple may not cost more than two multiples of the same class that
+    ///   combine to it (e.g. `R=1,R2=5` is rejected, since `R R` costs 2).
+    pub fn set_move_costs(
+        &mut self,
+        tpuzzle: &TPuzzle,
+        move_costs: &HashMap<Move, Depth>,
+    ) -> Result<(), SearchError> {
+        for (r#move, cost) in move_costs {
+            if *cost == Depth(0) {
+                return Err(SearchError {
+                    description: format!(
+                        "Move costs must be positive (found: {}={})",
+                        r#move, cost.0
+                    ),
+                });
+            }
+            let is_generator = self.by_move.contains_key(r#move)
+                || (r#move.amount == 1
+                    && self
+                        .by_move
+                        .keys()
+                        .any(|generator_move| generator_move.quantum == r#move.quantum));
+            if !is_generator {
+                return Err(SearchError {
+                    description: format!(
+                        "Move cost specified for a move that is not a generator: {}",
+                        r#move
+                    ),
+                });
+            }
+        }
+
+        let cost_for_move = |r#move: &Move| -> Depth {
+            move_costs
+                .get(r#move)
+                .or_else(|| {
+                    move_costs.get(&Move {
+                        quantum: r#move.quantum.clone(),
+                        amount: 1,
+                    })
+                })
+                .copied()
+                .unwrap_or(Depth(1))
+        };
+        for move_transformation_multiples in self.by_move_class.0.iter_mut() {
+            for info in move_transformation_multiples {
+                info.cost = cost_for_move(&info.r#move);
+            }
+        }
+        for info in self.flat.0.iter_mut() {
+            info.cost = cost_for_move(&info.r#move);
+        }
+        for info in self.by_move.values_mut() {
+            info.cost = cost_for_move(&info.r#move);
+        }
+        if move_costs.is_empty() {
+            return Ok(());
+        }
+        for multiples in &self.by_move_class.0 {
+            self.check_move_class_costs(tpuzzle, multiples)?;
+        }
+        Ok(())
+    }
+
+    // See `set_move_costs` for the requirements.
+    fn check_move_class_costs(
+        &self,
+        tpuzzle: &TPuzzle,
+        multiples: &MoveTransformationMultiples<TPuzzle>,
+    ) -> Result<(), SearchError> {
+        let transformation_from_nodes = |nodes: Vec<AlgNode>| {
+            let alg = Alg { nodes };
+            tpuzzle
+                .puzzle_transformation_from_alg(&alg)
+                .map_err(|_| SearchError {
+                    description: format!("Could not get transformation for move costs: {}", alg),
+                })
+        };
+        let multiple_with_transformation = |transformation: &TPuzzle::Transformation| {
+            multiples
+                .iter()
+                .find(|info| info.transformation == *transformation)
+        };
+        for info in multiples {
+            let inverse_alg = Alg {
+                nodes: vec![info.alg_node.clone()],
+            }
+            .invert();
+            let inverse_transformation = transformation_from_nodes(inverse_alg.nodes)?;
+            if let Some(inverse_info) = multiple_with_transformation(&inverse_transformation) {
+                if inverse_info.cost != info.cost {
+                    return Err(SearchError {
+                        description: format!(
+                            "A move and its inverse must have the same cost (found: {}={}, {}={})",
+                            info.r#move, info.cost.0, inverse_info.r#move, inverse_info.cost.0
+                        ),
+                    });
+                }
+            }
+            for second_info in multiples {
+                let combined_transformation = transformation_from_nodes(vec![
+                    info.alg_node.clone(),
+                    second_info.alg_node.clone(),
+                ])?;
+                if let Some(combined_info) = multiple_with_transformation(&combined_transformation)
+                {
+                    if combined_info.cost > info.cost + second_info.cost {
+                        return Err(SearchError {
+                            description: format!(
+                                "A move may not cost more than two moves of the same kind that combine to it (found: {}={}, but {} {} costs {})",
+                                combined_info.r#move,
+                                combined_info.cost.0,
+                                info.r#move,
+                                second_info.r#move,
+                                (info.cost + second_info.cost).0
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     #[allow(clippy::type_complexity)] // TODO
     pub fn transfer_move_classes<
         TargetTPuzzle: SemiGroupActionPuzzle<Transformation = FlatMoveIndex>,
@@ -171,6 +533,8 @@ impl<TPuzzle: SemiGroupActionPuzzle> SearchGenerators<TPuzzle> {
                 r#move.clone(),
                 MoveTransformationInfo::<TargetTPuzzle> {
                     r#move: info.r#move.clone(),
+                    alg_node: info.alg_node.clone(),
+                    cost: info.cost,
                     transformation,
                     flat_move_index: info.flat_move_index,
                     move_class_index: info.move_class_index,
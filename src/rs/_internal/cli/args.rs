@@ -4,13 +4,19 @@ use clap_complete::{Generator, Shell};
 use cubing::alg::{Alg, Move};
 use cubing::kpuzzle::KPuzzle;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::io::stdout;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::time::Duration;
 
+use crate::_internal::errors::ArgumentError;
 use crate::_internal::puzzle_traits::puzzle_traits::GroupActionPuzzle;
+use crate::_internal::search::prune_table_persistence::{
+    default_cache_dir, PruneTablePersistenceOptions,
+};
 use crate::_internal::search::prune_table_trait::Depth;
 
 /// twsearch-cpp-wrapper — a native Rust wrapper for `twsearch` functionality.
@@ -32,6 +38,7 @@ pub struct TwsearchArgs {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // Only constructed once per invocation.
 pub enum CliCommand {
     /// Run a single search.
     Search(SearchCommandArgs),
@@ -39,10 +46,11 @@ pub enum CliCommand {
     /// Use with: <https://experiments.cubing.net/cubing.js/twsearch/text-ui.html>
     Serve(ServeCommandArgs),
 
-    // TOOD: Detect identical pieces and warn/error (and give advice on how to run the "same" search with fully-distinguishable pieces).
     /// Run the Schreier-Sims algorithm to calculate the number of reachable patterns.
     ///
-    /// Warning: Does NOT account for identical pieces.
+    /// Warning: Does NOT account for identical pieces. If the puzzle definition
+    /// has any, the count is for a version of the puzzle in which all pieces
+    /// are distinguishable (and a warning is printed).
     SchreierSims(SchreierSimsArgs),
     /// Enumerate the entire pattern graph and print antipodes.
     GodsAlgorithm(GodsAlgorithmArgs),
@@ -63,6 +71,7 @@ pub enum CliCommand {
 #[derive(Args, Debug, Default)]
 pub struct CommonSearchArgs {
     /// Check that a position is valid before attempting to solve it. This may take extra time or memory for large puzzles.
+    /// With `auto` (the default), the reachability check (Schreier–Sims) is skipped for large puzzles.
     #[clap(long/*, visible_alias = "checkbeforesolve" */)]
     pub check_before_solve: Option<EnableAutoAlwaysNeverValueEnum>,
 
@@ -89,10 +98,105 @@ pub struct CommonSearchArgs {
     #[clap(long/* , visible_alias = "maxdepth" */)]
     pub max_depth: Option<Depth>,
 
+    /// Stop the search after this many seconds.
+    #[clap(long, value_name = "SECONDS")]
+    pub time_limit_seconds: Option<f64>,
+
+    /// A comma-separated list of positive integer costs for generator moves,
+    /// like `R=1,R2=2,M=3`. A cost for a move like `M` also applies to its
+    /// other multiples (`M2`, `M'`) unless they have their own cost. Moves cost
+    /// 1 by default. Depths are measured in total cost, so the cheapest
+    /// solutions are found first. A move and its inverse must have the same
+    /// cost, and a move may not cost more than two moves of the same kind that
+    /// combine to it (like `R2` compared to `R R`).
+    #[clap(long)]
+    pub move_costs: Option<String>,
+
+    /// A comma-separated list of whole-puzzle rotations or reflections (like
+    /// `x,y`). Symmetric patterns share prune table entries, which gives a
+    /// deeper prune table in the same amount of memory (at the cost of slower
+    /// prune table lookups). The generator moves (including their costs from
+    /// `--move-costs`) and the target pattern must be preserved by these
+    /// symmetries.
+    #[clap(long)]
+    pub symmetry_moves: Option<String>,
+
+    /// A mask pattern file for a prune table on a subset of the puzzle (e.g.
+    /// only the corners). This can be specified multiple times, in which case
+    /// the search uses the maximum depth from all the masked prune tables.
+    #[clap(long)]
+    pub prune_table_mask: Vec<PathBuf>,
+
+    /// Use a prune table with the exact distance for every pattern, which is
+    /// filled completely before the search starts. This is only feasible for
+    /// small puzzles (e.g. 2x2x2, Pyraminx, Skewb).
+    #[clap(long, conflicts_with_all = ["prune_table_mask", "symmetry_moves"])]
+    pub exact_prune_table: bool,
+
+    /// Search from both the search pattern and the target pattern, each to
+    /// half the depth, and join the two halves using a hash map. This uses no
+    /// prune table, which can help for deep searches on puzzles whose prune
+    /// tables are weak. The hash map is limited by `--memory-MiB`. Not
+    /// supported with move costs.
+    #[clap(long, conflicts_with_all = ["prune_table_mask", "symmetry_moves", "exact_prune_table"])]
+    pub bidirectional: bool,
+
     #[command(flatten)]
     pub performance_args: PerformanceArgs,
 }
 
+pub fn parse_time_limit_seconds(
+    time_limit_seconds: Option<f64>,
+) -> Result<Option<Duration>, ArgumentError> {
+    time_limit_seconds
+        .map(|time_limit_seconds| {
+            Duration::try_from_secs_f64(time_limit_seconds).map_err(|_| {
+                "Invalid time limit (must be a non-negative number of seconds).".into()
+            })
+        })
+        .transpose()
+}
+
+pub fn parse_move_costs(
+    move_costs: &Option<String>,
+) -> Result<HashMap<Move, Depth>, ArgumentError> {
+    let Some(move_costs) = move_costs else {
+        return Ok(HashMap::default());
+    };
+    move_costs
+        .split(',')
+        .map(|move_cost| {
+            let invalid_move_cost = || ArgumentError {
+                description: format!(
+                    "Invalid move cost (expected a move and a positive integer cost, like `R2=2`): {}",
+                    move_cost
+                ),
+            };
+            let (r#move, cost) = move_cost.split_once('=').ok_or_else(invalid_move_cost)?;
+            let r#move: Move = r#move.trim().parse().map_err(|_| invalid_move_cost())?;
+            let cost: usize = cost.trim().parse().map_err(|_| invalid_move_cost())?;
+            if cost == 0 {
+                return Err(invalid_move_cost());
+            }
+            Ok((r#move, Depth(cost)))
+        })
+        .collect()
+}
+
+pub fn parse_symmetry_moves(symmetry_moves: &Option<String>) -> Result<Vec<Move>, ArgumentError> {
+    let Some(symmetry_moves) = symmetry_moves else {
+        return Ok(vec![]);
+    };
+    symmetry_moves
+        .split(',')
+        .map(|r#move| {
+            r#move.trim().parse().map_err(|_| ArgumentError {
+                description: format!("Invalid symmetry move: {}", r#move),
+            })
+        })
+        .collect()
+}
+
 #[derive(Args, Debug)]
 pub struct SearchCommandArgs {
     #[command(flatten)]
@@ -100,6 +204,12 @@ pub struct SearchCommandArgs {
 
     #[command(flatten)]
     pub optional: SearchCommandOptionalArgs,
+
+    /// Write statistics about the search (nodes and prune table lookups per
+    /// depth, prune table size, timing) to this file as JSON once the search
+    /// has finished.
+    #[clap(long, value_name = "STATS_JSON_FILE")]
+    pub stats_json: Option<PathBuf>,
 }
 
 #[derive(Args, Debug, Default)]
@@ -151,9 +261,10 @@ pub struct GeneratorArgs {
     #[clap(long = "generator-moves")]
     pub generator_moves_string: Option<String>,
 
-    /// A comma-separated list of algs to use. All multiples of these
-    /// algs are considered. For example, `--algs U,F,R2` only permits
-    /// half-turns on R, and all possible turns on U and F.
+    /// A comma-separated list of algs to use. Each alg is treated as a single
+    /// move (for example, `--generator-algs "R U R' U',R' F R F'"`), and all
+    /// multiples of it are considered. Solutions show each alg as a grouping
+    /// like `(R U R' U')2`.
     #[clap(long)]
     pub generator_algs: Option<String>,
 }
@@ -165,18 +276,21 @@ pub enum Generators {
 }
 
 impl Generators {
+    /// Alg generators are not included. See [`Generators::algs`].
     pub fn enumerate_moves_for_kpuzzle(&self, kpuzzle: &KPuzzle) -> Vec<Move> {
-        if let Generators::Custom(custom_generators) = self {
-            if !custom_generators.algs.is_empty() {
-                eprintln!("WARNING: Alg generators are not implemented yet. Ignoring.");
-            }
-        };
-
         match self {
             Generators::Default => kpuzzle.puzzle_definition_all_moves(),
             Generators::Custom(generators) => generators.moves.clone(),
         }
     }
+
+    /// Algs that should be treated as single (atomic) moves.
+    pub fn algs(&self) -> Vec<Alg> {
+        match self {
+            Generators::Default => vec![],
+            Generators::Custom(generators) => generators.algs.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -219,11 +333,48 @@ fn parse_comma_separated<T: FromStr<Err = E>, E: Display>(
 
 #[derive(Args, Debug, Default)]
 pub struct SearchPersistenceArgs {
+    /// Write prune tables to the cache dir so that later runs can load them
+    /// instead of rebuilding them. `auto` only writes tables that take a while
+    /// to generate. Passing this or `--cache-dir` enables reading prune tables
+    /// from the cache dir.
     #[clap(long, help_heading = "Persistence"/* , visible_alias = "writeprunetables" */)]
     pub write_prune_tables: Option<EnableAutoAlwaysNeverValueEnum>,
 
+    /// Defaults to `$XDG_CACHE_HOME/twsearch` (or `~/.cache/twsearch`).
     #[clap(long, help_heading = "Persistence"/* , visible_alias = "cachedir" */)]
     pub cache_dir: Option<PathBuf>,
+
+    /// Map prune tables from the cache dir into memory (read-only) instead of
+    /// reading them, so that processes using the same table share one copy
+    /// in the page cache. A table is only copied into memory if it needs to
+    /// be extended. Enables reading prune tables from the cache dir.
+    #[clap(long, help_heading = "Persistence")]
+    pub mmap_prune_tables: bool,
+}
+
+impl SearchPersistenceArgs {
+    /// Returns `None` unless at least one of the persistence flags was passed.
+    pub fn prune_table_persistence_options(
+        &self,
+    ) -> Result<Option<PruneTablePersistenceOptions>, ArgumentError> {
+        if self.write_prune_tables.is_none() && self.cache_dir.is_none() && !self.mmap_prune_tables
+        {
+            return Ok(None);
+        }
+        let Some(cache_dir) = self.cache_dir.clone().or_else(default_cache_dir) else {
+            return Err(
+                "Could not determine a default cache dir. Please pass `--cache-dir`.".into(),
+            );
+        };
+        Ok(Some(PruneTablePersistenceOptions {
+            cache_dir,
+            write_prune_tables: self
+                .write_prune_tables
+                .clone()
+                .unwrap_or(EnableAutoAlwaysNeverValueEnum::Auto),
+            mmap_prune_tables: self.mmap_prune_tables,
+        }))
+    }
 }
 
 #[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
@@ -271,6 +422,13 @@ pub struct MemoryArgs {
     pub memory_mebibytes: Option<usize>,
 }
 
+impl MemoryArgs {
+    pub fn memory_bytes(&self) -> Option<usize> {
+        self.memory_mebibytes
+            .map(|memory_mebibytes| memory_mebibytes.saturating_mul(1 << 20))
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct CompletionsArgs {
     /// Print completions for the given shell.
@@ -282,12 +440,14 @@ pub struct CompletionsArgs {
     shell: Shell,
 }
 
-// TODO: support moves arg?
 #[derive(Args, Debug)]
 pub struct SchreierSimsArgs {
     #[command(flatten)]
     pub def_args: DefOnlyArgs,
 
+    #[command(flatten)]
+    pub generator_args: GeneratorArgs,
+
     #[command(flatten)]
     pub performance_args: PerformanceArgs,
 }
@@ -338,6 +498,10 @@ pub struct TimingTestArgs {
 
     #[command(flatten)]
     pub performance_args: PerformanceArgs,
+
+    /// Print the results as JSON instead of a table.
+    #[clap(long)]
+    pub json: bool,
 }
 
 #[derive(Args, Debug)]
@@ -353,6 +517,14 @@ pub struct CanonicalAlgsArgs {
 
     #[command(flatten)]
     pub performance_args: PerformanceArgs,
+
+    /// Enumerate canonical move sequences up to this many moves (default: 5).
+    #[clap(long)]
+    pub max_depth: Option<Depth>,
+
+    /// Print each canonical move sequence (in addition to the counts).
+    #[clap(long)]
+    pub list_algs: bool,
 }
 
 #[derive(Clone, Args, Debug)]
@@ -372,8 +544,17 @@ impl Default for MetricArgs {
 
 #[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
 pub enum MetricEnum {
+    /// Every multiple of a generator counts as one move.
     Hand,
+    /// Every quantum (e.g. a quarter turn) of a generator counts as one move.
     Quantum,
+    /// Like `hand`, but rotations (e.g. `x` or `Uv`) are not used (a warning
+    /// is printed if any are passed as generators). Slice moves count as one
+    /// move when they are generators.
+    SliceTurn,
+    /// Simultaneous turns of commuting generators on the same axis (e.g. `U D'`) count as one move.
+    /// Rotations are not used (a warning is printed if any are passed as generators).
+    Axial,
 }
 
 impl Display for MetricEnum {
@@ -381,6 +562,8 @@ impl Display for MetricEnum {
         let s = match self {
             MetricEnum::Hand => "hand",
             MetricEnum::Quantum => "quantum",
+            MetricEnum::SliceTurn => "slice-turn",
+            MetricEnum::Axial => "axial",
         };
         write!(f, "{}", s)
     }
@@ -423,8 +606,9 @@ pub struct ScrambleAndTargetPatternOptionalArgs {
     #[clap(long, help_heading = "Scramble input", group = "scramble_input"/* , visible_short_alias = 's' */)]
     pub stdin_scrambles: bool,
     /// Use the target pattern from the specified file instead of the default start pattern from the defintion.
+    /// This can be specified multiple times, in which case a solution may reach any of the target patterns.
     #[clap(long, help_heading = "Scramble input")]
-    pub experimental_target_pattern: Option<PathBuf>,
+    pub experimental_target_pattern: Vec<PathBuf>,
 }
 
 #[derive(Args, Debug, Default)]
@@ -489,6 +673,9 @@ pub struct ServeArgsForIndividualSearch<'a> {
 
 #[derive(Args, Debug)]
 pub struct ServeCommandArgs {
+    /// Maximum time to spend on each search request. Clients can request a shorter time limit.
+    #[clap(long, value_name = "SECONDS")]
+    pub time_limit_seconds: Option<f64>,
     #[command(flatten)]
     pub performance_args: PerformanceArgs,
     #[command(flatten)]
@@ -507,4 +694,5 @@ pub struct ServeClientArgs {
     pub start_prune_depth: Option<Depth>,
     pub quantum_metric: Option<bool>, // TODO: enum
     pub generator_moves: Option<Vec<Move>>,
+    pub time_limit_seconds: Option<f64>,
 }
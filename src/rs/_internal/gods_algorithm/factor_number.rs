@@ -62,3 +62,24 @@ fn factor_number_from(n: u64, from: u64) -> Factorization {
 pub fn factor_number(n: u64) -> Factorization {
     factor_number_from(n, 2)
 }
+
+/// Factors a product without computing it (so that it can be larger than a `u64`).
+pub fn factor_product(factors: impl IntoIterator<Item = u64>) -> Factorization {
+    let mut prime_powers: Vec<PrimePower> = vec![];
+    for factor in factors {
+        if factor <= 1 {
+            continue;
+        }
+        for prime_power in factor_number(factor).prime_powers {
+            match prime_powers
+                .iter_mut()
+                .find(|p| p.prime == prime_power.prime)
+            {
+                Some(existing) => existing.power += prime_power.power,
+                None => prime_powers.push(prime_power),
+            }
+        }
+    }
+    prime_powers.sort_by_key(|p| p.prime);
+    Factorization { prime_powers }
+}
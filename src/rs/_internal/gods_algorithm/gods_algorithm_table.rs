@@ -75,9 +75,10 @@ impl GodsAlgorithmSearch {
         quantum_metric: &MetricEnum,
     ) -> Result<Self, SearchError> {
         let depth_to_patterns = vec![];
-        let search_generators = SearchGenerators::try_new(
+        let search_generators = SearchGenerators::try_new_with_alg_generators(
             &kpuzzle,
             generators.enumerate_moves_for_kpuzzle(&kpuzzle),
+            generators.algs(),
             quantum_metric,
             false,
         )?;
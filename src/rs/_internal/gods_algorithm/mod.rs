@@ -1,3 +1,3 @@
 mod bulk_queue;
-mod factor_number;
+pub mod factor_number;
 pub mod gods_algorithm_table;
@@ -3,4 +3,5 @@ pub mod cli;
 pub mod errors;
 pub mod gods_algorithm;
 pub mod puzzle_traits;
+pub mod schreier_sims;
 pub mod search;
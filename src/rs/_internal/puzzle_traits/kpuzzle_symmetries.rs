@@ -0,0 +1,358 @@
+use std::cell::RefCell;
+
+use cubing::{
+    alg::Move,
+    kpuzzle::{KPattern, KPuzzle, KTransformation, OrientationWithMod},
+};
+
+use crate::_internal::{errors::SearchError, search::prune_table_trait::Depth};
+
+// For each orbit, the new label and orientation offset for each piece label of the default pattern.
+type Relabeling = Vec<Vec<Option<(u8, u8)>>>;
+
+struct KPuzzleSymmetry {
+    transformation: KTransformation,
+    relabeling: Relabeling,
+    // The permutation and orientation delta of `transformation` for each orbit, for fast access.
+    permutations: Vec<Vec<u8>>,
+    orientation_deltas: Vec<Vec<u8>>,
+}
+
+/// A group of whole-puzzle symmetries (e.g. rotations or reflections), used to
+/// reduce patterns to a canonical representative.
+///
+/// Conjugating a pattern by a symmetry `s` relabels its pieces by `s⁻¹`
+/// (relative to the default pattern) and then applies `s`. If the generators
+/// and target patterns are preserved by conjugation, then so is the distance
+/// to the target patterns.
+pub struct KPuzzleSymmetries {
+    kpuzzle: KPuzzle,
+    // Starts with the identity.
+    symmetries: Vec<KPuzzleSymmetry>,
+    // The number of bytes that `reduced_hash_u64` serializes each conjugate into.
+    num_serialized_bytes: usize,
+}
+
+thread_local! {
+    // Reused by `reduced_hash_u64`, which is called for every prune table lookup.
+    static SERIALIZED_CONJUGATE: RefCell<Vec<u8>> = const { RefCell::new(vec![]) };
+}
+
+impl KPuzzleSymmetries {
+    /// `generators` are the transformations of the generator moves, with their costs.
+    pub fn try_new<'a>(
+        kpuzzle: &KPuzzle,
+        symmetry_moves: &[Move],
+        generators: impl Iterator<Item = (&'a KTransformation, Depth)>,
+        target_patterns: &[KPattern],
+    ) -> Result<Self, SearchError> {
+        let mut symmetry_transformations = vec![];
+        for r#move in symmetry_moves {
+            let Ok(transformation) = kpuzzle.transformation_from_move(r#move) else {
+                return Err(SearchError {
+                    description: format!("Could not get transformation for symmetry: {}", r#move),
+                });
+            };
+            symmetry_transformations.push(transformation);
+        }
+
+        // Close the symmetries under composition.
+        let mut group_elements = vec![kpuzzle.identity_transformation()];
+        let mut i = 0;
+        while let Some(group_element) = group_elements.get(i) {
+            let products: Vec<KTransformation> = symmetry_transformations
+                .iter()
+                .map(|transformation| group_element.apply_transformation(transformation))
+                .collect();
+            for product in products {
+                if !group_elements.contains(&product) {
+                    group_elements.push(product);
+                }
+            }
+            i += 1;
+        }
+
+        let mut symmetries = vec![];
+        for transformation in group_elements {
+            let relabeling = Self::relabeling(kpuzzle, &transformation.invert())?;
+            let (permutations, orientation_deltas) = kpuzzle
+                .orbit_info_iter()
+                .map(|orbit_info| {
+                    (0..orbit_info.num_pieces)
+                        .map(|i| {
+                            (
+                                transformation.get_permutation_idx(orbit_info, i),
+                                transformation.get_orientation_delta(orbit_info, i),
+                            )
+                        })
+                        .unzip()
+                })
+                .unzip();
+            symmetries.push(KPuzzleSymmetry {
+                transformation,
+                relabeling,
+                permutations,
+                orientation_deltas,
+            });
+        }
+        let num_serialized_bytes = kpuzzle
+            .orbit_info_iter()
+            .map(|orbit_info| 3 * orbit_info.num_pieces as usize)
+            .sum();
+        let symmetries = Self {
+            kpuzzle: kpuzzle.clone(),
+            symmetries,
+            num_serialized_bytes,
+        };
+
+        let generators: Vec<(&KTransformation, Depth)> = generators.collect();
+        for symmetry in &symmetries.symmetries[1..] {
+            let inverse = symmetry.transformation.invert();
+            for (generator_transformation, cost) in &generators {
+                let conjugate = inverse
+                    .apply_transformation(generator_transformation)
+                    .apply_transformation(&symmetry.transformation);
+                let Some((_, conjugate_cost)) = generators
+                    .iter()
+                    .find(|(transformation, _)| *transformation == &conjugate)
+                else {
+                    return Err(
+                        "The generators are not preserved by the symmetries (e.g. `--generator-moves U` with `--symmetry-moves x`)."
+                            .into(),
+                    );
+                };
+                // Otherwise, symmetric patterns could have different distances to the target patterns.
+                if conjugate_cost != cost {
+                    return Err(
+                        "The move costs are not preserved by the symmetries (e.g. `--move-costs U=2` with `--symmetry-moves x`)."
+                            .into(),
+                    );
+                }
+            }
+            for target_pattern in target_patterns {
+                if !target_patterns.contains(&symmetries.conjugate(symmetry, target_pattern)) {
+                    return Err("The target patterns are not preserved by the symmetries.".into());
+                }
+            }
+        }
+
+        Ok(symmetries)
+    }
+
+    /// The size of the symmetry group (including the identity).
+    pub fn num_symmetries(&self) -> usize {
+        self.symmetries.len()
+    }
+
+    /// All the patterns that are symmetric to `pattern` (including `pattern` itself, which is first).
+    pub fn conjugates<'a>(&'a self, pattern: &'a KPattern) -> impl Iterator<Item = KPattern> + 'a {
+        self.symmetries
+            .iter()
+            .map(|symmetry| self.conjugate(symmetry, pattern))
+    }
+
+    /// A hash that is the same for all patterns that are symmetric to each other.
+    ///
+    /// This is the minimum hash of the conjugates, which are serialized
+    /// directly (into a reused buffer) instead of constructing a `KPattern` for
+    /// each one.
+    pub fn reduced_hash_u64(&self, pattern: &KPattern) -> u64 {
+        SERIALIZED_CONJUGATE.with_borrow_mut(|bytes| {
+            bytes.resize(self.num_serialized_bytes, 0);
+            let mut min_hash = u64::MAX;
+            for symmetry in &self.symmetries {
+                let mut byte_index = 0;
+                for (orbit_index, orbit_info) in self.kpuzzle.orbit_info_iter().enumerate() {
+                    let permutation = &symmetry.permutations[orbit_index];
+                    let orientation_deltas = &symmetry.orientation_deltas[orbit_index];
+                    let orbit_relabeling = &symmetry.relabeling[orbit_index];
+                    for i in 0..orbit_info.num_pieces as usize {
+                        let from_slot = permutation[i];
+                        let piece = pattern.get_piece(orbit_info, from_slot);
+                        let orientation_with_mod =
+                            pattern.get_orientation_with_mod(orbit_info, from_slot);
+                        let (piece, orientation_offset) =
+                            orbit_relabeling[piece as usize].unwrap_or((piece, 0));
+                        let modulus = match orientation_with_mod.orientation_mod {
+                            0 => orbit_info.num_orientations,
+                            orientation_mod => orientation_mod,
+                        };
+                        bytes[byte_index] = piece;
+                        bytes[byte_index + 1] = (orientation_with_mod.orientation
+                            + orientation_offset
+                            + orientation_deltas[i])
+                            % modulus;
+                        bytes[byte_index + 2] = orientation_with_mod.orientation_mod;
+                        byte_index += 3;
+                    }
+                }
+                min_hash = u64::min(min_hash, cityhasher::hash(&bytes[..]));
+            }
+            min_hash
+        })
+    }
+
+    fn conjugate(&self, symmetry: &KPuzzleSymmetry, pattern: &KPattern) -> KPattern {
+        let mut relabeled = pattern.clone();
+        for (orbit_info, orbit_relabeling) in pattern
+            .kpuzzle()
+            .orbit_info_iter()
+            .zip(&symmetry.relabeling)
+        {
+            for i in 0..orbit_info.num_pieces {
+                let Some((new_label, orientation_offset)) =
+                    orbit_relabeling[pattern.get_piece(orbit_info, i) as usize]
+                else {
+                    continue;
+                };
+                let orientation_with_mod = pattern.get_orientation_with_mod(orbit_info, i);
+                let modulus = match orientation_with_mod.orientation_mod {
+                    0 => orbit_info.num_orientations,
+                    orientation_mod => orientation_mod,
+                };
+                relabeled.set_piece(orbit_info, i, new_label);
+                relabeled.set_orientation_with_mod(
+                    orbit_info,
+                    i,
+                    &OrientationWithMod {
+                        orientation: (orientation_with_mod.orientation + orientation_offset)
+                            % modulus,
+                        orientation_mod: orientation_with_mod.orientation_mod,
+                    },
+                );
+            }
+        }
+        relabeled.apply_transformation(&symmetry.transformation)
+    }
+
+    // Relabels the pieces of the default pattern as if they had been moved by `transformation`.
+    // This fails if `transformation` does not map identical pieces to identical pieces.
+    fn relabeling(
+        kpuzzle: &KPuzzle,
+        transformation: &KTransformation,
+    ) -> Result<Relabeling, SearchError> {
+        let default_pattern = kpuzzle.default_pattern();
+        let mut relabeling = vec![];
+        for orbit_info in kpuzzle.orbit_info_iter() {
+            let mut orbit_relabeling: Vec<Option<(u8, u8)>> = vec![None; u8::MAX as usize + 1];
+            for slot in 0..orbit_info.num_pieces {
+                let label = default_pattern.get_piece(orbit_info, slot);
+                let from_slot = transformation.get_permutation_idx(orbit_info, slot);
+                let new_label = default_pattern.get_piece(orbit_info, from_slot);
+                let orientation_offset = (default_pattern
+                    .get_orientation_with_mod(orbit_info, from_slot)
+                    .orientation
+                    + transformation.get_orientation_delta(orbit_info, slot)
+                    + orbit_info.num_orientations
+                    - default_pattern
+                        .get_orientation_with_mod(orbit_info, slot)
+                        .orientation)
+                    % orbit_info.num_orientations;
+                match orbit_relabeling[label as usize] {
+                    None => {
+                        orbit_relabeling[label as usize] = Some((new_label, orientation_offset))
+                    }
+                    Some(existing) if existing == (new_label, orientation_offset) => {}
+                    Some(_) => {
+                        return Err(SearchError {
+                            description: format!(
+                                "The symmetries do not preserve the identical pieces in orbit: {}",
+                                orbit_info.name
+                            ),
+                        })
+                    }
+                }
+            }
+            relabeling.push(orbit_relabeling);
+        }
+        Ok(relabeling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::{
+        alg::{parse_alg, Move},
+        kpuzzle::KTransformation,
+        puzzles::cube3x3x3_kpuzzle,
+    };
+
+    use crate::_internal::search::prune_table_trait::Depth;
+
+    use super::KPuzzleSymmetries;
+
+    #[test]
+    fn kpuzzle_symmetries_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let symmetries = KPuzzleSymmetries::try_new(
+            kpuzzle,
+            &["x".parse().unwrap(), "y".parse().unwrap()],
+            std::iter::empty(),
+            &[kpuzzle.default_pattern()],
+        )
+        .unwrap();
+        assert_eq!(symmetries.num_symmetries(), 24);
+        let pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U"))
+            .unwrap();
+        let conjugate = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("y' R U y"))
+            .unwrap();
+        assert!(symmetries.conjugates(&pattern).any(|c| c == conjugate));
+        assert_eq!(
+            symmetries.reduced_hash_u64(&pattern),
+            symmetries.reduced_hash_u64(&conjugate)
+        );
+        let other = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U'"))
+            .unwrap();
+        assert_ne!(
+            symmetries.reduced_hash_u64(&pattern),
+            symmetries.reduced_hash_u64(&other)
+        );
+    }
+
+    #[test]
+    fn kpuzzle_symmetries_move_costs_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let generators: Vec<(Move, KTransformation)> = ["F", "R", "B", "L"]
+            .into_iter()
+            .flat_map(|family| {
+                [
+                    family.to_owned(),
+                    format!("{}2", family),
+                    format!("{}'", family),
+                ]
+            })
+            .map(|r#move| {
+                let r#move: Move = r#move.parse().unwrap();
+                let transformation = kpuzzle.transformation_from_move(&r#move).unwrap();
+                (r#move, transformation)
+            })
+            .collect();
+        let try_new_with_r_cost = |r_cost: usize| {
+            KPuzzleSymmetries::try_new(
+                kpuzzle,
+                &["y".parse().unwrap()],
+                generators.iter().map(|(r#move, transformation)| {
+                    let cost = if r#move.quantum.family == "R" {
+                        r_cost
+                    } else {
+                        1
+                    };
+                    (transformation, Depth(cost))
+                }),
+                &[kpuzzle.default_pattern()],
+            )
+        };
+        assert_eq!(try_new_with_r_cost(1).unwrap().num_symmetries(), 4);
+        assert!(try_new_with_r_cost(2)
+            .err()
+            .unwrap()
+            .description
+            .contains("move costs"));
+    }
+}
@@ -1,4 +1,5 @@
 #[allow(clippy::module_inception)]
 pub mod puzzle_traits;
 
+pub mod kpuzzle_symmetries;
 mod puzzle_traits_puzzle_for_kpuzzle;
@@ -1,22 +1,27 @@
 use std::fmt::Debug;
 
-use cubing::{alg::Move, kpuzzle::InvalidAlgError};
+use cubing::{
+    alg::{Alg, Move},
+    kpuzzle::{InvalidAlgError, InvalidMoveError},
+};
 
 use crate::_internal::{
-    canonical_fsm::search_generators::MoveTransformationInfo, search::move_count::MoveCount,
+    canonical_fsm::search_generators::{MoveTransformationInfo, SearchGenerators},
+    errors::SearchError,
+    search::move_count::MoveCount,
 };
 
 // TODO: split this into 3 related traits.
 /// The `Clone` implementation must be cheap for both the main struct as well as the `Pattern` and `Transformation` types (e.g. implemented using data shared with an `Arc` under the hood whenever any non-trivial amount of data is associated).
-pub trait SemiGroupActionPuzzle: Debug + Clone {
-    type Pattern: Eq + Clone + Debug;
+pub trait SemiGroupActionPuzzle: Debug + Clone + Send + Sync + 'static {
+    type Pattern: Eq + Clone + Debug + Send + Sync;
     /// This is a proper "transformation" (such as a permutation) in the general
     /// case, but for `GenericPuzzleCore` it can be anything that is applied to a
     /// pattern, such as:
     ///
     /// - A [`Move`]
     /// - An index or reference into an array that encodes how to apply it
-    type Transformation: Eq + Clone + Debug;
+    type Transformation: Eq + Clone + Debug + Send + Sync;
 
     // /********* Functions "defined on the puzzle". ********/
     // fn puzzle_default_pattern(&self) -> Self::Pattern;
@@ -30,6 +35,21 @@ pub trait SemiGroupActionPuzzle: Debug + Clone {
         r#move: &Move,
     ) -> Result<Self::Transformation, InvalidAlgError>;
 
+    /// Used for alg generators, which the search treats as single moves.
+    /// Puzzles that can only represent individual moves as transformations can
+    /// use the default implementation, which returns an error.
+    fn alg_order(&self, alg: &Alg) -> Result<MoveCount, InvalidAlgError> {
+        Err(alg_generators_unsupported(alg))
+    }
+
+    /// See [`SemiGroupActionPuzzle::alg_order`].
+    fn puzzle_transformation_from_alg(
+        &self,
+        alg: &Alg,
+    ) -> Result<Self::Transformation, InvalidAlgError> {
+        Err(alg_generators_unsupported(alg))
+    }
+
     // TODO: this is a leaky abstraction. use traits and enums to create a natural API for this.
     fn do_moves_commute(
         &self,
@@ -63,6 +83,12 @@ pub trait SemiGroupActionPuzzle: Debug + Clone {
     ) -> bool;
 }
 
+fn alg_generators_unsupported(alg: &Alg) -> InvalidAlgError {
+    InvalidAlgError::InvalidMove(InvalidMoveError {
+        description: format!("Alg generators are not supported for this puzzle: {}", alg),
+    })
+}
+
 pub trait GroupActionPuzzle: SemiGroupActionPuzzle {
     // /********* Functions "defined on the puzzle". ********/
     // fn puzzle_transformation_from_alg(
@@ -90,5 +116,25 @@ pub trait GroupActionPuzzle: SemiGroupActionPuzzle {
 }
 
 pub trait HashablePatternPuzzle: SemiGroupActionPuzzle {
+    /// A group of symmetries used to share prune table entries between symmetric patterns.
+    type PatternSymmetries: Send + Sync;
+
     fn pattern_hash_u64(&self, pattern: &Self::Pattern) -> u64;
+    /// Must be stable across runs (used as part of the key for persisted prune tables).
+    fn puzzle_definition_hash_u64(&self) -> u64;
+
+    /// Returns the group generated by `symmetry_moves` (e.g. whole-puzzle
+    /// rotations), which must preserve the generators and the target patterns.
+    fn pattern_symmetries(
+        &self,
+        symmetry_moves: &[Move],
+        search_generators: &SearchGenerators<Self>,
+        target_patterns: &[Self::Pattern],
+    ) -> Result<Self::PatternSymmetries, SearchError>;
+    /// Hashes a canonical representative of `pattern`, so that symmetric patterns have the same hash.
+    fn pattern_symmetry_reduced_hash_u64(
+        &self,
+        pattern: &Self::Pattern,
+        pattern_symmetries: &Self::PatternSymmetries,
+    ) -> u64;
 }
@@ -1,15 +1,31 @@
 use std::hash::BuildHasher;
 
 use cubing::{
-    alg::Move,
+    alg::{Alg, Move},
     kpuzzle::{InvalidAlgError, KPattern, KPuzzle, KTransformation, KTransformationBuffer},
 };
 
 use crate::_internal::{
-    canonical_fsm::search_generators::MoveTransformationInfo, search::move_count::MoveCount,
+    canonical_fsm::search_generators::{MoveTransformationInfo, SearchGenerators},
+    errors::SearchError,
+    search::move_count::MoveCount,
 };
 
-use super::puzzle_traits::{GroupActionPuzzle, HashablePatternPuzzle, SemiGroupActionPuzzle};
+use super::{
+    kpuzzle_symmetries::KPuzzleSymmetries,
+    puzzle_traits::{GroupActionPuzzle, HashablePatternPuzzle, SemiGroupActionPuzzle},
+};
+
+fn transformation_order(transformation: &KTransformation) -> MoveCount {
+    let identity_transformation = transformation.kpuzzle().identity_transformation();
+    let mut order = MoveCount(1);
+    let mut current_transformation = KTransformationBuffer::from(transformation.clone());
+    while *current_transformation.current() != identity_transformation {
+        current_transformation.apply_transformation(transformation);
+        order += MoveCount(1);
+    }
+    order
+}
 
 impl SemiGroupActionPuzzle for KPuzzle {
     type Pattern = KPattern;
@@ -20,15 +36,22 @@ impl SemiGroupActionPuzzle for KPuzzle {
     // }
 
     fn move_order(&self, r#move: &Move) -> Result<MoveCount, InvalidAlgError> {
-        let transformation = self.puzzle_transformation_from_move(r#move)?;
-        let identity_transformation = transformation.kpuzzle().identity_transformation();
-        let mut order = MoveCount(1);
-        let mut current_transformation = KTransformationBuffer::from(transformation.clone());
-        while *current_transformation.current() != identity_transformation {
-            current_transformation.apply_transformation(&transformation);
-            order += MoveCount(1);
-        }
-        Ok(order)
+        Ok(transformation_order(
+            &self.puzzle_transformation_from_move(r#move)?,
+        ))
+    }
+
+    fn alg_order(&self, alg: &Alg) -> Result<MoveCount, InvalidAlgError> {
+        Ok(transformation_order(
+            &self.puzzle_transformation_from_alg(alg)?,
+        ))
+    }
+
+    fn puzzle_transformation_from_alg(
+        &self,
+        alg: &Alg,
+    ) -> Result<Self::Transformation, InvalidAlgError> {
+        self.transformation_from_alg(alg)
     }
 
     fn do_moves_commute(
@@ -71,10 +94,50 @@ impl SemiGroupActionPuzzle for KPuzzle {
 }
 
 impl HashablePatternPuzzle for KPuzzle {
+    type PatternSymmetries = KPuzzleSymmetries;
+
     fn pattern_hash_u64(&self, pattern: &Self::Pattern) -> u64 {
         let h = cityhasher::CityHasher::new();
         h.hash_one(unsafe { pattern.byte_slice() })
     }
+
+    fn puzzle_definition_hash_u64(&self) -> u64 {
+        // `serde_json::Value` sorts object keys, so this is independent of `HashMap` iteration order.
+        let definition_json = serde_json::to_value(self.definition())
+            .expect("Could not serialize KPuzzle definition")
+            .to_string();
+        cityhasher::hash(definition_json)
+    }
+
+    fn pattern_symmetries(
+        &self,
+        symmetry_moves: &[Move],
+        search_generators: &SearchGenerators<Self>,
+        target_patterns: &[Self::Pattern],
+    ) -> Result<Self::PatternSymmetries, SearchError> {
+        KPuzzleSymmetries::try_new(
+            self,
+            symmetry_moves,
+            search_generators
+                .flat
+                .iter()
+                .map(|(_, move_transformation_info)| {
+                    (
+                        &move_transformation_info.transformation,
+                        move_transformation_info.cost,
+                    )
+                }),
+            target_patterns,
+        )
+    }
+
+    fn pattern_symmetry_reduced_hash_u64(
+        &self,
+        pattern: &Self::Pattern,
+        pattern_symmetries: &Self::PatternSymmetries,
+    ) -> u64 {
+        pattern_symmetries.reduced_hash_u64(pattern)
+    }
 }
 
 impl GroupActionPuzzle for KPuzzle {
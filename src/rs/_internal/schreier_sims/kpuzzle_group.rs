@@ -0,0 +1,111 @@
+use cubing::{
+    alg::{Alg, Move},
+    kpuzzle::{KPuzzle, KTransformation},
+};
+
+use crate::_internal::errors::SearchError;
+
+use super::stabilizer_chain::{GroupOrder, Permutation, StabilizerChain};
+
+pub struct KPuzzleGroup {
+    pub stabilizer_chain: StabilizerChain,
+    /// Orbits whose default pattern has identical pieces (or orientations
+    /// that are ignored). The group order treats all pieces as distinguishable,
+    /// so it overcounts the number of distinct patterns for these orbits.
+    pub orbits_with_identical_pieces: Vec<String>,
+}
+
+impl KPuzzleGroup {
+    pub fn try_new(
+        kpuzzle: &KPuzzle,
+        generator_moves: &[Move],
+        generator_algs: &[Alg],
+    ) -> Result<Self, SearchError> {
+        let def = kpuzzle.definition();
+
+        // Each (slot, orientation) pair for each orbit is a point that the generators permute.
+        let mut orbit_offsets = vec![];
+        let mut num_points = 0;
+        for orbit_definition in &def.orbits {
+            orbit_offsets.push(num_points);
+            num_points +=
+                orbit_definition.num_pieces as usize * orbit_definition.num_orientations as usize;
+        }
+        if num_points > u32::MAX as usize {
+            return Err("Puzzle definition is too large for Schreier–Sims.".into());
+        }
+
+        let mut transformations: Vec<KTransformation> = vec![];
+        for r#move in generator_moves {
+            let Ok(transformation) = kpuzzle.transformation_from_move(r#move) else {
+                return Err(SearchError {
+                    description: format!("Could not get transformation for move: {}", r#move),
+                });
+            };
+            transformations.push(transformation);
+        }
+        for alg in generator_algs {
+            let Ok(transformation) = kpuzzle.transformation_from_alg(alg) else {
+                return Err(SearchError {
+                    description: format!("Could not get transformation for alg: {}", alg),
+                });
+            };
+            transformations.push(transformation);
+        }
+
+        let mut stabilizer_chain = StabilizerChain::new(num_points);
+        for transformation in transformations {
+            let transformation_data = transformation.to_data();
+            let mut images = vec![0; num_points];
+            for (orbit_definition, orbit_offset) in def.orbits.iter().zip(&orbit_offsets) {
+                let num_orientations = orbit_definition.num_orientations as usize;
+                let orbit_data = &transformation_data[&orbit_definition.orbit_name];
+                // The piece in slot `permutation[i]` moves to slot `i`, gaining `orientation_delta[i]`.
+                for i in 0..orbit_definition.num_pieces as usize {
+                    let from_slot = orbit_data.permutation[i] as usize;
+                    let orientation_delta = orbit_data.orientation_delta[i] as usize;
+                    for orientation in 0..num_orientations {
+                        let from = orbit_offset + from_slot * num_orientations + orientation;
+                        let to = orbit_offset
+                            + i * num_orientations
+                            + (orientation + orientation_delta) % num_orientations;
+                        images[from] = to as u32;
+                    }
+                }
+            }
+            stabilizer_chain.add_generator(Permutation::from_images(images));
+        }
+
+        let mut orbits_with_identical_pieces = vec![];
+        for orbit_definition in &def.orbits {
+            let Some(orbit_pattern) = def.default_pattern.get(&orbit_definition.orbit_name) else {
+                continue;
+            };
+            let mut pieces = orbit_pattern.pieces.clone();
+            pieces.sort();
+            pieces.dedup();
+            let has_identical_pieces = pieces.len() < orbit_pattern.pieces.len();
+            let has_ignored_orientations =
+                orbit_pattern
+                    .orientation_mod
+                    .as_ref()
+                    .is_some_and(|orientation_mod| {
+                        orientation_mod
+                            .iter()
+                            .any(|&m| m != 0 && m != orbit_definition.num_orientations)
+                    });
+            if has_identical_pieces || has_ignored_orientations {
+                orbits_with_identical_pieces.push(orbit_definition.orbit_name.to_string());
+            }
+        }
+
+        Ok(Self {
+            stabilizer_chain,
+            orbits_with_identical_pieces,
+        })
+    }
+
+    pub fn order(&self) -> GroupOrder {
+        self.stabilizer_chain.order()
+    }
+}
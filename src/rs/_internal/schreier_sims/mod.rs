@@ -0,0 +1,2 @@
+pub mod kpuzzle_group;
+pub mod stabilizer_chain;
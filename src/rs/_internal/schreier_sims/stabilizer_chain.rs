@@ -0,0 +1,246 @@
+use std::fmt::Display;
+
+/// A permutation of `0..n`, stored as the image of each point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permutation(Vec<u32>);
+
+impl Permutation {
+    pub fn identity(num_points: usize) -> Self {
+        Self((0..num_points as u32).collect())
+    }
+
+    pub fn from_images(images: Vec<u32>) -> Self {
+        Self(images)
+    }
+
+    pub fn image(&self, point: usize) -> usize {
+        self.0[point] as usize
+    }
+
+    /// Applies `self` first, then `other`.
+    pub fn then(&self, other: &Permutation) -> Permutation {
+        Self(self.0.iter().map(|&i| other.0[i as usize]).collect())
+    }
+
+    pub fn inverse(&self) -> Permutation {
+        let mut images = vec![0; self.0.len()];
+        for (i, &j) in self.0.iter().enumerate() {
+            images[j as usize] = i as u32;
+        }
+        Self(images)
+    }
+}
+
+/// A stabilizer chain for the base `0, 1, …, n - 1`, built using Knuth's
+/// variant of the Schreier–Sims algorithm ("Efficient representation of perm
+/// groups", 1991).
+pub struct StabilizerChain {
+    num_points: usize,
+    // `transversals[k][j]` (if present) is a group element that fixes all
+    // points before `k` and maps `k` to `j`, along with its inverse.
+    transversals: Vec<Vec<Option<(Permutation, Permutation)>>>,
+    // The generators for the stabilizer of all points before `k`.
+    generators: Vec<Vec<Permutation>>,
+}
+
+impl StabilizerChain {
+    pub fn new(num_points: usize) -> Self {
+        let transversals = (0..num_points)
+            .map(|k| {
+                let mut transversal = vec![None; num_points];
+                let identity = Permutation::identity(num_points);
+                transversal[k] = Some((identity.clone(), identity));
+                transversal
+            })
+            .collect();
+        Self {
+            num_points,
+            transversals,
+            generators: vec![vec![]; num_points + 1],
+        }
+    }
+
+    pub fn add_generator(&mut self, permutation: Permutation) {
+        assert_eq!(permutation.0.len(), self.num_points);
+        self.extend(0, permutation);
+    }
+
+    pub fn contains(&self, permutation: &Permutation) -> bool {
+        self.contains_from_level(0, permutation.clone())
+    }
+
+    /// The size of each basic orbit. The group order is their product.
+    pub fn orbit_sizes(&self) -> Vec<u64> {
+        self.transversals
+            .iter()
+            .map(|transversal| transversal.iter().filter(|entry| entry.is_some()).count() as u64)
+            .filter(|orbit_size| *orbit_size > 1)
+            .collect()
+    }
+
+    pub fn order(&self) -> GroupOrder {
+        let mut order = GroupOrder::one();
+        for orbit_size in self.orbit_sizes() {
+            order.multiply(orbit_size);
+        }
+        order
+    }
+
+    // `permutation` must fix all points before `level`.
+    fn contains_from_level(&self, level: usize, mut permutation: Permutation) -> bool {
+        for k in level..self.num_points {
+            let j = permutation.image(k);
+            if j == k {
+                continue;
+            }
+            match &self.transversals[k][j] {
+                Some((_, inverse)) => permutation = permutation.then(inverse),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // `permutation` must fix all points before `level`.
+    fn extend(&mut self, level: usize, permutation: Permutation) {
+        if self.contains_from_level(level, permutation.clone()) {
+            return;
+        }
+        self.generators[level].push(permutation.clone());
+
+        // Close the orbit of the base point under the new generator. Any
+        // product that lands on an already known orbit point gives a (Schreier)
+        // element of the next stabilizer, which is added recursively.
+        let mut queue: Vec<Permutation> = self.transversals[level]
+            .iter()
+            .flatten()
+            .map(|(transversal_element, _)| transversal_element.then(&permutation))
+            .collect();
+        while let Some(candidate) = queue.pop() {
+            let j = candidate.image(level);
+            match &self.transversals[level][j] {
+                Some((_, inverse)) => {
+                    let schreier_generator = candidate.then(inverse);
+                    self.extend(level + 1, schreier_generator);
+                }
+                None => {
+                    for generator in &self.generators[level] {
+                        queue.push(candidate.then(generator));
+                    }
+                    let inverse = candidate.inverse();
+                    self.transversals[level][j] = Some((candidate, inverse));
+                }
+            }
+        }
+    }
+}
+
+/// An arbitrary-precision group order (many puzzles have orders that don't fit in a `u64`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupOrder {
+    // Little-endian base 2³² digits.
+    limbs: Vec<u32>,
+}
+
+impl GroupOrder {
+    pub fn one() -> Self {
+        Self { limbs: vec![1] }
+    }
+
+    pub fn multiply(&mut self, factor: u64) {
+        let mut carry: u128 = 0;
+        for limb in self.limbs.iter_mut() {
+            let product = (*limb as u128) * (factor as u128) + carry;
+            *limb = product as u32;
+            carry = product >> 32;
+        }
+        while carry > 0 {
+            self.limbs.push(carry as u32);
+            carry >>= 32;
+        }
+        while self.limbs.len() > 1 && self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.limbs.len() > 4 {
+            return None;
+        }
+        Some(
+            self.limbs
+                .iter()
+                .rev()
+                .fold(0, |acc, limb| (acc << 32) | (*limb as u128)),
+        )
+    }
+
+    pub fn log2(&self) -> f64 {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, limb| acc * 2f64.powi(32) + (*limb as f64))
+            .log2()
+    }
+}
+
+impl Display for GroupOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const CHUNK: u64 = 1_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut chunks = vec![];
+        loop {
+            let mut remainder: u64 = 0;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 32) | (*limb as u64);
+                *limb = (value / CHUNK) as u32;
+                remainder = value % CHUNK;
+            }
+            chunks.push(remainder);
+            while limbs.len() > 1 && limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+            if limbs == [0] {
+                break;
+            }
+        }
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupOrder, Permutation, StabilizerChain};
+
+    #[test]
+    fn stabilizer_chain_symmetric_group_test() {
+        // S₅, generated by a transposition and a 5-cycle.
+        let mut chain = StabilizerChain::new(5);
+        chain.add_generator(Permutation::from_images(vec![1, 0, 2, 3, 4]));
+        chain.add_generator(Permutation::from_images(vec![1, 2, 3, 4, 0]));
+        assert_eq!(chain.order().to_u128(), Some(120));
+        assert!(chain.contains(&Permutation::from_images(vec![4, 3, 2, 1, 0])));
+
+        // A₅ does not contain any transpositions.
+        let mut chain = StabilizerChain::new(5);
+        chain.add_generator(Permutation::from_images(vec![1, 2, 0, 3, 4]));
+        chain.add_generator(Permutation::from_images(vec![1, 2, 3, 4, 0]));
+        assert_eq!(chain.order().to_u128(), Some(60));
+        assert!(!chain.contains(&Permutation::from_images(vec![1, 0, 2, 3, 4])));
+    }
+
+    #[test]
+    fn group_order_display_test() {
+        let mut order = GroupOrder::one();
+        for factor in [1_000_000_007, 1_000_000_009, 1_000_000_021] {
+            order.multiply(factor);
+        }
+        assert_eq!(order.to_string(), "1000000037000000399000001323");
+        assert_eq!(GroupOrder::one().to_string(), "1");
+    }
+}
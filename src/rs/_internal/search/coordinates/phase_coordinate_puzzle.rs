@@ -17,6 +17,7 @@ use crate::{
             FlatMoveIndex, MoveTransformationInfo, SearchGenerators,
         },
         cli::args::MetricEnum,
+        errors::SearchError,
         puzzle_traits::puzzle_traits::SemiGroupActionPuzzle,
         search::{
             idf_search::{
@@ -26,15 +27,17 @@ use crate::{
             indexed_vec::IndexedVec,
             move_count::MoveCount,
             pattern_validity_checker::AlwaysValid,
-            prune_table_trait::{Depth, PruneTable},
+            prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions},
             recursion_filter_trait::RecursionFilterNoOp,
             search_logger::SearchLogger,
+            search_statistics::PruneTableStatistics,
         },
     },
     whole_number_newtype_generic,
 };
 
-pub trait SemanticCoordinate<TPuzzle: SemiGroupActionPuzzle>: Eq + Hash + Clone + Debug
+pub trait SemanticCoordinate<TPuzzle: SemiGroupActionPuzzle>:
+    Eq + Hash + Clone + Debug + Send + Sync + 'static
 where
     Self: std::marker::Sized,
 {
@@ -252,6 +255,19 @@ where
         };
         Ok(*phase_coordinate_index)
     }
+
+    pub(crate) fn exact_prune_table_statistics(&self) -> PruneTableStatistics {
+        let exact_prune_table = &self.data.exact_prune_table;
+        PruneTableStatistics {
+            num_entries: exact_prune_table.len(),
+            num_bytes: exact_prune_table.len() * std::mem::size_of::<Depth>(),
+            pruning_depth: exact_prune_table
+                .iter()
+                .map(|(_, depth)| *depth)
+                .max()
+                .unwrap_or_default(),
+        }
+    }
 }
 
 fn puzzle_transformation_from_move<
@@ -352,15 +368,17 @@ impl<TPuzzle: SemiGroupActionPuzzle, TSemanticCoordinate: SemanticCoordinate<TPu
     PruneTable<PhaseCoordinatePuzzle<TPuzzle, TSemanticCoordinate>>
     for PhaseCoordinatePruneTable<TPuzzle, TSemanticCoordinate>
 {
+    type TableOptions = ();
+
     fn new(
         puzzle: PhaseCoordinatePuzzle<TPuzzle, TSemanticCoordinate>,
         _search_api_data: Arc<
             IDFSearchAPIData<PhaseCoordinatePuzzle<TPuzzle, TSemanticCoordinate>>,
         >,
         _search_logger: Arc<SearchLogger>,
-        _min_size: Option<usize>,
-    ) -> Self {
-        Self { tpuzzle: puzzle }
+        _options: PruneTableConstructionOptions<()>,
+    ) -> Result<Self, SearchError> {
+        Ok(Self { tpuzzle: puzzle })
     }
 
     fn lookup(
@@ -370,9 +388,18 @@ impl<TPuzzle: SemiGroupActionPuzzle, TSemanticCoordinate: SemanticCoordinate<TPu
         *self.tpuzzle.data.exact_prune_table.at(*pattern)
     }
 
-    fn extend_for_search_depth(&mut self, _search_depth: Depth, _approximate_num_entries: usize) {
+    fn extend_for_search_depth(
+        &mut self,
+        _search_depth: Depth,
+        _approximate_num_entries: usize,
+        _should_stop: &(dyn Fn() -> bool + Sync),
+    ) {
         // no-op
     }
+
+    fn statistics(&self) -> PruneTableStatistics {
+        self.tpuzzle.exact_prune_table_statistics()
+    }
 }
 
 // TODO: simplify the default for below.
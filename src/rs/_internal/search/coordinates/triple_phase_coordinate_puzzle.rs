@@ -4,6 +4,7 @@ use cubing::{alg::Move, kpuzzle::InvalidAlgError};
 
 use crate::_internal::{
     canonical_fsm::search_generators::{FlatMoveIndex, MoveTransformationInfo},
+    errors::SearchError,
     puzzle_traits::puzzle_traits::SemiGroupActionPuzzle,
     search::{
         idf_search::{
@@ -12,9 +13,10 @@ use crate::_internal::{
         },
         move_count::MoveCount,
         pattern_validity_checker::AlwaysValid,
-        prune_table_trait::{Depth, PruneTable},
+        prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions},
         recursion_filter_trait::RecursionFilterNoOp,
         search_logger::SearchLogger,
+        search_statistics::PruneTableStatistics,
     },
 };
 
@@ -364,6 +366,8 @@ impl<
         TSemanticCoordinate3,
     >
 {
+    type TableOptions = ();
+
     fn new(
         puzzle: TriplePhaseCoordinatePuzzle<
             TPuzzle,
@@ -382,9 +386,9 @@ impl<
             >,
         >,
         _search_logger: Arc<SearchLogger>,
-        _min_size: Option<usize>,
-    ) -> Self {
-        Self { tpuzzle: puzzle }
+        _options: PruneTableConstructionOptions<()>,
+    ) -> Result<Self, SearchError> {
+        Ok(Self { tpuzzle: puzzle })
     }
 
     fn lookup(
@@ -420,9 +424,30 @@ impl<
         max(max(depth1, depth2), depth3)
     }
 
-    fn extend_for_search_depth(&mut self, _search_depth: Depth, _approximate_num_entries: usize) {
+    fn extend_for_search_depth(
+        &mut self,
+        _search_depth: Depth,
+        _approximate_num_entries: usize,
+        _should_stop: &(dyn Fn() -> bool + Sync),
+    ) {
         // no-op
     }
+
+    // The tables are combined the same way as in `lookup`.
+    fn statistics(&self) -> PruneTableStatistics {
+        let mut statistics = PruneTableStatistics::default();
+        for puzzle_statistics in [
+            self.tpuzzle.data.puzzle1.exact_prune_table_statistics(),
+            self.tpuzzle.data.puzzle2.exact_prune_table_statistics(),
+            self.tpuzzle.data.puzzle3.exact_prune_table_statistics(),
+        ] {
+            statistics.num_entries += puzzle_statistics.num_entries;
+            statistics.num_bytes += puzzle_statistics.num_bytes;
+            statistics.pruning_depth =
+                max(statistics.pruning_depth, puzzle_statistics.pruning_depth);
+        }
+        statistics
+    }
 }
 
 pub struct TriplePhaseCoordinateSearchOptimizations<
@@ -1,11 +1,17 @@
+use std::any::type_name;
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use cubing::alg::Move;
 use thousands::Separable;
 
 use crate::_internal::canonical_fsm::canonical_fsm::{
     CanonicalFSMState, CANONICAL_FSM_START_STATE,
 };
+use crate::_internal::errors::SearchError;
 use crate::_internal::puzzle_traits::puzzle_traits::{
     HashablePatternPuzzle, SemiGroupActionPuzzle,
 };
@@ -13,9 +19,15 @@ use crate::whole_number_newtype;
 
 use super::idf_search::idf_search::IDFSearchAPIData;
 use super::pattern_validity_checker::PatternValidityChecker;
-use super::prune_table_trait::{Depth, PruneTable};
+use super::prune_table_entries::{PruneTableEntries, PruneTableEntryPacking};
+use super::prune_table_persistence::{
+    map_prune_table, prune_table_cache_file_path, read_prune_table, write_prune_table,
+    PruneTableCacheKeyData, PruneTablePersistenceOptions,
+};
+use super::prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions};
 use super::recursive_work_tracker::RecursiveWorkTracker;
-use super::search_logger::SearchLogger;
+use super::search_logger::{SearchLogEvent, SearchLogger};
+use super::search_statistics::PruneTableStatistics;
 
 whole_number_newtype!(DepthU8, u8);
 
@@ -23,37 +35,124 @@ type PruneTableEntryType = DepthU8;
 // 0 is uninitialized, all other values are stored as 1+depth.
 // This allows us to save initialization time by allowing table memory pages to start as "blank" (all 0).
 const UNINITIALIZED_SENTINEL: PruneTableEntryType = DepthU8(0);
-const INVALID_PATTERN_SENTINEL: PruneTableEntryType = DepthU8(u8::MAX); // TODO: avoid harcoding `u8` here.
-const INVALID_PATTERN_DEPTH: PruneTableEntryType = DepthU8(INVALID_PATTERN_SENTINEL.0 - 1);
-const MAX_PRUNE_TABLE_DEPTH: PruneTableEntryType = DepthU8(u8::MAX - 2); // TODO: avoid harcoding `u8` here.
+
+// The largest entry value marks invalid patterns, which leaves the values below it for depths.
+fn invalid_pattern_sentinel(packing: PruneTableEntryPacking) -> PruneTableEntryType {
+    packing.max_entry_value()
+}
+fn invalid_pattern_depth(packing: PruneTableEntryPacking) -> PruneTableEntryType {
+    DepthU8(invalid_pattern_sentinel(packing).0 - 1)
+}
+fn max_prune_table_depth(packing: PruneTableEntryPacking) -> PruneTableEntryType {
+    DepthU8(invalid_pattern_sentinel(packing).0 - 2)
+}
+
+// Entries are packed two per byte whenever the pruning depth allows it, so
+// that the same amount of memory holds twice as many entries.
+fn packing_for_pruning_depth(pruning_depth: PruneTableEntryType) -> PruneTableEntryPacking {
+    if pruning_depth <= max_prune_table_depth(PruneTableEntryPacking::Nibble) {
+        PruneTableEntryPacking::Nibble
+    } else {
+        PruneTableEntryPacking::Byte
+    }
+}
 
 const DEFAULT_MIN_PRUNE_TABLE_SIZE: usize = 1 << 20;
 
+// Filling shallow depths is faster than spinning up threads.
+const MIN_PARALLEL_FILL_DEPTH: PruneTableEntryType = DepthU8(5);
+// We split the fill into at least this many subtrees per thread, so that
+// threads with quick subtrees can pick up more work.
+const MIN_PARALLEL_FILL_TASKS_PER_THREAD: usize = 4;
+const MAX_PARALLEL_FILL_SPLIT_DEPTH: usize = 3;
+
+// Checking whether to stop can be relatively expensive (e.g. checking the time), so we only do it every so often.
+const NUM_RECURSIVE_CALLS_BETWEEN_STOP_CHECKS: usize = 1 << 12;
+
+// A subtree of the fill for a single depth.
+struct PruneTableFillTask<TPuzzle: SemiGroupActionPuzzle> {
+    pattern: TPuzzle::Pattern,
+    state: CanonicalFSMState,
+    remaining_depth: PruneTableEntryType,
+}
+
 struct HashPruneTableImmutableData<TPuzzle: SemiGroupActionPuzzle> {
     // TODO
     search_api_data: Arc<IDFSearchAPIData<TPuzzle>>,
 }
 struct HashPruneTableMutableData<TPuzzle: SemiGroupActionPuzzle + HashablePatternPuzzle> {
     tpuzzle: TPuzzle,
-    min_size: usize,               // power of 2
+    min_size: usize, // power of 2
+    max_memory_bytes: Option<usize>,
+    // Set once we've logged that `max_size` prevented the table from growing.
+    logged_max_size: bool,
     prune_table_size: usize,       // power of 2
     prune_table_index_mask: usize, // prune_table_size - 1
     current_pruning_depth: PruneTableEntryType,
-    pattern_hash_to_depth: Vec<PruneTableEntryType>,
+    pattern_hash_to_depth: PruneTableEntries,
     recursive_work_tracker: RecursiveWorkTracker,
     search_logger: Arc<SearchLogger>,
+    persistence: Option<PruneTablePersistenceOptions>,
+    // If set, symmetric patterns share an entry.
+    pattern_symmetries: Option<TPuzzle::PatternSymmetries>,
+    symmetry_moves: Vec<Move>,
+    // Reset whenever the table is resized.
+    attempted_cache_read: bool,
+    // Time spent generating the current table contents in this process.
+    generation_duration: Duration,
 }
 
 impl<TPuzzle: SemiGroupActionPuzzle + HashablePatternPuzzle> HashPruneTableMutableData<TPuzzle> {
+    // The largest number of entries (a power of 2) that fits in the memory limit, if there is one.
+    fn max_size(&self, packing: PruneTableEntryPacking) -> Option<usize> {
+        self.max_memory_bytes.map(|max_memory_bytes| {
+            let max_num_entries = usize::max(
+                1,
+                max_memory_bytes.saturating_mul(packing.entries_per_byte()),
+            );
+            // Round down to a power of 2.
+            1 << max_num_entries.ilog2()
+        })
+    }
+
+    fn log_max_size(&mut self, max_size: usize, requested_size: usize) {
+        if !self.logged_max_size {
+            self.search_logger.write_warning(&format!(
+                "[Prune table] Reached the memory limit, so the prune table will not grow beyond {} entries (requested: {} entries).",
+                max_size.separate_with_underscores(),
+                requested_size.separate_with_underscores()
+            ));
+            self.logged_max_size = true;
+        }
+    }
+
+    // Discards all entries.
+    fn reset(&mut self, prune_table_size: usize, packing: PruneTableEntryPacking) {
+        // Free the old entries first, so that the old and new entries never take up memory at the same time.
+        self.pattern_hash_to_depth = PruneTableEntries::new(packing, 0);
+        self.pattern_hash_to_depth = PruneTableEntries::new(packing, prune_table_size);
+        self.prune_table_size = prune_table_size;
+        self.prune_table_index_mask = prune_table_size - 1;
+        self.current_pruning_depth = DepthU8(0);
+        self.attempted_cache_read = false;
+        self.generation_duration = Duration::ZERO;
+    }
+
     fn hash_pattern(&self, pattern: &TPuzzle::Pattern) -> usize {
+        let pattern_hash = match &self.pattern_symmetries {
+            Some(pattern_symmetries) => self
+                .tpuzzle
+                .pattern_symmetry_reduced_hash_u64(pattern, pattern_symmetries),
+            None => self.tpuzzle.pattern_hash_u64(pattern),
+        };
         // TODO: use modulo when the size is not a power of 2.
-        self.tpuzzle.pattern_hash_u64(pattern) as usize & self.prune_table_index_mask
+        pattern_hash as usize & self.prune_table_index_mask
     }
 
     // Returns a heurstic depth for the given pattern.
     fn lookup(&self, pattern: &TPuzzle::Pattern) -> Depth {
         let pattern_hash = self.hash_pattern(pattern);
-        let table_value = self.pattern_hash_to_depth[pattern_hash];
+        let table_value = self.pattern_hash_to_depth.get(pattern_hash);
         if table_value == UNINITIALIZED_SENTINEL {
             Depth((self.current_pruning_depth.0 as usize) + 1)
         } else {
@@ -61,20 +160,35 @@ impl<TPuzzle: SemiGroupActionPuzzle + HashablePatternPuzzle> HashPruneTableMutab
         }
     }
 
-    fn set_if_uninitialized(&mut self, pattern: &TPuzzle::Pattern, depth: DepthU8) {
+    fn set_if_uninitialized(&self, pattern: &TPuzzle::Pattern, depth: DepthU8) {
         let pattern_hash = self.hash_pattern(pattern);
-        if self.pattern_hash_to_depth[pattern_hash] == UNINITIALIZED_SENTINEL
-            || self.pattern_hash_to_depth[pattern_hash] == INVALID_PATTERN_SENTINEL
-        {
-            self.pattern_hash_to_depth[pattern_hash] = DepthU8(depth.0 + 1) // TODO: arithmetic on `Depth`
-        };
+        let invalid_pattern_sentinel =
+            invalid_pattern_sentinel(self.pattern_hash_to_depth.packing());
+        self.pattern_hash_to_depth.set_if(
+            pattern_hash,
+            DepthU8(depth.0 + 1), // TODO: arithmetic on `Depth`
+            |table_value| {
+                table_value == UNINITIALIZED_SENTINEL || table_value == invalid_pattern_sentinel
+            },
+        );
     }
 
-    fn set_invalid_depth(&mut self, pattern: &TPuzzle::Pattern) {
-        self.set_if_uninitialized(pattern, INVALID_PATTERN_DEPTH)
+    fn set_invalid_depth(&self, pattern: &TPuzzle::Pattern) {
+        self.set_if_uninitialized(
+            pattern,
+            invalid_pattern_depth(self.pattern_hash_to_depth.packing()),
+        )
     }
 }
 
+/// Options that only apply to a [`HashPruneTable`].
+#[derive(Clone, Debug, Default)]
+pub struct HashPruneTableOptions {
+    /// Whole-puzzle symmetries (e.g. rotations) whose group is used to share
+    /// entries between symmetric patterns.
+    pub symmetry_moves: Vec<Move>,
+}
+
 pub struct HashPruneTable<
     TPuzzle: SemiGroupActionPuzzle + HashablePatternPuzzle,
     TPatternValidityChecker: PatternValidityChecker<TPuzzle>,
@@ -90,21 +204,166 @@ impl<
         TPatternValidityChecker: PatternValidityChecker<TPuzzle>,
     > HashPruneTable<TPuzzle, TPatternValidityChecker>
 {
+    /// The number of entries in the table (filled or not).
+    pub fn size(&self) -> usize {
+        self.mutable.prune_table_size
+    }
+
+    /// The depth up to which the table has been filled.
+    pub fn pruning_depth(&self) -> Depth {
+        Depth(self.mutable.current_pruning_depth.0 as usize)
+    }
+
+    /// Counts the filled entries. This scans the whole table, so it should only be used for statistics.
+    pub fn num_filled_entries(&self) -> usize {
+        self.mutable.pattern_hash_to_depth.num_nonzero_entries()
+    }
+
+    /// How the entries are currently stored. This changes automatically as the table gets deeper.
+    pub fn entry_packing(&self) -> PruneTableEntryPacking {
+        self.mutable.pattern_hash_to_depth.packing()
+    }
+
+    // Returns the key and path of the cache file, or `None` if persistence is not enabled.
+    fn cache_file(&self) -> Option<(u64, PathBuf)> {
+        let persistence = self.mutable.persistence.as_ref()?;
+        let search_api_data = &self.immutable.search_api_data;
+        let key = PruneTableCacheKeyData {
+            puzzle_definition_hash: self.mutable.tpuzzle.puzzle_definition_hash_u64(),
+            generator_moves: search_api_data
+                .search_generators
+                .flat
+                .iter()
+                .map(
+                    |(_, move_transformation_info)| match move_transformation_info.cost {
+                        Depth(1) => move_transformation_info.r#move.to_string(),
+                        cost => format!("{}={}", move_transformation_info.r#move, cost.0),
+                    },
+                )
+                .collect(),
+            metric: &search_api_data.metric,
+            symmetry_moves: self
+                .mutable
+                .symmetry_moves
+                .iter()
+                .map(|r#move| r#move.to_string())
+                .collect(),
+            target_pattern_hashes: search_api_data
+                .target_patterns
+                .iter()
+                .map(|target_pattern| self.mutable.tpuzzle.pattern_hash_u64(target_pattern))
+                .collect(),
+            prune_table_size: self.mutable.prune_table_size,
+            entries_per_byte: self
+                .mutable
+                .pattern_hash_to_depth
+                .packing()
+                .entries_per_byte(),
+            pattern_validity_checker_name: type_name::<TPatternValidityChecker>(),
+        }
+        .key();
+        Some((
+            key,
+            prune_table_cache_file_path(&persistence.cache_dir, key),
+        ))
+    }
+
+    fn read_from_cache(&mut self, key: u64, cache_file_path: &Path) {
+        let packing = self.mutable.pattern_hash_to_depth.packing();
+        let prune_table_size = self.mutable.prune_table_size;
+        let num_bytes = self.mutable.pattern_hash_to_depth.num_bytes();
+        let mmap_prune_tables = self
+            .mutable
+            .persistence
+            .as_ref()
+            .is_some_and(|persistence| persistence.mmap_prune_tables);
+        let result = if mmap_prune_tables {
+            map_prune_table(cache_file_path, key, num_bytes).map(|mapped| {
+                mapped.map(|mapped| {
+                    (
+                        mapped.pruning_depth,
+                        PruneTableEntries::from_mapped(packing, prune_table_size, mapped),
+                    )
+                })
+            })
+        } else {
+            read_prune_table(cache_file_path, key, num_bytes).map(|persisted| {
+                persisted.map(|persisted| {
+                    (
+                        persisted.pruning_depth,
+                        PruneTableEntries::from_bytes(
+                            packing,
+                            prune_table_size,
+                            persisted.entry_bytes,
+                        ),
+                    )
+                })
+            })
+        };
+        match result {
+            Ok(Some((pruning_depth, pattern_hash_to_depth))) => {
+                if pruning_depth <= self.mutable.current_pruning_depth
+                    || pruning_depth > max_prune_table_depth(packing)
+                {
+                    return;
+                }
+                let Some(pattern_hash_to_depth) = pattern_hash_to_depth else {
+                    return;
+                };
+                self.mutable.recursive_work_tracker.print_message(&format!(
+                    "{} prune table (depth {}) from: {}",
+                    if mmap_prune_tables {
+                        "Mapped"
+                    } else {
+                        "Loaded"
+                    },
+                    pruning_depth.0,
+                    cache_file_path.display()
+                ));
+                self.mutable.pattern_hash_to_depth = pattern_hash_to_depth;
+                self.mutable.current_pruning_depth = pruning_depth;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                self.mutable.search_logger.write_error(&format!(
+                    "[Prune table] {}\n[Prune table] Ignoring this file and rebuilding the prune table instead.",
+                    e.description
+                ));
+            }
+        }
+    }
+
+    fn write_to_cache(&mut self, key: u64, cache_file_path: &Path) {
+        match write_prune_table(
+            cache_file_path,
+            key,
+            self.mutable.current_pruning_depth,
+            self.mutable.pattern_hash_to_depth.as_bytes(),
+        ) {
+            Ok(()) => self.mutable.recursive_work_tracker.print_message(&format!(
+                "Wrote prune table (depth {}) to: {}",
+                self.mutable.current_pruning_depth.0,
+                cache_file_path.display()
+            )),
+            Err(e) => self
+                .mutable
+                .search_logger
+                .write_warning(&format!("[Prune table] {}", e.description)),
+        }
+    }
+
+    // Calls `f` with the pattern, state, and remaining depth after each move that is allowed from the given pattern.
+    // Stops early if `f` returns `false`.
     // TODO: dedup with IDFSearch?
     // TODO: Store a reference to `search_api_data` so that you can't accidentally pass in the wrong `search_api_data`?
-    fn recurse(
+    fn for_each_child(
         immutable_data: &HashPruneTableImmutableData<TPuzzle>,
-        mutable_data: &mut HashPruneTableMutableData<TPuzzle>,
-        // TODO: Use a `PatternStack` to avoid allocations.
+        mutable_data: &HashPruneTableMutableData<TPuzzle>,
         current_pattern: &TPuzzle::Pattern,
         current_state: CanonicalFSMState,
         remaining_depth: PruneTableEntryType,
+        mut f: impl FnMut(&TPuzzle::Pattern, CanonicalFSMState, PruneTableEntryType) -> bool,
     ) {
-        mutable_data.recursive_work_tracker.record_recursive_call();
-        if remaining_depth == DepthU8(0) {
-            mutable_data.set_if_uninitialized(current_pattern, remaining_depth);
-            return;
-        }
         for (move_class_index, move_transformation_multiples) in immutable_data
             .search_api_data
             .search_generators
@@ -123,6 +382,11 @@ impl<
             };
 
             for move_transformation_info in move_transformation_multiples {
+                let Some(next_remaining_depth) =
+                    (remaining_depth.0 as usize).checked_sub(move_transformation_info.cost.0)
+                else {
+                    continue;
+                };
                 let Some(next_pattern) = mutable_data.tpuzzle.pattern_apply_transformation(
                     current_pattern,
                     &move_transformation_info.transformation,
@@ -134,15 +398,164 @@ impl<
                     mutable_data.set_invalid_depth(&next_pattern);
                     continue;
                 }
-                Self::recurse(
+                if !f(
+                    &next_pattern,
+                    next_state,
+                    DepthU8(next_remaining_depth as u8),
+                ) {
+                    return;
+                }
+            }
+        }
+    }
+
+    // This only needs shared access to the table, so it can run on several threads at once.
+    // Returns `false` if `should_stop` returned `true`.
+    fn recurse(
+        immutable_data: &HashPruneTableImmutableData<TPuzzle>,
+        mutable_data: &HashPruneTableMutableData<TPuzzle>,
+        // TODO: Use a `PatternStack` to avoid allocations.
+        current_pattern: &TPuzzle::Pattern,
+        current_state: CanonicalFSMState,
+        remaining_depth: PruneTableEntryType,
+        num_recursive_calls: &mut usize,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    ) -> bool {
+        *num_recursive_calls += 1;
+        if *num_recursive_calls % NUM_RECURSIVE_CALLS_BETWEEN_STOP_CHECKS == 0 && should_stop() {
+            return false;
+        }
+        if remaining_depth == DepthU8(0) {
+            mutable_data.set_if_uninitialized(current_pattern, remaining_depth);
+            return true;
+        }
+        let mut keep_going = true;
+        Self::for_each_child(
+            immutable_data,
+            mutable_data,
+            current_pattern,
+            current_state,
+            remaining_depth,
+            |next_pattern, next_state, next_remaining_depth| {
+                keep_going = Self::recurse(
                     immutable_data,
                     mutable_data,
-                    &next_pattern,
+                    next_pattern,
                     next_state,
-                    remaining_depth - DepthU8(1),
+                    next_remaining_depth,
+                    num_recursive_calls,
+                    should_stop,
+                );
+                keep_going
+            },
+        );
+        keep_going
+    }
+
+    // Fills in all the patterns at exactly `depth` from the target patterns,
+    // adding to `num_recursive_calls`. Returns `false` if the fill was stopped
+    // before it finished.
+    fn fill_depth(
+        &self,
+        depth: PruneTableEntryType,
+        should_stop: &(dyn Fn() -> bool + Sync),
+        num_recursive_calls: &mut usize,
+    ) -> bool {
+        let mut tasks: Vec<PruneTableFillTask<TPuzzle>> = self
+            .immutable
+            .search_api_data
+            .target_patterns
+            .iter()
+            .map(|target_pattern| PruneTableFillTask {
+                pattern: target_pattern.clone(),
+                state: CANONICAL_FSM_START_STATE,
+                remaining_depth: depth,
+            })
+            .collect();
+
+        let num_threads = self.immutable.search_api_data.num_threads;
+        if num_threads == 1 || depth < MIN_PARALLEL_FILL_DEPTH {
+            return tasks.iter().all(|task| {
+                Self::recurse(
+                    &self.immutable,
+                    &self.mutable,
+                    &task.pattern,
+                    task.state,
+                    task.remaining_depth,
+                    num_recursive_calls,
+                    should_stop,
                 )
+            });
+        }
+
+        // Split the fill into subtrees (like `IDFSearch` does for searches), and fill them using a pool of threads.
+        let mut split_depth = 0;
+        while tasks.len() < num_threads * MIN_PARALLEL_FILL_TASKS_PER_THREAD
+            && split_depth < MAX_PARALLEL_FILL_SPLIT_DEPTH
+        {
+            let mut subtasks = vec![];
+            for task in tasks {
+                if task.remaining_depth == DepthU8(0) {
+                    subtasks.push(task);
+                    continue;
+                }
+                *num_recursive_calls += 1;
+                Self::for_each_child(
+                    &self.immutable,
+                    &self.mutable,
+                    &task.pattern,
+                    task.state,
+                    task.remaining_depth,
+                    |next_pattern, next_state, next_remaining_depth| {
+                        subtasks.push(PruneTableFillTask {
+                            pattern: next_pattern.clone(),
+                            state: next_state,
+                            remaining_depth: next_remaining_depth,
+                        });
+                        true
+                    },
+                );
             }
+            tasks = subtasks;
+            split_depth += 1;
         }
+
+        let next_task_index = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            let thread_handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut num_recursive_calls = 0;
+                        let mut finished = true;
+                        while finished {
+                            let task_index = next_task_index.fetch_add(1, Ordering::Relaxed);
+                            let Some(task) = tasks.get(task_index) else {
+                                break;
+                            };
+                            finished = Self::recurse(
+                                &self.immutable,
+                                &self.mutable,
+                                &task.pattern,
+                                task.state,
+                                task.remaining_depth,
+                                &mut num_recursive_calls,
+                                should_stop,
+                            );
+                        }
+                        (num_recursive_calls, finished)
+                    })
+                })
+                .collect();
+            let mut all_finished = true;
+            for thread_handle in thread_handles {
+                let (thread_num_recursive_calls, finished) = thread_handle
+                    .join()
+                    .expect("Internal error: prune table thread panicked");
+                *num_recursive_calls += thread_num_recursive_calls;
+                all_finished &= finished;
+            }
+            all_finished
+        })
     }
 }
 
@@ -151,35 +564,69 @@ impl<
         TPatternValidityChecker: PatternValidityChecker<TPuzzle>,
     > PruneTable<TPuzzle> for HashPruneTable<TPuzzle, TPatternValidityChecker>
 {
+    type TableOptions = HashPruneTableOptions;
+
     fn new(
         tpuzzle: TPuzzle,
         search_api_data: Arc<IDFSearchAPIData<TPuzzle>>,
         search_logger: Arc<SearchLogger>,
-        min_size: Option<usize>,
-    ) -> Self {
-        let min_size = match min_size {
+        options: PruneTableConstructionOptions<HashPruneTableOptions>,
+    ) -> Result<Self, SearchError> {
+        let symmetry_moves = options.table_options.symmetry_moves;
+        let pattern_symmetries = if symmetry_moves.is_empty() {
+            None
+        } else {
+            let pattern_symmetries = tpuzzle.pattern_symmetries(
+                &symmetry_moves,
+                &search_api_data.search_generators,
+                &search_api_data.target_patterns,
+            )?;
+            search_logger.write_info("[Prune table] Sharing entries between symmetric patterns.");
+            Some(pattern_symmetries)
+        };
+        let min_size = match options.min_size {
             Some(min_size) => min_size.next_power_of_two(),
             None => DEFAULT_MIN_PRUNE_TABLE_SIZE,
         };
+        let packing = packing_for_pruning_depth(DepthU8(0));
         let mut prune_table = Self {
             immutable: HashPruneTableImmutableData { search_api_data },
             mutable: HashPruneTableMutableData {
                 tpuzzle,
                 min_size,
-                prune_table_size: min_size,
-                prune_table_index_mask: min_size - 1,
+                max_memory_bytes: options.max_memory_bytes,
+                logged_max_size: false,
+                prune_table_size: 1,
+                prune_table_index_mask: 0,
                 current_pruning_depth: DepthU8(0),
-                pattern_hash_to_depth: vec![DepthU8(0); min_size],
+                pattern_hash_to_depth: PruneTableEntries::new(packing, 1),
                 recursive_work_tracker: RecursiveWorkTracker::new(
                     "Prune table".to_owned(),
                     search_logger.clone(),
                 ),
                 search_logger,
+                persistence: options.persistence,
+                pattern_symmetries,
+                symmetry_moves,
+                attempted_cache_read: false,
+                generation_duration: Duration::ZERO,
             },
             phantom_validity_checker: PhantomData,
         };
-        prune_table.extend_for_search_depth(Depth(0), 1);
-        prune_table
+        if let Some(max_size) = prune_table.mutable.max_size(packing) {
+            if min_size > max_size {
+                prune_table.mutable.search_logger.write_warning(&format!(
+                    "[Prune table] Minimum size of {} entries exceeds the memory limit, using {} entries instead.",
+                    min_size.separate_with_underscores(),
+                    max_size.separate_with_underscores()
+                ));
+                prune_table.mutable.min_size = max_size;
+            }
+        }
+        let min_size = prune_table.mutable.min_size;
+        prune_table.mutable.reset(min_size, packing);
+        prune_table.extend_for_search_depth(Depth(0), 1, &|| false);
+        Ok(prune_table)
     }
 
     // Returns a heuristic depth for the given pattern.
@@ -189,59 +636,514 @@ impl<
 
     // TODO: dedup with IDFSearch?
     // TODO: Store a reference to `search_api_data` so that you can't accidentally pass in the wrong `search_api_data`?
-    fn extend_for_search_depth(&mut self, search_depth: Depth, approximate_num_entries: usize) {
+    fn extend_for_search_depth(
+        &mut self,
+        search_depth: Depth,
+        approximate_num_entries: usize,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    ) {
         let mut new_pruning_depth = DepthU8(
             std::convert::TryInto::<u8>::try_into(search_depth.0 / 2)
                 .expect("Prune table depth exceeded available size"),
         );
-        if new_pruning_depth > MAX_PRUNE_TABLE_DEPTH {
+        let max_depth = max_prune_table_depth(PruneTableEntryPacking::Byte);
+        if new_pruning_depth > max_depth {
             self.mutable.search_logger.write_warning(&format!(
                 "[Prune table] Exceeded max depth, limiting to {:?}.",
-                MAX_PRUNE_TABLE_DEPTH
+                max_depth
             ));
-            new_pruning_depth = MAX_PRUNE_TABLE_DEPTH;
+            new_pruning_depth = max_depth;
         }
+        // Once entries take a full byte, they keep doing so.
+        let packing = match self.mutable.pattern_hash_to_depth.packing() {
+            PruneTableEntryPacking::Byte => PruneTableEntryPacking::Byte,
+            PruneTableEntryPacking::Nibble => packing_for_pruning_depth(new_pruning_depth),
+        };
 
-        let new_prune_table_size = usize::max(
+        let mut new_prune_table_size = usize::max(
             usize::next_power_of_two(approximate_num_entries),
             self.mutable.min_size,
         );
-        match new_prune_table_size.cmp(&self.mutable.prune_table_size) {
-            std::cmp::Ordering::Less => {
-                // Don't shrink the prune table.
-                return;
+        if packing != self.mutable.pattern_hash_to_depth.packing() {
+            // Keep (at least) the current number of entries if the memory limit allows it.
+            new_prune_table_size = usize::max(new_prune_table_size, self.mutable.prune_table_size);
+        }
+        if let Some(max_size) = self.mutable.max_size(packing) {
+            if new_prune_table_size > max_size {
+                self.mutable.log_max_size(max_size, new_prune_table_size);
+                new_prune_table_size = max_size;
             }
-            std::cmp::Ordering::Equal => {
-                if new_pruning_depth <= self.mutable.current_pruning_depth {
+        }
+        let log_resize = |search_logger: &SearchLogger| {
+            search_logger.log(SearchLogEvent::PruneTableResized {
+                num_entries: new_prune_table_size,
+                entry_packing: packing,
+                pruning_depth: Depth(new_pruning_depth.0 as usize),
+            })
+        };
+        if packing != self.mutable.pattern_hash_to_depth.packing() {
+            log_resize(&self.mutable.search_logger);
+            self.mutable.reset(new_prune_table_size, packing);
+        } else {
+            match new_prune_table_size.cmp(&self.mutable.prune_table_size) {
+                std::cmp::Ordering::Less => {
+                    // Don't shrink the prune table.
                     return;
                 }
+                std::cmp::Ordering::Equal => {
+                    if new_pruning_depth <= self.mutable.current_pruning_depth {
+                        return;
+                    }
+                }
+                std::cmp::Ordering::Greater => {
+                    log_resize(&self.mutable.search_logger);
+                    self.mutable.reset(new_prune_table_size, packing);
+                }
             }
-            std::cmp::Ordering::Greater => {
-                self.mutable.recursive_work_tracker.print_message(&format!(
-                    "Increasing prune table size to {} entries…",
-                    new_prune_table_size.separate_with_underscores()
-                ));
-                self.mutable.pattern_hash_to_depth = vec![DepthU8(0); new_prune_table_size];
-                self.mutable.prune_table_size = new_prune_table_size;
-                self.mutable.prune_table_index_mask = new_prune_table_size - 1;
-                self.mutable.current_pruning_depth = DepthU8(0);
+        }
+
+        let cache_file = self.cache_file();
+        if let Some((key, cache_file_path)) = &cache_file {
+            if !self.mutable.attempted_cache_read {
+                self.mutable.attempted_cache_read = true;
+                self.read_from_cache(*key, cache_file_path);
+                if new_pruning_depth <= self.mutable.current_pruning_depth {
+                    return;
+                }
             }
         }
 
+        if self.mutable.pattern_hash_to_depth.is_read_only() {
+            self.mutable.recursive_work_tracker.print_message(
+                "Copying the mapped prune table into memory, so that it can be extended…",
+            );
+            self.mutable.pattern_hash_to_depth.make_writable();
+        }
+
+        let generation_start_time = instant::Instant::now();
         for depth_as_u8 in (*self.mutable.current_pruning_depth + 1)..(*new_pruning_depth + 1) {
             let depth = DepthU8(depth_as_u8);
             self.mutable
                 .recursive_work_tracker
                 .start_depth(Depth(*depth as usize), None);
-            Self::recurse(
-                &self.immutable,
-                &mut self.mutable,
-                &self.immutable.search_api_data.target_pattern,
-                CANONICAL_FSM_START_STATE,
-                depth,
-            );
+            let mut num_recursive_calls = 0;
+            let finished = self.fill_depth(depth, should_stop, &mut num_recursive_calls);
+            self.mutable
+                .recursive_work_tracker
+                .record_recursive_calls(num_recursive_calls);
             self.mutable.recursive_work_tracker.finish_latest_depth();
+            if !finished {
+                break;
+            }
+            self.mutable.current_pruning_depth = depth;
         }
-        self.mutable.current_pruning_depth = new_pruning_depth
+        self.mutable.generation_duration += instant::Instant::now() - generation_start_time;
+        // Entries of a partially filled depth are still valid lower bounds, but the table is only written once it is complete.
+        if self.mutable.current_pruning_depth < new_pruning_depth {
+            return;
+        }
+
+        if let Some((key, cache_file_path)) = &cache_file {
+            if self
+                .mutable
+                .persistence
+                .as_ref()
+                .is_some_and(|persistence| {
+                    persistence.should_write(self.mutable.generation_duration)
+                })
+            {
+                self.write_to_cache(*key, cache_file_path);
+            }
+        }
+    }
+
+    fn statistics(&self) -> PruneTableStatistics {
+        PruneTableStatistics {
+            num_entries: self.size(),
+            num_bytes: self.mutable.pattern_hash_to_depth.num_bytes(),
+            pruning_depth: self.pruning_depth(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use cubing::{
+        alg::{parse_alg, parse_move},
+        kpuzzle::KPuzzle,
+        puzzles::cube2x2x2_kpuzzle,
+    };
+
+    use crate::_internal::{
+        cli::args::{EnableAutoAlwaysNeverValueEnum, VerbosityLevel},
+        search::{
+            idf_search::idf_search::{IDFSearch, IDFSearchConstructionOptions},
+            prune_table_entries::PruneTableEntryPacking,
+            prune_table_persistence::{write_prune_table, PruneTablePersistenceOptions},
+            prune_table_trait::{Depth, PruneTable},
+            search_logger::{SearchLogEvent, SearchLogLevel, SearchLogSink, SearchLogger},
+        },
+    };
+
+    use super::{max_prune_table_depth, DepthU8};
+
+    #[derive(Default)]
+    struct WarningCollector {
+        warnings: Mutex<Vec<String>>,
+    }
+
+    impl SearchLogSink for WarningCollector {
+        fn log(&self, event: &SearchLogEvent) {
+            if let SearchLogEvent::Message {
+                level: SearchLogLevel::Warning,
+                message,
+            } = event
+            {
+                self.warnings.lock().unwrap().push(message.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn hash_prune_table_entry_packing_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let idf_search = <IDFSearch>::try_new(
+            kpuzzle.clone(),
+            vec![parse_move!("U")],
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(Default::default()),
+                ..Default::default()
+            },
+        )
+        .map_err(|e| e.description)?;
+        let mut prune_table = idf_search.prune_table.lock().unwrap();
+        assert_eq!(prune_table.entry_packing(), PruneTableEntryPacking::Nibble);
+
+        let u_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("U"))
+            .unwrap();
+        let unreachable_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R"))
+            .unwrap();
+        for (search_depth, expected_packing) in [
+            (Depth(26), PruneTableEntryPacking::Nibble),
+            (Depth(28), PruneTableEntryPacking::Byte),
+        ] {
+            prune_table.extend_for_search_depth(search_depth, 1 << 10, &|| false);
+            assert_eq!(prune_table.entry_packing(), expected_packing);
+            assert_eq!(prune_table.pruning_depth(), Depth(search_depth.0 / 2));
+            assert_eq!(prune_table.lookup(&u_pattern), Depth(0));
+            assert_eq!(
+                prune_table.lookup(&unreachable_pattern),
+                Depth(search_depth.0 / 2 + 1)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hash_prune_table_parallel_fill_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let prune_tables = [1, 4]
+            .into_iter()
+            .map(|num_threads| {
+                <IDFSearch>::try_new(
+                    kpuzzle.clone(),
+                    vec![parse_move!("U"), parse_move!("F"), parse_move!("R")],
+                    kpuzzle.default_pattern(),
+                    IDFSearchConstructionOptions {
+                        search_logger: Arc::new(Default::default()),
+                        num_threads: Some(num_threads),
+                        ..Default::default()
+                    },
+                )
+                .map(|idf_search| idf_search.prune_table)
+                .map_err(|e| e.description)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        for prune_table in &prune_tables {
+            prune_table
+                .lock()
+                .unwrap()
+                .extend_for_search_depth(Depth(12), 1 << 16, &|| false);
+        }
+        let single_threaded = prune_tables[0].lock().unwrap();
+        let multi_threaded = prune_tables[1].lock().unwrap();
+        assert_eq!(multi_threaded.pruning_depth(), Depth(6));
+        assert_eq!(
+            single_threaded.num_filled_entries(),
+            multi_threaded.num_filled_entries()
+        );
+        let mut pattern = kpuzzle.default_pattern();
+        for alg in ["R", "U2", "F'", "R2", "U'", "F", "R", "U", "F2"] {
+            pattern = pattern.apply_alg(&alg.parse().unwrap()).unwrap();
+            assert_eq!(
+                single_threaded.lookup(&pattern),
+                multi_threaded.lookup(&pattern)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hash_prune_table_memory_limit_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let max_memory_bytes = 1 << 12;
+        let warning_collector = Arc::new(WarningCollector::default());
+        let idf_search = <IDFSearch>::try_new(
+            kpuzzle.clone(),
+            vec![parse_move!("U")],
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(SearchLogger {
+                    verbosity: VerbosityLevel::Warning,
+                    sink: Some(warning_collector.clone()),
+                }),
+                max_prune_table_memory_bytes: Some(max_memory_bytes),
+                ..Default::default()
+            },
+        )
+        .map_err(|e| e.description)?;
+        let mut prune_table = idf_search.prune_table.lock().unwrap();
+        // The last depth switches to one entry per byte.
+        for search_depth in [Depth(2), Depth(4), Depth(28)] {
+            prune_table.extend_for_search_depth(search_depth, 1 << 20, &|| false);
+            assert!(prune_table.statistics().num_bytes <= max_memory_bytes);
+        }
+        assert_eq!(prune_table.entry_packing(), PruneTableEntryPacking::Byte);
+        let num_cap_warnings = warning_collector
+            .warnings
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|warning| warning.contains("will not grow beyond"))
+            .count();
+        assert_eq!(num_cap_warnings, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn hash_prune_table_stopped_fill_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let prune_tables = (0..2)
+            .map(|_| {
+                <IDFSearch>::try_new(
+                    kpuzzle.clone(),
+                    vec![parse_move!("U"), parse_move!("F"), parse_move!("R")],
+                    kpuzzle.default_pattern(),
+                    IDFSearchConstructionOptions {
+                        search_logger: Arc::new(Default::default()),
+                        ..Default::default()
+                    },
+                )
+                .map(|idf_search| idf_search.prune_table)
+                .map_err(|e| e.description)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut resumed = prune_tables[0].lock().unwrap();
+        let mut uninterrupted = prune_tables[1].lock().unwrap();
+
+        resumed.extend_for_search_depth(Depth(12), 1 << 16, &|| true);
+        assert!(resumed.pruning_depth() < Depth(6));
+        resumed.extend_for_search_depth(Depth(12), 1 << 16, &|| false);
+        uninterrupted.extend_for_search_depth(Depth(12), 1 << 16, &|| false);
+        assert_eq!(resumed.pruning_depth(), Depth(6));
+        assert_eq!(
+            resumed.num_filled_entries(),
+            uninterrupted.num_filled_entries()
+        );
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct PruneTableLogCollector {
+        messages: Mutex<Vec<String>>,
+        num_filled_depths: Mutex<usize>,
+    }
+
+    impl SearchLogSink for PruneTableLogCollector {
+        fn log(&self, event: &SearchLogEvent) {
+            match event {
+                SearchLogEvent::Message { message, .. } => {
+                    self.messages.lock().unwrap().push(message.to_string())
+                }
+                SearchLogEvent::DepthStarted {
+                    work_name: "Prune table",
+                    ..
+                } => *self.num_filled_depths.lock().unwrap() += 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn persisted_idf_search(
+        cache_dir: &Path,
+        mmap_prune_tables: bool,
+        search_logger: Arc<SearchLogger>,
+    ) -> IDFSearch<KPuzzle> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        <IDFSearch>::try_new(
+            kpuzzle.clone(),
+            vec![parse_move!("U"), parse_move!("F"), parse_move!("R")],
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                search_logger,
+                prune_table_persistence: Some(PruneTablePersistenceOptions {
+                    cache_dir: cache_dir.to_owned(),
+                    write_prune_tables: EnableAutoAlwaysNeverValueEnum::Always,
+                    mmap_prune_tables,
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn hash_prune_table_mmap_test() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "twsearch-hash-prune-table-mmap-test-{}",
+            std::process::id()
+        ));
+        let pattern = cube2x2x2_kpuzzle()
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U2 F'"))
+            .unwrap();
+
+        let built =
+            persisted_idf_search(&cache_dir, true, Arc::new(Default::default())).prune_table;
+        let mut built = built.lock().unwrap();
+        built.extend_for_search_depth(Depth(8), 1 << 16, &|| false);
+        // There is no file to map yet, so the table is built (and then written).
+        assert!(!built.mutable.pattern_hash_to_depth.is_read_only());
+
+        let reopened =
+            persisted_idf_search(&cache_dir, true, Arc::new(Default::default())).prune_table;
+        let mut reopened = reopened.lock().unwrap();
+        reopened.extend_for_search_depth(Depth(8), 1 << 16, &|| false);
+        assert!(reopened.mutable.pattern_hash_to_depth.is_read_only());
+        assert_eq!(reopened.pruning_depth(), Depth(4));
+        assert_eq!(reopened.num_filled_entries(), built.num_filled_entries());
+        assert_eq!(reopened.lookup(&pattern), built.lookup(&pattern));
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    struct PersistedPruneTableResult {
+        pruning_depth: Depth,
+        num_filled_entries: usize,
+        num_filled_depths: usize,
+        messages: Vec<String>,
+    }
+
+    // Extends a persisted prune table to depth 4, after `prepare_cache_file`
+    // is called with the key, path, and size (in bytes) of its cache file.
+    fn extend_persisted_prune_table(
+        cache_dir: &Path,
+        prepare_cache_file: impl FnOnce(u64, PathBuf, usize),
+    ) -> PersistedPruneTableResult {
+        let log_collector = Arc::new(PruneTableLogCollector::default());
+        let search_logger = Arc::new(SearchLogger {
+            verbosity: VerbosityLevel::Info,
+            sink: Some(log_collector.clone()),
+        });
+        let prune_table = persisted_idf_search(cache_dir, false, search_logger).prune_table;
+        let mut prune_table = prune_table.lock().unwrap();
+        let (key, cache_file_path) = prune_table.cache_file().unwrap();
+        prepare_cache_file(
+            key,
+            cache_file_path,
+            prune_table.mutable.pattern_hash_to_depth.num_bytes(),
+        );
+        prune_table.extend_for_search_depth(Depth(8), 1 << 16, &|| false);
+        let messages = log_collector.messages.lock().unwrap().clone();
+        let num_filled_depths = *log_collector.num_filled_depths.lock().unwrap();
+        PersistedPruneTableResult {
+            pruning_depth: prune_table.pruning_depth(),
+            num_filled_entries: prune_table.num_filled_entries(),
+            num_filled_depths,
+            messages,
+        }
+    }
+
+    #[test]
+    fn hash_prune_table_persistence_test() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "twsearch-hash-prune-table-persistence-test-{}",
+            std::process::id()
+        ));
+
+        let built = extend_persisted_prune_table(&cache_dir, |_, _, _| {});
+        assert_eq!(built.pruning_depth, Depth(4));
+        assert_eq!(built.num_filled_depths, 4);
+
+        // The cached table is loaded and used, instead of filling any depths.
+        let loaded = extend_persisted_prune_table(&cache_dir, |_, _, _| {});
+        assert_eq!(loaded.pruning_depth, Depth(4));
+        assert_eq!(loaded.num_filled_entries, built.num_filled_entries);
+        assert_eq!(loaded.num_filled_depths, 0);
+        assert!(loaded
+            .messages
+            .iter()
+            .any(|message| message.contains("Loaded prune table (depth 4)")));
+
+        let assert_rebuilt = |rebuilt: PersistedPruneTableResult, expected_error: Option<&str>| {
+            assert_eq!(rebuilt.pruning_depth, Depth(4));
+            assert_eq!(rebuilt.num_filled_entries, built.num_filled_entries);
+            assert_eq!(rebuilt.num_filled_depths, 4);
+            if let Some(expected_error) = expected_error {
+                assert!(rebuilt
+                    .messages
+                    .iter()
+                    .any(|message| message.contains(expected_error)));
+            }
+        };
+
+        // Stale key.
+        assert_rebuilt(
+            extend_persisted_prune_table(&cache_dir, |key, cache_file_path, num_bytes| {
+                write_prune_table(&cache_file_path, key ^ 1, DepthU8(4), &vec![0; num_bytes])
+                    .unwrap()
+            }),
+            Some("different puzzle"),
+        );
+        // Stale size.
+        assert_rebuilt(
+            extend_persisted_prune_table(&cache_dir, |key, cache_file_path, num_bytes| {
+                write_prune_table(&cache_file_path, key, DepthU8(4), &vec![0; num_bytes / 2])
+                    .unwrap()
+            }),
+            Some("table size does not match"),
+        );
+        // Stale depth (deeper than the entries can store).
+        assert_rebuilt(
+            extend_persisted_prune_table(&cache_dir, |key, cache_file_path, num_bytes| {
+                let pruning_depth =
+                    DepthU8(max_prune_table_depth(PruneTableEntryPacking::Nibble).0 + 1);
+                write_prune_table(&cache_file_path, key, pruning_depth, &vec![0; num_bytes])
+                    .unwrap()
+            }),
+            None,
+        );
+        // Corrupted checksum.
+        assert_rebuilt(
+            extend_persisted_prune_table(&cache_dir, |_, cache_file_path, _| {
+                let mut bytes = fs::read(&cache_file_path).unwrap();
+                *bytes.last_mut().unwrap() ^= 1;
+                fs::write(&cache_file_path, bytes).unwrap();
+            }),
+            Some("checksum does not match"),
+        );
+
+        fs::remove_dir_all(&cache_dir).unwrap();
     }
 }
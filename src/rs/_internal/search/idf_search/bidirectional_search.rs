@@ -0,0 +1,710 @@
+use std::{
+    collections::HashMap,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use cubing::{
+    alg::{Alg, Move},
+    kpuzzle::{KPattern, KPuzzle, KTransformation},
+};
+use thousands::Separable;
+
+use crate::_internal::{
+    canonical_fsm::{
+        canonical_fsm::{CanonicalFSMState, CANONICAL_FSM_START_STATE},
+        search_generators::{FlatMoveIndex, MoveTransformationInfo},
+    },
+    errors::SearchError,
+    search::{
+        indexed_vec::IndexedVec,
+        prune_table_trait::Depth,
+        search_logger::SearchLogEvent,
+        search_statistics::{SearchDepthStatistics, SearchStatistics},
+    },
+};
+
+use super::idf_search::{
+    IDFSearchAPIData, IDFSearchConstructionOptions, IndividualSearchData, IndividualSearchOptions,
+    SearchEndReason, SearchSolutions,
+};
+
+// Searching shallow depths is faster than spinning up threads.
+const MIN_PARALLEL_FORWARD_SEARCH_DEPTH: Depth = Depth(3);
+
+// Marks the end of the list of sequences for a pattern in a `Frontier`.
+const NO_SEQUENCE: u32 = u32::MAX;
+
+// The backward half of every canonical move sequence (of a given depth) that
+// ends at a target pattern, grouped by the pattern where it starts.
+//
+// The sequences for each pattern form a linked list in flat arrays, so that
+// each entry only takes a few bytes in addition to its pattern.
+struct Frontier {
+    depth: Depth,
+    // The number of bytes that each `KPattern` uses for its data.
+    pattern_num_bytes: usize,
+    // The index of the latest sequence that starts at each pattern.
+    latest_sequence_indices: HashMap<KPattern, u32>,
+    // The moves of all sequences, `depth` moves each.
+    moves: Vec<FlatMoveIndex>,
+    // For each sequence, the index of the previous sequence that starts at the same pattern (or `NO_SEQUENCE`).
+    previous_sequence_indices: Vec<u32>,
+}
+
+impl Frontier {
+    fn new(depth: Depth, pattern_num_bytes: usize) -> Self {
+        Self {
+            depth,
+            pattern_num_bytes,
+            latest_sequence_indices: Default::default(),
+            moves: vec![],
+            previous_sequence_indices: vec![],
+        }
+    }
+
+    // Returns `false` (without adding the sequence) if there are too many sequences to index.
+    fn insert(&mut self, pattern: KPattern, moves: &[FlatMoveIndex]) -> bool {
+        let Ok(sequence_index) = u32::try_from(self.previous_sequence_indices.len()) else {
+            return false;
+        };
+        if sequence_index == NO_SEQUENCE {
+            return false;
+        }
+        let latest_sequence_index = self
+            .latest_sequence_indices
+            .entry(pattern)
+            .or_insert(NO_SEQUENCE);
+        self.previous_sequence_indices.push(*latest_sequence_index);
+        *latest_sequence_index = sequence_index;
+        self.moves.extend_from_slice(moves);
+        true
+    }
+
+    fn num_patterns(&self) -> usize {
+        self.latest_sequence_indices.len()
+    }
+
+    // An estimate of the memory used by the entries (not counting unused capacity).
+    fn num_bytes(&self) -> usize {
+        self.latest_sequence_indices.len() * (size_of::<(KPattern, u32)>() + self.pattern_num_bytes)
+            + self.moves.len() * size_of::<FlatMoveIndex>()
+            + self.previous_sequence_indices.len() * size_of::<u32>()
+    }
+
+    fn sequences_starting_at<'a>(
+        &'a self,
+        pattern: &KPattern,
+    ) -> impl Iterator<Item = &'a [FlatMoveIndex]> + 'a {
+        let mut sequence_index = self
+            .latest_sequence_indices
+            .get(pattern)
+            .copied()
+            .unwrap_or(NO_SEQUENCE);
+        std::iter::from_fn(move || {
+            if sequence_index == NO_SEQUENCE {
+                return None;
+            }
+            let index = sequence_index as usize;
+            sequence_index = self.previous_sequence_indices[index];
+            Some(&self.moves[index * self.depth.0..(index + 1) * self.depth.0])
+        })
+    }
+}
+
+// Owned by the forward search for a single depth.
+struct ForwardSearchData<'a> {
+    frontier: &'a Frontier,
+    forward_moves: Vec<&'a MoveTransformationInfo<KPuzzle>>,
+    statistics: SearchDepthStatistics,
+}
+
+/// A meet-in-the-middle search: for each depth, it enumerates the move
+/// sequences of half that depth that end at a target pattern (using inverse
+/// moves) and stores them in a hash map. It then searches forward from the
+/// search pattern for the other half of the depth, and joins the two halves
+/// wherever their patterns match.
+///
+/// This uses no prune table, so it is useful for deep searches on puzzles
+/// whose prune tables are weak. Instead, its memory use is dominated by the
+/// backward half, which is limited by
+/// [`max_prune_table_memory_bytes`](IDFSearchConstructionOptions::max_prune_table_memory_bytes). Both halves use the same [`SearchGenerators`](crate::_internal::canonical_fsm::search_generators::SearchGenerators)
+/// and canonical FSM as [`IDFSearch`](super::idf_search::IDFSearch), so the
+/// solutions are the same (but may be returned in a different order).
+pub struct BidirectionalSearch {
+    api_data: Arc<IDFSearchAPIData<KPuzzle>>,
+    inverse_transformations: Arc<IndexedVec<FlatMoveIndex, KTransformation>>,
+    max_frontier_memory_bytes: Option<usize>,
+}
+
+impl BidirectionalSearch {
+    pub fn try_new(
+        kpuzzle: KPuzzle,
+        generator_moves: Vec<Move>,
+        target_pattern: KPattern,
+        options: IDFSearchConstructionOptions,
+    ) -> Result<Self, SearchError> {
+        Self::try_new_with_target_patterns(kpuzzle, generator_moves, vec![target_pattern], options)
+    }
+
+    /// Prune table options are ignored, except for `max_prune_table_memory_bytes` (see above).
+    pub fn try_new_with_target_patterns(
+        kpuzzle: KPuzzle,
+        generator_moves: Vec<Move>,
+        target_patterns: Vec<KPattern>,
+        options: IDFSearchConstructionOptions,
+    ) -> Result<Self, SearchError> {
+        let api_data =
+            IDFSearchAPIData::try_new(kpuzzle, generator_moves, target_patterns, &options)?;
+        // The halves are split by depth, which only works if every move has the same cost.
+        if !api_data.unit_move_costs {
+            return Err("Move costs are not supported for bidirectional search.".into());
+        }
+        let inverse_transformations = IndexedVec::new(
+            api_data
+                .search_generators
+                .flat
+                .iter()
+                .map(|(_, move_transformation_info)| {
+                    move_transformation_info.transformation.invert()
+                })
+                .collect(),
+        );
+        Ok(Self {
+            api_data: Arc::new(api_data),
+            inverse_transformations: Arc::new(inverse_transformations),
+            max_frontier_memory_bytes: options.max_prune_table_memory_bytes,
+        })
+    }
+
+    pub fn search(
+        &mut self,
+        search_pattern: &KPattern,
+        individual_search_options: IndividualSearchOptions,
+    ) -> SearchSolutions {
+        let (individual_search_data, search_solutions) = IndividualSearchData::start(
+            individual_search_options,
+            self.api_data.search_logger.clone(),
+        );
+        let search_pattern = search_pattern.clone();
+
+        // Threads are not available in WASM.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let bidirectional_search = Self {
+                api_data: self.api_data.clone(),
+                inverse_transformations: self.inverse_transformations.clone(),
+                max_frontier_memory_bytes: self.max_frontier_memory_bytes,
+            };
+            std::thread::spawn(move || {
+                bidirectional_search.search_synchronously(search_pattern, individual_search_data)
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.search_synchronously(search_pattern, individual_search_data);
+
+        search_solutions
+    }
+
+    fn search_synchronously(
+        &self,
+        search_pattern: KPattern,
+        individual_search_data: IndividualSearchData,
+    ) {
+        let individual_search_options = &individual_search_data.individual_search_options;
+        let initial_state = self
+            .api_data
+            .apply_optional_fsm_moves(
+                CANONICAL_FSM_START_STATE,
+                &individual_search_options.canonical_fsm_pre_moves,
+            )
+            .expect("TODO: invalid canonical FSM pre-moves.");
+
+        let mut cached_frontier: Option<Frontier> = None;
+        let mut statistics = SearchStatistics::default();
+        for depth in
+            *individual_search_options.get_min_depth()..*individual_search_options.get_max_depth()
+        {
+            if individual_search_data.is_stopped() || individual_search_data.check_time_limit() {
+                break;
+            }
+            let depth_start_time = instant::Instant::now();
+            let depth_start_pause_duration = individual_search_data.total_pause_duration();
+            let backward_depth = Depth(depth / 2);
+            let forward_depth = Depth(depth) - backward_depth;
+            self.api_data.search_logger.write_info("----------------");
+            // The frontier only changes every other depth.
+            if cached_frontier.as_ref().map(|frontier| frontier.depth) != Some(backward_depth) {
+                // Free the old frontier before building the new one.
+                cached_frontier.take();
+                let Some(frontier) = self.build_frontier(&individual_search_data, backward_depth)
+                else {
+                    break;
+                };
+                cached_frontier = Some(frontier);
+            }
+            let Some(frontier) = &cached_frontier else {
+                break;
+            };
+            self.api_data
+                .search_logger
+                .log(SearchLogEvent::DepthStarted {
+                    work_name: "Search",
+                    depth: Depth(depth),
+                    message: Some(&format!("Searching forward {} moves.", forward_depth.0)),
+                });
+            let mut forward_search_data = ForwardSearchData {
+                frontier,
+                forward_moves: vec![],
+                statistics: SearchDepthStatistics::new(Depth(depth)),
+            };
+            let done = if self.api_data.num_threads > 1
+                && forward_depth >= MIN_PARALLEL_FORWARD_SEARCH_DEPTH
+            {
+                self.search_forward_in_parallel(
+                    &individual_search_data,
+                    &mut forward_search_data,
+                    &search_pattern,
+                    initial_state,
+                    forward_depth,
+                )
+            } else {
+                self.search_forward(
+                    &individual_search_data,
+                    &mut forward_search_data,
+                    &search_pattern,
+                    initial_state,
+                    forward_depth,
+                )
+            };
+            let mut depth_statistics = forward_search_data.statistics;
+            depth_statistics.duration = (instant::Instant::now() - depth_start_time)
+                .saturating_sub(
+                    individual_search_data.total_pause_duration() - depth_start_pause_duration,
+                );
+            self.api_data
+                .search_logger
+                .log(SearchLogEvent::DepthFinished {
+                    work_name: "Search",
+                    depth: Depth(depth),
+                    num_recursive_calls: depth_statistics.num_recursive_calls,
+                    duration: depth_statistics.duration,
+                });
+            statistics.depths.push(depth_statistics);
+            if done || individual_search_data.finish_depth() {
+                break;
+            }
+        }
+        individual_search_data.send_end_of_search(statistics);
+    }
+
+    // Returns `None` if the search was stopped while building the frontier
+    // (including when the frontier would exceed the memory limit).
+    fn build_frontier(
+        &self,
+        individual_search_data: &IndividualSearchData,
+        backward_depth: Depth,
+    ) -> Option<Frontier> {
+        // SAFETY: We only use the length.
+        let pattern_num_bytes = unsafe { self.api_data.target_patterns[0].byte_slice() }.len();
+        let mut frontier = Frontier::new(backward_depth, pattern_num_bytes);
+        let mut backward_moves = vec![];
+        let identity = self.api_data.tpuzzle.identity_transformation();
+        if !self.extend_frontier(
+            individual_search_data,
+            &mut frontier,
+            &identity,
+            CANONICAL_FSM_START_STATE,
+            backward_depth,
+            &mut backward_moves,
+        ) {
+            return None;
+        }
+        self.api_data.search_logger.write_info(&format!(
+            "[Search] Built a frontier of {} patterns at depth {} from the target patterns.",
+            frontier.num_patterns().separate_with_underscores(),
+            backward_depth.0
+        ));
+        Some(frontier)
+    }
+
+    // Enumerates the canonical move sequences of `remaining_depth` more moves,
+    // with `inverse_transformation` being the inverse of the moves so far.
+    //
+    // Returns `false` if the search was stopped.
+    fn extend_frontier(
+        &self,
+        individual_search_data: &IndividualSearchData,
+        frontier: &mut Frontier,
+        inverse_transformation: &KTransformation,
+        current_state: CanonicalFSMState,
+        remaining_depth: Depth,
+        backward_moves: &mut Vec<FlatMoveIndex>,
+    ) -> bool {
+        if remaining_depth == Depth(0) {
+            for target_pattern in &self.api_data.target_patterns {
+                let inserted = frontier.insert(
+                    target_pattern.apply_transformation(inverse_transformation),
+                    backward_moves,
+                );
+                let exceeds_memory_limit = self
+                    .max_frontier_memory_bytes
+                    .is_some_and(|max_memory_bytes| frontier.num_bytes() > max_memory_bytes);
+                if !inserted || exceeds_memory_limit {
+                    self.api_data.search_logger.write_error(&format!(
+                        "[Search] The frontier at depth {} does not fit in the memory limit ({} patterns so far).",
+                        frontier.depth.0,
+                        frontier.num_patterns().separate_with_underscores()
+                    ));
+                    individual_search_data.stop(SearchEndReason::ExceededMemoryLimit);
+                    return false;
+                }
+            }
+            return true;
+        }
+        if individual_search_data.is_stopped() || individual_search_data.check_time_limit() {
+            return false;
+        }
+        for (flat_move_index, move_transformation_info) in
+            self.api_data.search_generators.flat.iter()
+        {
+            let Some(next_state) = self
+                .api_data
+                .canonical_fsm
+                .next_state(current_state, move_transformation_info.move_class_index)
+            else {
+                continue;
+            };
+            // Applying the new move first undoes it before the moves so far.
+            let next_inverse_transformation = self
+                .inverse_transformations
+                .at(flat_move_index)
+                .apply_transformation(inverse_transformation);
+            backward_moves.push(flat_move_index);
+            let keep_going = self.extend_frontier(
+                individual_search_data,
+                frontier,
+                &next_inverse_transformation,
+                next_state,
+                remaining_depth - Depth(1),
+                backward_moves,
+            );
+            backward_moves.pop();
+            if !keep_going {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Returns whether the search should stop.
+    fn search_forward<'a>(
+        &'a self,
+        individual_search_data: &IndividualSearchData,
+        forward_search_data: &mut ForwardSearchData<'a>,
+        current_pattern: &KPattern,
+        current_state: CanonicalFSMState,
+        remaining_depth: Depth,
+    ) -> bool {
+        forward_search_data.statistics.num_recursive_calls += 1;
+        if remaining_depth == Depth(0) {
+            for backward_moves in forward_search_data
+                .frontier
+                .sequences_starting_at(current_pattern)
+            {
+                if self.join(
+                    individual_search_data,
+                    current_state,
+                    &forward_search_data.forward_moves,
+                    backward_moves,
+                ) {
+                    return true;
+                }
+            }
+            return false;
+        }
+        individual_search_data.wait_while_paused();
+        if individual_search_data.is_stopped() || individual_search_data.check_time_limit() {
+            return true;
+        }
+        for (_, move_transformation_info) in self.api_data.search_generators.flat.iter() {
+            let Some(next_state) = self
+                .api_data
+                .canonical_fsm
+                .next_state(current_state, move_transformation_info.move_class_index)
+            else {
+                continue;
+            };
+            let next_pattern =
+                current_pattern.apply_transformation(&move_transformation_info.transformation);
+            forward_search_data
+                .forward_moves
+                .push(move_transformation_info);
+            let done = self.search_forward(
+                individual_search_data,
+                forward_search_data,
+                &next_pattern,
+                next_state,
+                remaining_depth - Depth(1),
+            );
+            forward_search_data.forward_moves.pop();
+            if done {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Like `search_forward`, but searches the subtree after each first move on a pool of threads.
+    fn search_forward_in_parallel<'a>(
+        &'a self,
+        individual_search_data: &IndividualSearchData,
+        forward_search_data: &mut ForwardSearchData<'a>,
+        search_pattern: &KPattern,
+        initial_state: CanonicalFSMState,
+        remaining_depth: Depth,
+    ) -> bool {
+        forward_search_data.statistics.num_recursive_calls += 1;
+        let first_moves: Vec<_> = self
+            .api_data
+            .search_generators
+            .flat
+            .iter()
+            .filter_map(|(_, move_transformation_info)| {
+                self.api_data
+                    .canonical_fsm
+                    .next_state(initial_state, move_transformation_info.move_class_index)
+                    .map(|next_state| (move_transformation_info, next_state))
+            })
+            .collect();
+        let next_task_index = AtomicUsize::new(0);
+        let frontier = forward_search_data.frontier;
+        let depth = forward_search_data.statistics.depth;
+        std::thread::scope(|scope| {
+            let thread_handles: Vec<_> = (0..self.api_data.num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut thread_data = ForwardSearchData {
+                            frontier,
+                            forward_moves: vec![],
+                            statistics: SearchDepthStatistics::new(depth),
+                        };
+                        loop {
+                            let task_index = next_task_index.fetch_add(1, Ordering::Relaxed);
+                            let Some((move_transformation_info, next_state)) =
+                                first_moves.get(task_index)
+                            else {
+                                break;
+                            };
+                            let next_pattern = search_pattern
+                                .apply_transformation(&move_transformation_info.transformation);
+                            thread_data.forward_moves.push(move_transformation_info);
+                            let done = self.search_forward(
+                                individual_search_data,
+                                &mut thread_data,
+                                &next_pattern,
+                                *next_state,
+                                remaining_depth - Depth(1),
+                            );
+                            thread_data.forward_moves.pop();
+                            if done {
+                                break;
+                            }
+                        }
+                        thread_data.statistics
+                    })
+                })
+                .collect();
+            for thread_handle in thread_handles {
+                let thread_statistics = thread_handle
+                    .join()
+                    .expect("Internal error: search thread panicked");
+                forward_search_data
+                    .statistics
+                    .add_counts(&thread_statistics);
+            }
+        });
+        individual_search_data.is_stopped()
+    }
+
+    // Sends the solution made of both halves, if it is canonical as a whole.
+    //
+    // Returns whether the search should stop.
+    fn join(
+        &self,
+        individual_search_data: &IndividualSearchData,
+        forward_state: CanonicalFSMState,
+        forward_moves: &[&MoveTransformationInfo<KPuzzle>],
+        backward_moves: &[FlatMoveIndex],
+    ) -> bool {
+        let mut current_state = forward_state;
+        for flat_move_index in backward_moves {
+            let move_class_index = self
+                .api_data
+                .search_generators
+                .flat
+                .at(*flat_move_index)
+                .move_class_index;
+            let Some(next_state) = self
+                .api_data
+                .canonical_fsm
+                .next_state(current_state, move_class_index)
+            else {
+                return false;
+            };
+            current_state = next_state;
+        }
+        if self
+            .api_data
+            .apply_optional_fsm_moves(
+                current_state,
+                &individual_search_data
+                    .individual_search_options
+                    .canonical_fsm_post_moves,
+            )
+            .is_none()
+        {
+            return false;
+        }
+
+        let nodes = forward_moves
+            .iter()
+            .map(|move_transformation_info| move_transformation_info.alg_node.clone())
+            .chain(backward_moves.iter().map(|flat_move_index| {
+                self.api_data
+                    .search_generators
+                    .flat
+                    .at(*flat_move_index)
+                    .alg_node
+                    .clone()
+            }))
+            .collect();
+        individual_search_data.send_solution(Alg { nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::{alg::parse_alg, kpuzzle::KPuzzle, puzzles::cube3x3x3_kpuzzle};
+
+    use crate::_internal::search::idf_search::idf_search::{
+        IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions, SearchEndReason,
+    };
+
+    use super::BidirectionalSearch;
+
+    #[test]
+    fn bidirectional_search_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let generator_moves: Vec<_> = ["U", "L", "F", "R", "B", "D"]
+            .into_iter()
+            .map(|r#move| r#move.parse().unwrap())
+            .collect();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U' F2 D"))
+            .unwrap();
+        let individual_search_options = IndividualSearchOptions {
+            all_optimal: Some(true),
+            ..Default::default()
+        };
+
+        let mut bidirectional_search = BidirectionalSearch::try_new(
+            kpuzzle.clone(),
+            generator_moves.clone(),
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions::default(),
+        )
+        .unwrap();
+        let mut solutions: Vec<String> = bidirectional_search
+            .search(&search_pattern, individual_search_options.clone())
+            .map(|alg| alg.to_string())
+            .collect();
+        solutions.sort();
+
+        let mut idf_search = <IDFSearch<KPuzzle>>::try_new(
+            kpuzzle.clone(),
+            generator_moves,
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions::default(),
+        )
+        .unwrap();
+        let mut expected_solutions: Vec<String> = idf_search
+            .search(&search_pattern, individual_search_options)
+            .map(|alg| alg.to_string())
+            .collect();
+        expected_solutions.sort();
+
+        assert_eq!(solutions, expected_solutions);
+        assert!(solutions.contains(&"D' F2 U R'".to_owned()));
+    }
+
+    #[test]
+    fn bidirectional_search_multithreaded_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U' F2 D L B"))
+            .unwrap();
+        let solutions_with_num_threads = |num_threads: usize| -> Vec<String> {
+            let mut solutions: Vec<String> = BidirectionalSearch::try_new(
+                kpuzzle.clone(),
+                ["U", "L", "F", "R", "B", "D"]
+                    .into_iter()
+                    .map(|r#move| r#move.parse().unwrap())
+                    .collect(),
+                kpuzzle.default_pattern(),
+                IDFSearchConstructionOptions {
+                    num_threads: Some(num_threads),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .search(
+                &search_pattern,
+                IndividualSearchOptions {
+                    all_optimal: Some(true),
+                    ..Default::default()
+                },
+            )
+            .map(|alg| alg.to_string())
+            .collect();
+            solutions.sort();
+            solutions
+        };
+        let solutions = solutions_with_num_threads(4);
+        assert!(solutions.contains(&"B' L' D' F2 U R'".to_owned()));
+        assert_eq!(solutions, solutions_with_num_threads(1));
+    }
+
+    #[test]
+    fn bidirectional_search_memory_limit_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let mut bidirectional_search = BidirectionalSearch::try_new(
+            kpuzzle.clone(),
+            ["U", "L", "F", "R", "B", "D"]
+                .into_iter()
+                .map(|r#move| r#move.parse().unwrap())
+                .collect(),
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                max_prune_table_memory_bytes: Some(1 << 16),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U' F2 D L B"))
+            .unwrap();
+        let mut solutions = bidirectional_search.search(&search_pattern, Default::default());
+        assert_eq!(solutions.next(), None);
+        assert_eq!(
+            solutions.end_reason(),
+            Some(SearchEndReason::ExceededMemoryLimit)
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use std::{
-    fmt::Debug,
+    collections::HashMap,
+    fmt::{Debug, Display},
     sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         mpsc::{channel, Receiver, Sender},
-        Arc,
+        Arc, Condvar, Mutex, MutexGuard,
     },
+    thread::available_parallelism,
+    time::Duration,
 };
 
 use cubing::{
@@ -18,7 +22,7 @@ use crate::_internal::{
             CanonicalFSM, CanonicalFSMConstructionOptions, CanonicalFSMState,
             CANONICAL_FSM_START_STATE,
         },
-        search_generators::SearchGenerators,
+        search_generators::{MoveTransformationInfo, SearchGenerators},
     },
     cli::args::MetricEnum,
     errors::SearchError,
@@ -29,18 +33,29 @@ use crate::_internal::{
 use super::{
     super::{
         pattern_validity_checker::PatternValidityChecker,
-        prune_table_trait::{Depth, PruneTable},
+        prune_table_persistence::PruneTablePersistenceOptions,
+        prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions},
         recursion_filter_trait::RecursionFilter,
         recursive_work_tracker::RecursiveWorkTracker,
-        search_logger::SearchLogger,
+        search_logger::{SearchLogEvent, SearchLogger},
+        search_statistics::{SearchDepthStatistics, SearchStatistics},
     },
     search_adaptations::{DefaultSearchAdaptations, SearchAdaptations},
 };
 
-// TODO: right now we return 0 solutions if we blow past this, should we return an explicit error,
-// or panic instead?
+// Searches that get this far end with `SearchEndReason::ExceededMaxSupportedDepth`.
 const MAX_SUPPORTED_SEARCH_DEPTH: Depth = Depth(500); // TODO: increase
 
+// Searching shallow depths is faster than spinning up threads.
+const MIN_PARALLEL_SEARCH_DEPTH: Depth = Depth(5);
+// We split the search tree into at least this many subtrees per thread, so
+// that threads with quick subtrees can pick up more work.
+const MIN_PARALLEL_SEARCH_TASKS_PER_THREAD: usize = 4;
+const MAX_PARALLEL_SEARCH_SPLIT_DEPTH: Depth = Depth(3);
+
+// Checking the time is relatively expensive, so we only do it every so often.
+const NUM_RECURSIVE_CALLS_BETWEEN_TIME_LIMIT_CHECKS: usize = 1 << 12;
+
 // TODO: use https://doc.rust-lang.org/std/ops/enum.ControlFlow.html as a wrapper instead?
 #[allow(clippy::enum_variant_names)]
 enum SearchRecursionResult {
@@ -50,7 +65,7 @@ enum SearchRecursionResult {
 }
 
 struct SolutionPreviousMoves<'a> {
-    latest_move: &'a Move,
+    latest_alg_node: &'a AlgNode,
     previous_moves: &'a SolutionMoves<'a>,
 }
 
@@ -69,9 +84,7 @@ impl SolutionMoves<'_> {
         match self.0 {
             Some(solution_previous_moves) => {
                 let mut nodes = solution_previous_moves.previous_moves.get_alg_nodes();
-                nodes.push(cubing::alg::AlgNode::MoveNode(
-                    solution_previous_moves.latest_move.clone(),
-                ));
+                nodes.push(solution_previous_moves.latest_alg_node.clone());
                 nodes
             }
             None => vec![],
@@ -79,23 +92,276 @@ impl SolutionMoves<'_> {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchEndReason {
+    /// `min_num_solutions` solutions were found.
+    ReachedMinNumSolutions,
+    /// All solutions at the optimal depth were found (for `all_optimal`).
+    FoundAllOptimalSolutions,
+    /// All depths up to `max_depth` were searched.
+    ExhaustedMaxDepth,
+    /// All depths up to the maximum depth that the search supports were
+    /// searched, without reaching `max_depth` (or without a `max_depth`). This
+    /// usually means that the search pattern cannot reach the target patterns.
+    ExceededMaxSupportedDepth,
+    /// The `time_limit` was reached.
+    TimedOut,
+    /// The search was cancelled using a [`SearchCancellationHandle`], or the [`SearchSolutions`] were dropped.
+    Cancelled,
+    /// The search ended without reporting why (e.g. a search thread panicked).
+    InternalError,
+    /// The search needed more memory than allowed by
+    /// [`IDFSearchConstructionOptions::max_prune_table_memory_bytes`] (e.g. for
+    /// the frontier of a
+    /// [`BidirectionalSearch`](super::bidirectional_search::BidirectionalSearch)).
+    ExceededMemoryLimit,
+}
+
+impl Display for SearchEndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SearchEndReason::ReachedMinNumSolutions => "reached the requested number of solutions",
+            SearchEndReason::FoundAllOptimalSolutions => "found all optimal solutions",
+            SearchEndReason::ExhaustedMaxDepth => "searched up to the max depth",
+            SearchEndReason::ExceededMaxSupportedDepth => {
+                "searched up to the maximum supported search depth"
+            }
+            SearchEndReason::TimedOut => "timed out",
+            SearchEndReason::Cancelled => "cancelled",
+            SearchEndReason::InternalError => "internal error",
+            SearchEndReason::ExceededMemoryLimit => "exceeded the memory limit",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+enum SearchSolutionsMessage {
+    Solution(Alg),
+    End(SearchEndReason, SearchStatistics),
+}
+
+// Pauses count neither towards the time limit nor towards the search statistics.
+#[derive(Default)]
+struct SearchTiming {
+    deadline: Option<instant::Instant>,
+    pause_start_time: Option<instant::Instant>,
+    total_pause_duration: Duration,
+}
+
+impl SearchTiming {
+    fn resume(&mut self) {
+        if let Some(pause_start_time) = self.pause_start_time.take() {
+            let pause_duration = instant::Instant::now() - pause_start_time;
+            if let Some(deadline) = self.deadline.as_mut() {
+                *deadline += pause_duration;
+            }
+            self.total_pause_duration += pause_duration;
+        }
+    }
+}
+
+// Shared between the search, its `SearchSolutions`, and any `SearchCancellationHandle`s.
+#[derive(Default)]
+struct SearchStopState {
+    stopped: AtomicBool,
+    // Set while the search waits for the next solution to be requested. All
+    // search threads wait while this is set. Only changed while holding the
+    // `num_solutions_requested` lock.
+    paused: AtomicBool,
+    // Set once the search has released its prune table (see
+    // `IDFSearch::take_over_from_previous_search`). Only changed while holding the
+    // `num_solutions_requested` lock.
+    finished: AtomicBool,
+    end_reason: Mutex<Option<SearchEndReason>>,
+    // The number of times that `SearchSolutions::next()` has been called.
+    num_solutions_requested: Mutex<usize>,
+    // Notified whenever `stopped`, `paused`, `finished`, or `num_solutions_requested` change.
+    changed: Condvar,
+    // Only locked while holding the `num_solutions_requested` lock (if at all),
+    // so that pauses start and end at the same time for the time limit.
+    timing: Mutex<SearchTiming>,
+}
+
+impl SearchStopState {
+    // Only the first reason is recorded.
+    fn stop(&self, end_reason: SearchEndReason) {
+        let mut current_end_reason = self
+            .end_reason
+            .lock()
+            .expect("Internal error: could not access search end reason");
+        if current_end_reason.is_none() {
+            *current_end_reason = Some(end_reason);
+        }
+        drop(current_end_reason);
+        // Wake up a search that is paused, so that it can finish. We hold the
+        // lock while notifying, so that the search cannot miss the notification
+        // between checking `stopped` and waiting.
+        let _num_solutions_requested = self.lock_num_solutions_requested();
+        self.stopped.store(true, Ordering::Relaxed);
+        self.unpause();
+        self.changed.notify_all();
+    }
+
+    fn lock_num_solutions_requested(&self) -> MutexGuard<'_, usize> {
+        self.num_solutions_requested
+            .lock()
+            .expect("Internal error: could not access number of requested solutions")
+    }
+
+    fn lock_timing(&self) -> MutexGuard<'_, SearchTiming> {
+        self.timing
+            .lock()
+            .expect("Internal error: could not access search timing")
+    }
+
+    fn request_solution(&self) {
+        let mut num_solutions_requested = self.lock_num_solutions_requested();
+        *num_solutions_requested += 1;
+        self.unpause();
+        self.changed.notify_all();
+    }
+
+    // Pauses the search unless more than `num_solutions_sofar` solutions have
+    // already been requested (or the search is stopped).
+    #[cfg(not(target_arch = "wasm32"))]
+    fn pause(&self, num_solutions_sofar: usize) {
+        let num_solutions_requested = self.lock_num_solutions_requested();
+        if *num_solutions_requested > num_solutions_sofar || self.is_stopped() {
+            return;
+        }
+        self.lock_timing().pause_start_time = Some(instant::Instant::now());
+        self.paused.store(true, Ordering::Relaxed);
+        self.changed.notify_all();
+    }
+
+    // Must be called while holding the `num_solutions_requested` lock.
+    fn unpause(&self) {
+        if self.paused.swap(false, Ordering::Relaxed) {
+            self.lock_timing().resume();
+        }
+    }
+
+    // Blocks while the search is paused.
+    fn wait_while_paused(&self) {
+        if !self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut num_solutions_requested = self.lock_num_solutions_requested();
+        while self.paused.load(Ordering::Relaxed) {
+            num_solutions_requested = self
+                .changed
+                .wait(num_solutions_requested)
+                .expect("Internal error: could not access number of requested solutions");
+        }
+    }
+
+    fn finish(&self) {
+        let _num_solutions_requested = self.lock_num_solutions_requested();
+        self.finished.store(true, Ordering::Relaxed);
+        self.changed.notify_all();
+    }
+
+    // Blocks until the search is paused or finished. Returns whether it is paused (and not finished).
+    fn wait_until_paused_or_finished(&self) -> bool {
+        let mut num_solutions_requested = self.lock_num_solutions_requested();
+        loop {
+            if self.finished.load(Ordering::Relaxed) {
+                return false;
+            }
+            if self.paused.load(Ordering::Relaxed) {
+                return true;
+            }
+            num_solutions_requested = self
+                .changed
+                .wait(num_solutions_requested)
+                .expect("Internal error: could not access number of requested solutions");
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn end_reason(&self) -> Option<SearchEndReason> {
+        *self
+            .end_reason
+            .lock()
+            .expect("Internal error: could not access search end reason")
+    }
+}
+
+/// Stops a search in progress. This can be used from any thread.
+#[derive(Clone)]
+pub struct SearchCancellationHandle {
+    stop_state: Arc<SearchStopState>,
+}
+
+impl SearchCancellationHandle {
+    pub fn cancel(&self) {
+        self.stop_state.stop(SearchEndReason::Cancelled);
+    }
+}
+
+/// Iterates over the solutions of a search as they are found.
+///
+/// After sending each solution, the search (including all of its threads)
+/// pauses until the next solution is requested by calling `next()`, so that it
+/// does not do any work that the caller does not need. (Searches in WASM run to
+/// completion before they return their `SearchSolutions`, so they never pause.)
+///
+/// A paused search still holds on to the prune table of its searcher. A new
+/// search on the same searcher waits for the previous search to finish, or
+/// cancels it (with [`SearchEndReason::Cancelled`]) if it is paused.
+///
+/// Dropping this cancels the search.
 pub struct SearchSolutions {
-    receiver: Receiver<Option<Alg>>,
+    receiver: Receiver<SearchSolutionsMessage>,
     done: bool,
+    end_reason: Option<SearchEndReason>,
+    statistics: Option<SearchStatistics>,
+    stop_state: Arc<SearchStopState>,
 }
 
 impl SearchSolutions {
-    pub fn construct() -> (Sender<Option<Alg>>, Self) {
-        // TODO: use `sync_channel` to control resumption?
-        let (sender, receiver) = channel::<Option<Alg>>();
+    fn construct() -> (Sender<SearchSolutionsMessage>, Arc<SearchStopState>, Self) {
+        // The search only sends a solution once it has been requested (see
+        // `SearchStopState::pause`), so this buffers at most one solution.
+        let (sender, receiver) = channel::<SearchSolutionsMessage>();
+        let stop_state = Arc::new(SearchStopState::default());
         (
             sender,
+            stop_state.clone(),
             Self {
                 receiver,
                 done: false,
+                end_reason: None,
+                statistics: None,
+                stop_state,
             },
         )
     }
+
+    pub fn cancellation_handle(&self) -> SearchCancellationHandle {
+        SearchCancellationHandle {
+            stop_state: self.stop_state.clone(),
+        }
+    }
+
+    /// Why the search ended. Returns `None` until the iterator has returned all solutions.
+    pub fn end_reason(&self) -> Option<SearchEndReason> {
+        self.end_reason
+    }
+
+    /// Statistics about the work done by the search. Returns `None` until the iterator has returned all solutions.
+    pub fn statistics(&self) -> Option<&SearchStatistics> {
+        self.statistics.as_ref()
+    }
+}
+
+impl Drop for SearchSolutions {
+    fn drop(&mut self) {
+        self.stop_state.stop(SearchEndReason::Cancelled);
+    }
 }
 
 impl Iterator for SearchSolutions {
@@ -105,18 +371,22 @@ impl Iterator for SearchSolutions {
         if self.done {
             None
         } else {
+            self.stop_state.request_solution();
             let received = match self.receiver.recv() {
                 Ok(received) => received,
                 Err(_) => {
-                    // TODO: this could be either a channel failure or no solutions found. We should find a way for the latter to avoid hitting this code path.
+                    // The search ended without reporting why (e.g. it panicked).
                     self.done = true;
+                    self.end_reason = Some(SearchEndReason::InternalError);
                     return None;
                 }
             };
             match received {
-                Some(alg) => Some(alg),
-                None => {
+                SearchSolutionsMessage::Solution(alg) => Some(alg),
+                SearchSolutionsMessage::End(end_reason, statistics) => {
                     self.done = true;
+                    self.end_reason = Some(end_reason);
+                    self.statistics = Some(statistics);
                     None
                 }
             }
@@ -132,6 +402,13 @@ pub struct IndividualSearchOptions {
     pub max_depth: Option<Depth>, // exclusive
     pub canonical_fsm_pre_moves: Option<Vec<Move>>,
     pub canonical_fsm_post_moves: Option<Vec<Move>>,
+    /// Return every solution at the optimal depth (then stop). When this is
+    /// set, `min_num_solutions` is ignored.
+    pub all_optimal: Option<bool>,
+    /// Stop searching after this amount of time (not including the time until
+    /// the search starts, or the time that the search is paused waiting for
+    /// the next solution to be requested).
+    pub time_limit: Option<Duration>,
 }
 
 impl IndividualSearchOptions {
@@ -144,21 +421,284 @@ impl IndividualSearchOptions {
     pub fn get_max_depth(&self) -> Depth {
         self.max_depth.unwrap_or(MAX_SUPPORTED_SEARCH_DEPTH)
     }
+    pub fn get_all_optimal(&self) -> bool {
+        self.all_optimal.unwrap_or(false)
+    }
 }
 
-struct IndividualSearchData {
-    individual_search_options: IndividualSearchOptions,
-    recursive_work_tracker: RecursiveWorkTracker,
+struct SolutionSendingState {
     num_solutions_sofar: usize,
-    solution_sender: Sender<Option<Alg>>,
+    solution_sender: Sender<SearchSolutionsMessage>,
+}
+
+// Shared between all threads of an individual search.
+pub(super) struct IndividualSearchData {
+    pub(super) individual_search_options: IndividualSearchOptions,
+    solution_sending_state: Mutex<SolutionSendingState>,
+    stop_state: Arc<SearchStopState>,
+    search_logger: Arc<SearchLogger>,
+    // Whether the search stops at `MAX_SUPPORTED_SEARCH_DEPTH` rather than at a requested `max_depth`.
+    limited_by_max_supported_depth: bool,
+}
+
+impl IndividualSearchData {
+    // Validates the options and connects the data for a new search to the returned `SearchSolutions`.
+    pub(super) fn start(
+        mut individual_search_options: IndividualSearchOptions,
+        search_logger: Arc<SearchLogger>,
+    ) -> (Self, SearchSolutions) {
+        // TODO: do validation more consistently.
+        if let Some(min_depth) = individual_search_options.min_depth {
+            if min_depth > MAX_SUPPORTED_SEARCH_DEPTH {
+                search_logger.write_error("Min depth too large, capping at maximum.");
+                individual_search_options.min_depth = Some(MAX_SUPPORTED_SEARCH_DEPTH);
+            }
+        }
+        let mut limited_by_max_supported_depth = true;
+        if let Some(max_depth) = individual_search_options.max_depth {
+            if max_depth > MAX_SUPPORTED_SEARCH_DEPTH {
+                search_logger.write_error("Max depth too large, capping at maximum.");
+                individual_search_options.max_depth = Some(MAX_SUPPORTED_SEARCH_DEPTH);
+            } else {
+                limited_by_max_supported_depth = false;
+            }
+        }
+
+        let (solution_sender, stop_state, search_solutions) = SearchSolutions::construct();
+        stop_state.lock_timing().deadline = individual_search_options
+            .time_limit
+            .map(|time_limit| instant::Instant::now() + time_limit);
+        (
+            Self {
+                individual_search_options,
+                solution_sending_state: Mutex::new(SolutionSendingState {
+                    num_solutions_sofar: 0,
+                    solution_sender,
+                }),
+                stop_state,
+                search_logger,
+                limited_by_max_supported_depth,
+            },
+            search_solutions,
+        )
+    }
+
+    pub(super) fn is_stopped(&self) -> bool {
+        self.stop_state.is_stopped()
+    }
+
+    pub(super) fn stop(&self, end_reason: SearchEndReason) {
+        self.stop_state.stop(end_reason);
+    }
+
+    // Blocks while the search is paused (waiting for the next solution to be requested).
+    pub(super) fn wait_while_paused(&self) {
+        self.stop_state.wait_while_paused();
+    }
+
+    // The total time that the search has been paused so far.
+    pub(super) fn total_pause_duration(&self) -> Duration {
+        self.stop_state.lock_timing().total_pause_duration
+    }
+
+    pub(super) fn num_solutions_sofar(&self) -> usize {
+        self.solution_sending_state
+            .lock()
+            .expect("Internal error: could not access solution state")
+            .num_solutions_sofar
+    }
+
+    // Returns whether the search should stop.
+    pub(super) fn check_time_limit(&self) -> bool {
+        let timed_out = {
+            let timing = self.stop_state.lock_timing();
+            // The clock is stopped while the search is paused.
+            timing.pause_start_time.is_none()
+                && timing
+                    .deadline
+                    .is_some_and(|deadline| instant::Instant::now() >= deadline)
+        };
+        if timed_out {
+            self.stop_state.stop(SearchEndReason::TimedOut);
+        }
+        timed_out
+    }
+
+    // Returns whether the search should stop.
+    pub(super) fn send_solution(&self, alg: Alg) -> bool {
+        let mut solution_sending_state = self
+            .solution_sending_state
+            .lock()
+            .expect("Internal error: could not access solution state");
+        // Only send a solution once it has been requested. (Another thread may
+        // also have paused or finished the search while we were waiting for the
+        // lock.)
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stop_state
+            .pause(solution_sending_state.num_solutions_sofar);
+        self.wait_while_paused();
+        if self.stop_state.is_stopped() {
+            return true;
+        }
+        solution_sending_state.num_solutions_sofar += 1;
+        self.search_logger.log(SearchLogEvent::SolutionFound {
+            solution: &alg,
+            solution_index: solution_sending_state.num_solutions_sofar,
+        });
+        if solution_sending_state
+            .solution_sender
+            .send(SearchSolutionsMessage::Solution(alg))
+            .is_err()
+        {
+            // The `SearchSolutions` were dropped.
+            self.stop_state.stop(SearchEndReason::Cancelled);
+            return true;
+        }
+        // With `all_optimal`, the search stops after finishing the current depth instead.
+        if !self.individual_search_options.get_all_optimal()
+            && solution_sending_state.num_solutions_sofar
+                >= self.individual_search_options.get_min_num_solutions()
+        {
+            self.stop_state
+                .stop(SearchEndReason::ReachedMinNumSolutions);
+            return true;
+        }
+        // Threads are not available in WASM, so nothing could request the next solution.
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stop_state
+            .pause(solution_sending_state.num_solutions_sofar);
+        drop(solution_sending_state);
+        self.wait_while_paused();
+        self.stop_state.is_stopped()
+    }
+
+    // Returns whether the search should stop after finishing a depth.
+    pub(super) fn finish_depth(&self) -> bool {
+        if self.individual_search_options.get_all_optimal() && self.num_solutions_sofar() > 0 {
+            self.stop_state
+                .stop(SearchEndReason::FoundAllOptimalSolutions);
+            return true;
+        }
+        false
+    }
+
+    pub(super) fn send_end_of_search(&self, statistics: SearchStatistics) {
+        let end_reason =
+            self.stop_state
+                .end_reason()
+                .unwrap_or(match self.limited_by_max_supported_depth {
+                    true => SearchEndReason::ExceededMaxSupportedDepth,
+                    false => SearchEndReason::ExhaustedMaxDepth,
+                });
+        // If the `SearchSolutions` have been dropped, there is no one to tell.
+        let _ = self
+            .solution_sending_state
+            .lock()
+            .expect("Internal error: could not access solution state")
+            .solution_sender
+            .send(SearchSolutionsMessage::End(end_reason, statistics));
+    }
+}
+
+impl Drop for IndividualSearchData {
+    fn drop(&mut self) {
+        self.stop_state.finish();
+    }
+}
+
+// Owned by a single search thread (for a single depth).
+struct SearchThreadData<'a, TPuzzle: SemiGroupActionPuzzle, TPruneTable> {
+    pattern_stack: PatternStack<TPuzzle>,
+    statistics: SearchDepthStatistics,
+    prune_table: &'a TPruneTable,
+}
+
+// The root of a subtree that can be searched independently by a single thread.
+struct ParallelSearchTask<'a, TPuzzle: SemiGroupActionPuzzle> {
+    prefix: Vec<&'a MoveTransformationInfo<TPuzzle>>,
 }
 
 pub struct IDFSearchAPIData<TPuzzle: SemiGroupActionPuzzle> {
     pub search_generators: SearchGenerators<TPuzzle>,
     pub canonical_fsm: CanonicalFSM<TPuzzle>, // TODO: move this into `SearchAdaptations`
     pub tpuzzle: TPuzzle,
-    pub target_pattern: TPuzzle::Pattern,
+    /// A search pattern is solved when it reaches any of these.
+    pub target_patterns: Vec<TPuzzle::Pattern>,
     pub search_logger: Arc<SearchLogger>,
+    pub metric: MetricEnum,
+    pub num_threads: usize,
+    /// Whether every move multiple has a cost of 1 (i.e. no custom move costs are in effect).
+    pub unit_move_costs: bool,
+}
+
+impl<TPuzzle: SemiGroupActionPuzzle> IDFSearchAPIData<TPuzzle> {
+    /// Builds the generators and canonical FSM for a search. Prune table options are ignored.
+    pub fn try_new(
+        tpuzzle: TPuzzle,
+        generator_moves: Vec<Move>, // TODO: turn this back into `Generators`
+        target_patterns: Vec<TPuzzle::Pattern>,
+        options: &IDFSearchConstructionOptions,
+    ) -> Result<Self, SearchError> {
+        if target_patterns.is_empty() {
+            return Err("At least one target pattern must be specified.".into());
+        }
+        let mut search_generators = SearchGenerators::try_new_with_alg_generators(
+            &tpuzzle,
+            generator_moves,
+            options.generator_algs.clone(),
+            &options.metric,
+            options.random_start,
+        )?;
+        search_generators.set_move_costs(&tpuzzle, &options.move_costs)?;
+        let unit_move_costs = search_generators
+            .flat
+            .iter()
+            .all(|(_, move_transformation_info)| move_transformation_info.cost == Depth(1));
+        let canonical_fsm = CanonicalFSM::try_new(
+            tpuzzle.clone(),
+            search_generators.clone(),
+            options.canonical_fsm_construction_options.clone(),
+        )?; // TODO: avoid a clone
+        let num_threads = options.num_threads.unwrap_or(1);
+        if num_threads == 0 {
+            return Err(SearchError {
+                description: "The number of threads must be at least 1.".to_owned(),
+            });
+        }
+        Ok(Self {
+            search_generators,
+            canonical_fsm,
+            tpuzzle,
+            target_patterns,
+            search_logger: options.search_logger.clone(),
+            metric: options.metric.clone(),
+            num_threads,
+            unit_move_costs,
+        })
+    }
+
+    // Returns `None` if the moves cannot be applied, else returns the result of applying the moves.
+    pub(super) fn apply_optional_fsm_moves(
+        &self,
+        start_state: CanonicalFSMState,
+        moves: &Option<Vec<Move>>,
+    ) -> Option<CanonicalFSMState> {
+        let mut current_state = start_state;
+        if let Some(moves) = moves {
+            for r#move in moves {
+                let move_class_index = self
+                    .search_generators
+                    .by_move
+                    .get(r#move)
+                    .expect("move!")
+                    .move_class_index;
+                current_state = self
+                    .canonical_fsm
+                    .next_state(current_state, move_class_index)?;
+            }
+        }
+        Some(current_state)
+    }
 }
 
 /// For information on [`SearchAdaptations`], see the documentation for that trait.
@@ -169,7 +709,10 @@ pub struct IDFSearch<
     >>::Adaptations,
 > {
     pub api_data: Arc<IDFSearchAPIData<TPuzzle>>,
-    pub prune_table: Adaptations::PruneTable, // TODO: push this into the associated data for the adaptations.
+    // Shared with the thread of the current search (if any).
+    pub prune_table: Arc<Mutex<Adaptations::PruneTable>>, // TODO: push this into the associated data for the adaptations.
+    // The latest search that has started using the prune table.
+    current_search: Arc<Mutex<Option<Arc<SearchStopState>>>>,
 }
 
 pub struct IDFSearchConstructionOptions {
@@ -177,6 +720,18 @@ pub struct IDFSearchConstructionOptions {
     pub metric: MetricEnum,
     pub random_start: bool,
     pub min_prune_table_size: Option<usize>,
+    /// Caps the prune table size. Unlimited by default.
+    pub max_prune_table_memory_bytes: Option<usize>,
+    /// Read and write prune tables from disk. Disabled by default.
+    pub prune_table_persistence: Option<PruneTablePersistenceOptions>,
+    /// Defaults to 1. Use [`default_num_threads`] to use all available cores.
+    pub num_threads: Option<usize>,
+    /// Algs that the search treats as single moves (in addition to the generator moves).
+    pub generator_algs: Vec<Alg>,
+    /// Costs for generator moves (see [`SearchGenerators::set_move_costs`]).
+    /// When set, search depths (and prune table depths) are measured in total cost.
+    /// Costs that the search cannot honor (e.g. `R2` costing more than `R R`) are rejected.
+    pub move_costs: HashMap<Move, Depth>,
     pub canonical_fsm_construction_options: CanonicalFSMConstructionOptions,
 }
 
@@ -187,11 +742,21 @@ impl Default for IDFSearchConstructionOptions {
             metric: MetricEnum::Hand,
             random_start: Default::default(),
             min_prune_table_size: Default::default(),
+            max_prune_table_memory_bytes: Default::default(),
+            prune_table_persistence: Default::default(),
+            num_threads: Default::default(),
+            generator_algs: Default::default(),
+            move_costs: Default::default(),
             canonical_fsm_construction_options: Default::default(),
         }
     }
 }
 
+/// The number of logical CPU cores available (or 1 if this cannot be determined, e.g. in WASM).
+pub fn default_num_threads() -> usize {
+    available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
 impl<
         TPuzzle: SemiGroupActionPuzzle + DefaultSearchAdaptations<TPuzzle>,
         Optimizations: SearchAdaptations<TPuzzle>,
@@ -203,75 +768,109 @@ impl<
         target_pattern: TPuzzle::Pattern,
         options: IDFSearchConstructionOptions,
     ) -> Result<Self, SearchError> {
-        let search_generators = SearchGenerators::try_new(
-            &tpuzzle,
+        Self::try_new_with_target_patterns(tpuzzle, generator_moves, vec![target_pattern], options)
+    }
+
+    /// Searches for solutions that reach any of the `target_patterns` (e.g.
+    /// all rotations of the solved pattern). The prune table is seeded from
+    /// all of them.
+    pub fn try_new_with_target_patterns(
+        tpuzzle: TPuzzle,
+        generator_moves: Vec<Move>, // TODO: turn this back into `Generators`
+        target_patterns: Vec<TPuzzle::Pattern>,
+        options: IDFSearchConstructionOptions,
+    ) -> Result<Self, SearchError> {
+        Self::try_new_with_prune_table_options(
+            tpuzzle,
             generator_moves,
-            &options.metric,
-            options.random_start,
-        )?;
-        let canonical_fsm = CanonicalFSM::try_new(
+            target_patterns,
+            options,
+            Default::default(),
+        )
+    }
+
+    /// Like [`IDFSearch::try_new_with_target_patterns`], with options that are
+    /// specific to the type of prune table (e.g. the symmetries of a
+    /// [`HashPruneTable`](super::super::hash_prune_table::HashPruneTable), or
+    /// the masks of a
+    /// [`MaskedMaxPruneTable`](super::super::masked_max_prune_table::MaskedMaxPruneTable)).
+    pub fn try_new_with_prune_table_options(
+        tpuzzle: TPuzzle,
+        generator_moves: Vec<Move>, // TODO: turn this back into `Generators`
+        target_patterns: Vec<TPuzzle::Pattern>,
+        options: IDFSearchConstructionOptions,
+        prune_table_options: <Optimizations::PruneTable as PruneTable<TPuzzle>>::TableOptions,
+    ) -> Result<Self, SearchError> {
+        let api_data = Arc::new(IDFSearchAPIData::try_new(
             tpuzzle.clone(),
-            search_generators.clone(),
-            options.canonical_fsm_construction_options,
-        )?; // TODO: avoid a clone
-        let api_data = Arc::new(IDFSearchAPIData {
-            search_generators,
-            canonical_fsm,
-            tpuzzle: tpuzzle.clone(),
-            target_pattern,
-            search_logger: options.search_logger.clone(),
-        });
+            generator_moves,
+            target_patterns,
+            &options,
+        )?);
 
         let prune_table = Optimizations::PruneTable::new(
             tpuzzle,
             api_data.clone(),
             options.search_logger,
-            options.min_prune_table_size,
-        ); // TODO: make the prune table reusable across searches.
+            PruneTableConstructionOptions {
+                min_size: options.min_prune_table_size,
+                max_memory_bytes: options.max_prune_table_memory_bytes,
+                persistence: options.prune_table_persistence,
+                table_options: prune_table_options,
+            },
+        )?; // TODO: make the prune table reusable across searches.
         Ok(Self {
             api_data,
-            prune_table,
+            prune_table: Arc::new(Mutex::new(prune_table)),
+            current_search: Default::default(),
         })
     }
 
     pub fn search(
         &mut self,
         search_pattern: &TPuzzle::Pattern,
-        mut individual_search_options: IndividualSearchOptions,
+        individual_search_options: IndividualSearchOptions,
     ) -> SearchSolutions {
-        // TODO: do validation more consistently.
-        if let Some(min_depth) = individual_search_options.min_depth {
-            if min_depth > MAX_SUPPORTED_SEARCH_DEPTH {
-                self.api_data
-                    .search_logger
-                    .write_error("Min depth too large, capping at maximum.");
-                individual_search_options.min_depth = Some(MAX_SUPPORTED_SEARCH_DEPTH);
-            }
-        }
-        if let Some(max_depth) = individual_search_options.max_depth {
-            if max_depth > MAX_SUPPORTED_SEARCH_DEPTH {
-                self.api_data
-                    .search_logger
-                    .write_error("Max depth too large, capping at maximum.");
-                individual_search_options.max_depth = Some(MAX_SUPPORTED_SEARCH_DEPTH);
-            }
+        let (individual_search_data, search_solutions) = IndividualSearchData::start(
+            individual_search_options,
+            self.api_data.search_logger.clone(),
+        );
+        let search_pattern = search_pattern.clone();
+
+        // Threads are not available in WASM.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let idf_search = Self {
+                api_data: self.api_data.clone(),
+                prune_table: self.prune_table.clone(),
+                current_search: self.current_search.clone(),
+            };
+            std::thread::spawn(move || {
+                idf_search.search_synchronously(search_pattern, individual_search_data)
+            });
         }
+        #[cfg(target_arch = "wasm32")]
+        self.search_synchronously(search_pattern, individual_search_data);
 
-        let (solution_sender, search_solutions) = SearchSolutions::construct();
-        let mut individual_search_data = IndividualSearchData {
-            individual_search_options,
-            recursive_work_tracker: RecursiveWorkTracker::new(
-                "Search".to_owned(),
-                self.api_data.search_logger.clone(),
-            ),
-            num_solutions_sofar: 0,
-            solution_sender,
-        };
+        search_solutions
+    }
 
-        let search_pattern = search_pattern.clone();
+    fn search_synchronously(
+        &self,
+        search_pattern: TPuzzle::Pattern,
+        individual_search_data: IndividualSearchData,
+    ) {
+        self.take_over_from_previous_search(&individual_search_data);
+        // The previous search (if any) has released the prune table, since it
+        // only marks itself as finished after that (when its `IndividualSearchData` is dropped).
+        let mut prune_table = self
+            .prune_table
+            .lock()
+            .expect("Internal error: could not access prune table");
+        let mut recursive_work_tracker =
+            RecursiveWorkTracker::new("Search".to_owned(), self.api_data.search_logger.clone());
+        let mut statistics = SearchStatistics::default();
 
-        // TODO: combine `KPatternStack` with `SolutionMoves`?
-        let mut pattern_stack = PatternStack::new(self.api_data.tpuzzle.clone(), search_pattern);
         for remaining_depth in *individual_search_data
             .individual_search_options
             .get_min_depth()
@@ -280,17 +879,26 @@ impl<
                 .get_max_depth()
         {
             let remaining_depth = Depth(remaining_depth);
+            if individual_search_data.stop_state.is_stopped()
+                || individual_search_data.check_time_limit()
+            {
+                break;
+            }
             self.api_data.search_logger.write_info("----------------");
-            self.prune_table.extend_for_search_depth(
+            prune_table.extend_for_search_depth(
                 remaining_depth,
-                individual_search_data
-                    .recursive_work_tracker
-                    .estimate_next_level_num_recursive_calls(),
+                recursive_work_tracker.estimate_next_level_num_recursive_calls(),
+                &|| {
+                    individual_search_data.is_stopped() || individual_search_data.check_time_limit()
+                },
             );
-            individual_search_data
-                .recursive_work_tracker
-                .start_depth(remaining_depth, Some("Starting search…"));
+            if individual_search_data.is_stopped() {
+                break;
+            }
+            recursive_work_tracker.start_depth(remaining_depth, Some("Starting search…"));
+            let depth_start_pause_duration = individual_search_data.total_pause_duration();
             let initial_state = self
+                .api_data
                 .apply_optional_fsm_moves(
                     CANONICAL_FSM_START_STATE,
                     &individual_search_data
@@ -298,40 +906,286 @@ impl<
                         .canonical_fsm_pre_moves,
                 )
                 .expect("TODO: invalid canonical FSM pre-moves.");
-            let recursion_result = self.recurse(
-                &mut individual_search_data,
-                &mut pattern_stack,
-                initial_state,
-                remaining_depth,
-                SolutionMoves(None),
-            );
-            individual_search_data
-                .recursive_work_tracker
-                .finish_latest_depth();
+            // TODO: combine `KPatternStack` with `SolutionMoves`?
+            let mut search_thread_data = SearchThreadData {
+                pattern_stack: PatternStack::new(
+                    self.api_data.tpuzzle.clone(),
+                    search_pattern.clone(),
+                ),
+                statistics: SearchDepthStatistics::new(remaining_depth),
+                prune_table: &*prune_table,
+            };
+            let recursion_result =
+                if self.api_data.num_threads > 1 && remaining_depth >= MIN_PARALLEL_SEARCH_DEPTH {
+                    self.recurse_in_parallel(
+                        &individual_search_data,
+                        &mut search_thread_data,
+                        initial_state,
+                        remaining_depth,
+                    )
+                } else {
+                    self.recurse(
+                        &individual_search_data,
+                        &mut search_thread_data,
+                        initial_state,
+                        remaining_depth,
+                        SolutionMoves(None),
+                    )
+                };
+            recursive_work_tracker
+                .record_recursive_calls(search_thread_data.statistics.num_recursive_calls);
+            recursive_work_tracker.finish_latest_depth();
+            let mut depth_statistics = search_thread_data.statistics;
+            depth_statistics.duration = recursive_work_tracker
+                .latest_depth_duration()
+                .saturating_sub(
+                    individual_search_data.total_pause_duration() - depth_start_pause_duration,
+                );
+            statistics.depths.push(depth_statistics);
             if let SearchRecursionResult::DoneSearching() = recursion_result {
                 break;
             }
+            if individual_search_data.finish_depth() {
+                break;
+            }
         }
-        search_solutions
+        statistics.prune_table = Some(prune_table.statistics());
+        individual_search_data.send_end_of_search(statistics);
+    }
+
+    // Waits for the previous search on this `IDFSearch` (if any) to finish, and
+    // then registers `individual_search_data` as the current search.
+    //
+    // A paused search holds on to the prune table until its next solution is
+    // requested, which could wait for this search (e.g. if both searches are
+    // consumed from the same thread). So if the previous search is or becomes
+    // paused, it is cancelled instead.
+    fn take_over_from_previous_search(&self, individual_search_data: &IndividualSearchData) {
+        loop {
+            let mut current_search = self
+                .current_search
+                .lock()
+                .expect("Internal error: could not access current search");
+            match current_search.as_ref() {
+                Some(previous_search) if !previous_search.finished.load(Ordering::Relaxed) => {
+                    let previous_search = previous_search.clone();
+                    drop(current_search);
+                    if previous_search.wait_until_paused_or_finished() {
+                        previous_search.stop(SearchEndReason::Cancelled);
+                    }
+                }
+                _ => {
+                    *current_search = Some(individual_search_data.stop_state.clone());
+                    return;
+                }
+            }
+        }
+    }
+
+    // Splits the search tree for the current depth into subtrees (by prefix
+    // moves), and searches them using a pool of threads.
+    fn recurse_in_parallel(
+        &self,
+        individual_search_data: &IndividualSearchData,
+        search_thread_data: &mut SearchThreadData<TPuzzle, Optimizations::PruneTable>,
+        initial_state: CanonicalFSMState,
+        remaining_depth: Depth,
+    ) -> SearchRecursionResult {
+        let num_threads = self.api_data.num_threads;
+
+        let mut tasks = vec![ParallelSearchTask { prefix: vec![] }];
+        let mut split_depth = Depth(0);
+        while tasks.len() < num_threads * MIN_PARALLEL_SEARCH_TASKS_PER_THREAD
+            && split_depth < MAX_PARALLEL_SEARCH_SPLIT_DEPTH
+            && split_depth + Depth(1) < remaining_depth
+        {
+            tasks = tasks
+                .into_iter()
+                .flat_map(|task| {
+                    self.split_parallel_search_task(task, initial_state, remaining_depth)
+                })
+                .collect();
+            split_depth += Depth(1);
+        }
+
+        let next_task_index = AtomicUsize::new(0);
+        let root_pattern = search_thread_data.pattern_stack.current_pattern();
+        std::thread::scope(|scope| {
+            let thread_handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut thread_data = SearchThreadData {
+                            pattern_stack: PatternStack::new(
+                                self.api_data.tpuzzle.clone(),
+                                root_pattern.clone(),
+                            ),
+                            statistics: SearchDepthStatistics::new(remaining_depth),
+                            prune_table: search_thread_data.prune_table,
+                        };
+                        loop {
+                            let task_index = next_task_index.fetch_add(1, Ordering::Relaxed);
+                            let Some(task) = tasks.get(task_index) else {
+                                break;
+                            };
+                            if let SearchRecursionResult::DoneSearching() = self
+                                .recurse_from_prefix(
+                                    individual_search_data,
+                                    &mut thread_data,
+                                    &task.prefix,
+                                    initial_state,
+                                    remaining_depth,
+                                    SolutionMoves(None),
+                                )
+                            {
+                                break;
+                            }
+                        }
+                        thread_data.statistics
+                    })
+                })
+                .collect();
+            for thread_handle in thread_handles {
+                let thread_statistics = thread_handle
+                    .join()
+                    .expect("Internal error: search thread panicked");
+                search_thread_data.statistics.add_counts(&thread_statistics);
+            }
+        });
+
+        if individual_search_data.stop_state.is_stopped() {
+            SearchRecursionResult::DoneSearching()
+        } else {
+            SearchRecursionResult::ContinueSearchingDefault()
+        }
+    }
+
+    fn split_parallel_search_task<'a>(
+        &'a self,
+        task: ParallelSearchTask<'a, TPuzzle>,
+        initial_state: CanonicalFSMState,
+        remaining_depth: Depth,
+    ) -> Vec<ParallelSearchTask<'a, TPuzzle>> {
+        let mut current_state = initial_state;
+        let mut remaining_depth = remaining_depth;
+        for move_transformation_info in &task.prefix {
+            current_state = self
+                .api_data
+                .canonical_fsm
+                .next_state(current_state, move_transformation_info.move_class_index)
+                .expect("Internal error: invalid parallel search task");
+            remaining_depth = remaining_depth - move_transformation_info.cost;
+        }
+        let mut subtasks = vec![];
+        for (move_class_index, move_transformation_multiples) in
+            self.api_data.search_generators.by_move_class.iter()
+        {
+            if self
+                .api_data
+                .canonical_fsm
+                .next_state(current_state, move_class_index)
+                .is_none()
+            {
+                continue;
+            };
+            for move_transformation_info in move_transformation_multiples {
+                if move_transformation_info.cost > remaining_depth {
+                    continue;
+                }
+                if !Optimizations::RecursionFilter::keep_move(
+                    move_transformation_info,
+                    remaining_depth,
+                ) {
+                    continue;
+                }
+                let mut prefix = task.prefix.clone();
+                prefix.push(move_transformation_info);
+                subtasks.push(ParallelSearchTask { prefix });
+            }
+        }
+        subtasks
+    }
+
+    // Applies the moves of a `ParallelSearchTask` prefix (without pruning) and then continues with the standard recursion.
+    fn recurse_from_prefix(
+        &self,
+        individual_search_data: &IndividualSearchData,
+        search_thread_data: &mut SearchThreadData<TPuzzle, Optimizations::PruneTable>,
+        prefix: &[&MoveTransformationInfo<TPuzzle>],
+        current_state: CanonicalFSMState,
+        remaining_depth: Depth,
+        solution_moves: SolutionMoves,
+    ) -> SearchRecursionResult {
+        let Some((move_transformation_info, remaining_prefix)) = prefix.split_first() else {
+            return self.recurse(
+                individual_search_data,
+                search_thread_data,
+                current_state,
+                remaining_depth,
+                solution_moves,
+            );
+        };
+        if !Optimizations::PatternValidityChecker::is_valid(
+            search_thread_data.pattern_stack.current_pattern(),
+        ) {
+            return SearchRecursionResult::ContinueSearchingDefault();
+        }
+        if move_transformation_info.cost > remaining_depth {
+            return SearchRecursionResult::ContinueSearchingDefault();
+        }
+        let Some(next_state) = self
+            .api_data
+            .canonical_fsm
+            .next_state(current_state, move_transformation_info.move_class_index)
+        else {
+            return SearchRecursionResult::ContinueSearchingDefault();
+        };
+        if !search_thread_data
+            .pattern_stack
+            .push(&move_transformation_info.transformation)
+        {
+            return SearchRecursionResult::ContinueSearchingDefault();
+        }
+        let recursive_result = self.recurse_from_prefix(
+            individual_search_data,
+            search_thread_data,
+            remaining_prefix,
+            next_state,
+            remaining_depth - move_transformation_info.cost,
+            SolutionMoves(Some(&SolutionPreviousMoves {
+                latest_alg_node: &move_transformation_info.alg_node,
+                previous_moves: &solution_moves,
+            })),
+        );
+        search_thread_data.pattern_stack.pop();
+        recursive_result
     }
 
     fn recurse(
         &self,
-        individual_search_data: &mut IndividualSearchData,
-        pattern_stack: &mut PatternStack<TPuzzle>,
+        individual_search_data: &IndividualSearchData,
+        search_thread_data: &mut SearchThreadData<TPuzzle, Optimizations::PruneTable>,
         current_state: CanonicalFSMState,
         remaining_depth: Depth,
         solution_moves: SolutionMoves,
     ) -> SearchRecursionResult {
-        let current_pattern = pattern_stack.current_pattern();
+        individual_search_data.wait_while_paused();
+        if individual_search_data.stop_state.is_stopped() {
+            return SearchRecursionResult::DoneSearching();
+        }
+        let current_pattern = search_thread_data.pattern_stack.current_pattern();
         // TODO: apply invalid checks only to intermediate state (i.e. exclude remaining_depth == 0)?
         if !Optimizations::PatternValidityChecker::is_valid(current_pattern) {
             return SearchRecursionResult::ContinueSearchingDefault();
         }
 
-        individual_search_data
-            .recursive_work_tracker
-            .record_recursive_call();
+        search_thread_data.statistics.num_recursive_calls += 1;
+        if search_thread_data.statistics.num_recursive_calls
+            % NUM_RECURSIVE_CALLS_BETWEEN_TIME_LIMIT_CHECKS
+            == 0
+            && individual_search_data.check_time_limit()
+        {
+            return SearchRecursionResult::DoneSearching();
+        }
         if remaining_depth == Depth(0) {
             return self.base_case(
                 individual_search_data,
@@ -340,8 +1194,14 @@ impl<
                 solution_moves,
             );
         }
-        let prune_table_depth = self.prune_table.lookup(current_pattern);
-        if prune_table_depth > remaining_depth + Depth(1) {
+        let prune_table_depth = search_thread_data.prune_table.lookup(current_pattern);
+        search_thread_data
+            .statistics
+            .record_prune_table_lookup(prune_table_depth, prune_table_depth > remaining_depth);
+        // If this pattern is more than 1 move too far from the target, so is
+        // any other multiple of the latest move. This only holds if every
+        // move multiple costs 1.
+        if self.api_data.unit_move_costs && prune_table_depth > remaining_depth + Depth(1) {
             return SearchRecursionResult::ContinueSearchingExcludingCurrentMoveClass();
         }
         if prune_table_depth > remaining_depth {
@@ -360,6 +1220,9 @@ impl<
             };
 
             for move_transformation_info in move_transformation_multiples {
+                if move_transformation_info.cost > remaining_depth {
+                    continue;
+                }
                 if !Optimizations::RecursionFilter::keep_move(
                     move_transformation_info,
                     remaining_depth,
@@ -367,21 +1230,24 @@ impl<
                     continue;
                 }
 
-                if !pattern_stack.push(&move_transformation_info.transformation) {
+                if !search_thread_data
+                    .pattern_stack
+                    .push(&move_transformation_info.transformation)
+                {
                     continue;
                 }
 
                 let recursive_result = self.recurse(
                     individual_search_data,
-                    pattern_stack,
+                    search_thread_data,
                     next_state,
-                    remaining_depth - Depth(1),
+                    remaining_depth - move_transformation_info.cost,
                     SolutionMoves(Some(&SolutionPreviousMoves {
-                        latest_move: &move_transformation_info.r#move,
+                        latest_alg_node: &move_transformation_info.alg_node,
                         previous_moves: &solution_moves,
                     })),
                 );
-                pattern_stack.pop();
+                search_thread_data.pattern_stack.pop();
 
                 match recursive_result {
                     SearchRecursionResult::DoneSearching() => {
@@ -397,46 +1263,18 @@ impl<
         SearchRecursionResult::ContinueSearchingDefault()
     }
 
-    // Returns `None` if the moves cannot be applied, else returns the result of applying the moves.
-    fn apply_optional_fsm_moves(
-        &self,
-        start_state: CanonicalFSMState,
-        moves: &Option<Vec<Move>>,
-    ) -> Option<CanonicalFSMState> {
-        let mut current_state = start_state;
-        if let Some(moves) = moves {
-            for r#move in moves {
-                let move_class_index = self
-                    .api_data
-                    .search_generators
-                    .by_move
-                    .get(r#move)
-                    .expect("move!")
-                    .move_class_index;
-                current_state = match self
-                    .api_data
-                    .canonical_fsm
-                    .next_state(current_state, move_class_index)
-                {
-                    Some(next_state) => next_state,
-                    None => return None,
-                }
-            }
-        }
-        Some(current_state)
-    }
-
     fn base_case(
         &self,
-        individual_search_data: &mut IndividualSearchData,
+        individual_search_data: &IndividualSearchData,
         current_pattern: &TPuzzle::Pattern,
         current_state: CanonicalFSMState,
         solution_moves: SolutionMoves,
     ) -> SearchRecursionResult {
-        if current_pattern != &self.api_data.target_pattern {
+        if !self.api_data.target_patterns.contains(current_pattern) {
             return SearchRecursionResult::ContinueSearchingDefault();
         }
         if self
+            .api_data
             .apply_optional_fsm_moves(
                 current_state,
                 &individual_search_data
@@ -452,24 +1290,164 @@ impl<
             return SearchRecursionResult::ContinueSearchingDefault();
         }
 
-        let alg = Alg::from(solution_moves);
-        individual_search_data.num_solutions_sofar += 1;
-        individual_search_data
-            .solution_sender
-            .send(Some(alg))
-            .expect("Internal error: could not send solution");
-        if individual_search_data.num_solutions_sofar
-            >= individual_search_data
-                .individual_search_options
-                .get_min_num_solutions()
-        {
-            individual_search_data
-                .solution_sender
-                .send(None)
-                .expect("Internal error: could not send end of search");
+        if individual_search_data.send_solution(Alg::from(solution_moves)) {
             SearchRecursionResult::DoneSearching()
         } else {
             SearchRecursionResult::ContinueSearchingDefault()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use cubing::{alg::parse_alg, kpuzzle::KPuzzle, puzzles::cube3x3x3_kpuzzle};
+
+    use crate::_internal::{
+        cli::args::VerbosityLevel,
+        search::{
+            prune_table_trait::Depth,
+            search_logger::{SearchLogEvent, SearchLogSink, SearchLogger},
+        },
+    };
+
+    use super::{
+        IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions, SearchEndReason,
+    };
+
+    #[test]
+    fn idf_search_multiple_target_patterns_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let target_patterns = ["", "x", "y"]
+            .into_iter()
+            .map(|alg| {
+                kpuzzle
+                    .default_pattern()
+                    .apply_alg(&alg.parse().unwrap())
+                    .unwrap()
+            })
+            .collect();
+        let mut idf_search = <IDFSearch<KPuzzle>>::try_new_with_target_patterns(
+            kpuzzle.clone(),
+            ["U", "L", "F", "R", "B", "D"]
+                .into_iter()
+                .map(|r#move| r#move.parse().unwrap())
+                .collect(),
+            target_patterns,
+            IDFSearchConstructionOptions::default(),
+        )
+        .unwrap();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("x R"))
+            .unwrap();
+        let mut solutions = idf_search.search(&search_pattern, Default::default());
+        assert_eq!(solutions.next().unwrap().to_string(), "R'");
+    }
+
+    #[derive(Default)]
+    struct SolutionCounter {
+        num_solutions_found: AtomicUsize,
+    }
+
+    impl SearchLogSink for SolutionCounter {
+        fn log(&self, event: &SearchLogEvent) {
+            if let SearchLogEvent::SolutionFound { .. } = event {
+                self.num_solutions_found.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn idf_search_pauses_between_solutions_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        // Every sequence of rotations reaches one of the target patterns, so
+        // every search thread finds solutions all the time.
+        let target_patterns = ["", "x", "x2", "x'", "z", "z'"]
+            .into_iter()
+            .flat_map(|first| ["", "y", "y2", "y'"].map(|second| format!("{} {}", first, second)))
+            .map(|alg| {
+                kpuzzle
+                    .default_pattern()
+                    .apply_alg(&alg.parse().unwrap())
+                    .unwrap()
+            })
+            .collect();
+        let solution_counter = Arc::new(SolutionCounter::default());
+        let mut idf_search = <IDFSearch<KPuzzle>>::try_new_with_target_patterns(
+            kpuzzle.clone(),
+            ["x", "y"]
+                .into_iter()
+                .map(|r#move| r#move.parse().unwrap())
+                .collect(),
+            target_patterns,
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(SearchLogger {
+                    verbosity: VerbosityLevel::Info,
+                    sink: Some(solution_counter.clone()),
+                }),
+                num_threads: Some(4),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // Deep enough to search in parallel.
+        let mut solutions = idf_search.search(
+            &kpuzzle.default_pattern(),
+            IndividualSearchOptions {
+                min_num_solutions: Some(1000),
+                min_depth: Some(Depth(7)),
+                ..Default::default()
+            },
+        );
+        for _ in 0..3 {
+            solutions.next().unwrap();
+        }
+        solutions.cancellation_handle().cancel();
+        assert_eq!(solutions.next(), None);
+        assert_eq!(solutions.end_reason(), Some(SearchEndReason::Cancelled));
+        // No thread sent another solution while the search was paused.
+        assert_eq!(
+            solution_counter.num_solutions_found.load(Ordering::Relaxed),
+            3
+        );
+    }
+
+    #[test]
+    fn idf_search_while_previous_search_is_paused_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let mut idf_search = <IDFSearch<KPuzzle>>::try_new(
+            kpuzzle.clone(),
+            ["U", "R"]
+                .into_iter()
+                .map(|r#move| r#move.parse().unwrap())
+                .collect(),
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions::default(),
+        )
+        .unwrap();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U"))
+            .unwrap();
+        let options = IndividualSearchOptions {
+            min_num_solutions: Some(5),
+            ..Default::default()
+        };
+        let mut paused_solutions = idf_search.search(&search_pattern, options.clone());
+        assert_eq!(paused_solutions.next().unwrap().to_string(), "U' R'");
+        let mut solutions = idf_search.search(&search_pattern, options);
+        assert_eq!(solutions.next().unwrap().to_string(), "U' R'");
+        assert_eq!(solutions.count(), 4);
+        // The new search cancelled the paused one.
+        assert_eq!(paused_solutions.next(), None);
+        assert_eq!(
+            paused_solutions.end_reason(),
+            Some(SearchEndReason::Cancelled)
+        );
+    }
+}
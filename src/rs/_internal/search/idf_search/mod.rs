@@ -1,2 +1,3 @@
+pub mod bidirectional_search;
 pub mod idf_search;
 pub mod search_adaptations;
@@ -9,7 +9,9 @@ use crate::_internal::{
 
 use super::super::{
     hash_prune_table::HashPruneTable,
+    masked_max_prune_table::MaskedMaxPruneTable,
     pattern_validity_checker::{AlwaysValid, PatternValidityChecker},
+    perfect_index_prune_table::PerfectIndexPruneTable,
     prune_table_trait::PruneTable,
 };
 
@@ -49,7 +51,7 @@ use super::super::{
 ///
 /// TODO: figure out if/when dynamic dispatch is actually cheap and ergonomic
 /// enough once we know all the adaptations we need for common puzzles.
-pub trait SearchAdaptations<TPuzzle: SemiGroupActionPuzzle> {
+pub trait SearchAdaptations<TPuzzle: SemiGroupActionPuzzle>: 'static {
     type PatternValidityChecker: PatternValidityChecker<TPuzzle>;
     type PruneTable: PruneTable<TPuzzle>;
     type RecursionFilter: RecursionFilter<TPuzzle>;
@@ -67,6 +69,26 @@ impl<TPuzzle: HashablePatternPuzzle> SearchAdaptations<TPuzzle>
     type RecursionFilter = RecursionFilterNoOp;
 }
 
+/// Uses the maximum of several prune tables, each on a masked subset of the
+/// puzzle (see [`MaskedMaxPruneTable`]).
+pub struct KPuzzleMaskedMaxPruneTableAdaptations {}
+
+impl SearchAdaptations<KPuzzle> for KPuzzleMaskedMaxPruneTableAdaptations {
+    type PatternValidityChecker = AlwaysValid;
+    type PruneTable = MaskedMaxPruneTable;
+    type RecursionFilter = RecursionFilterNoOp;
+}
+
+/// Uses a prune table with the exact distance for every pattern (see
+/// [`PerfectIndexPruneTable`]). This is only feasible for small puzzles.
+pub struct KPuzzlePerfectIndexPruneTableAdaptations {}
+
+impl SearchAdaptations<KPuzzle> for KPuzzlePerfectIndexPruneTableAdaptations {
+    type PatternValidityChecker = AlwaysValid;
+    type PruneTable = PerfectIndexPruneTable;
+    type RecursionFilter = RecursionFilterNoOp;
+}
+
 pub trait DefaultSearchAdaptations<TPuzzle: SemiGroupActionPuzzle> {
     type Adaptations: SearchAdaptations<TPuzzle>;
 }
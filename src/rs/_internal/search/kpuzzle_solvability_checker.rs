@@ -0,0 +1,478 @@
+use cubing::{
+    alg::{Alg, Move},
+    kpuzzle::{KPattern, KPuzzle, KPuzzleOrbitInfo, KTransformation},
+};
+
+use crate::_internal::{
+    errors::SearchError,
+    schreier_sims::stabilizer_chain::{Permutation, StabilizerChain},
+};
+
+// Unless requested explicitly, the reachability check is skipped for puzzles
+// with more (slot, orientation) points than this, since Schreier–Sims takes
+// time and memory that grow quickly with the number of points.
+const MAX_AUTO_REACHABILITY_CHECK_NUM_POINTS: usize = 256;
+
+struct CheckedOrbit {
+    // Orientations are compared modulo this (the orientation mod of every piece in the orbit).
+    orientation_mod: u8,
+    // Whether every generator preserves the orientation sum (modulo `orientation_mod`).
+    orientation_sum_is_preserved: bool,
+    // If all pieces are distinct, the pattern determines which transformation
+    // takes it to the target pattern. This is the index of the orbit in the
+    // parity vectors, and its first point for Schreier–Sims.
+    distinct_pieces: Option<(usize, usize)>,
+}
+
+/// Checks whether a pattern can be solved using the generators, so that a
+/// search for an unsolvable pattern can fail immediately (with an explanation)
+/// instead of running until the maximum depth.
+///
+/// The checks are derived from the generators:
+///
+/// - Each orbit must contain the same pieces as the target pattern.
+/// - Orientation sums that every generator preserves must match the target pattern.
+/// - Permutation parities must be reachable using the parities of the
+///   generators (e.g. the corner and edge parities of a 3x3x3 must match).
+/// - The transformation from the pattern to the target pattern must be in the
+///   group generated by the generators (using Schreier–Sims).
+///
+/// The last two checks only use orbits whose pieces are all distinct, and
+/// orbits with mixed orientation mods are only checked for their pieces.
+pub struct KPuzzleSolvabilityChecker {
+    kpuzzle: KPuzzle,
+    target_patterns: Vec<KPattern>,
+    // For each orbit (in `orbit_info_iter()` order), or `None` if the orientation mods are mixed.
+    checked_orbits: Vec<Option<CheckedOrbit>>,
+    // A reduced basis for the parity vectors of the generators, with the pivot of each vector.
+    parity_basis: Vec<(usize, Vec<bool>)>,
+    // For each orbit with distinct pieces, whether some generator is an odd permutation of it.
+    orbits_with_odd_generators: Vec<bool>,
+    num_points: usize,
+    stabilizer_chain: Option<StabilizerChain>,
+}
+
+fn is_odd_permutation(permutation: &[u8]) -> bool {
+    let mut visited = vec![false; permutation.len()];
+    let mut num_even_cycles = 0;
+    for start in 0..permutation.len() {
+        if visited[start] {
+            continue;
+        }
+        let mut cycle_length = 0;
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = permutation[i] as usize;
+            cycle_length += 1;
+        }
+        if cycle_length % 2 == 0 {
+            num_even_cycles += 1;
+        }
+    }
+    num_even_cycles % 2 == 1
+}
+
+// Reduces `vector` using the `basis` (over GF(2)).
+fn reduce_parity_vector(basis: &[(usize, Vec<bool>)], mut vector: Vec<bool>) -> Vec<bool> {
+    for (pivot, basis_vector) in basis {
+        if vector[*pivot] {
+            for (entry, basis_entry) in vector.iter_mut().zip(basis_vector) {
+                *entry ^= basis_entry;
+            }
+        }
+    }
+    vector
+}
+
+fn effective_orientation_mod(orbit_info: &KPuzzleOrbitInfo, orientation_mod: u8) -> u8 {
+    match orientation_mod {
+        0 => orbit_info.num_orientations,
+        orientation_mod => orientation_mod,
+    }
+}
+
+impl KPuzzleSolvabilityChecker {
+    /// The reachability check (Schreier–Sims) is only used for small puzzles
+    /// unless `always_check_reachability` is set.
+    pub fn try_new(
+        kpuzzle: &KPuzzle,
+        generator_moves: &[Move],
+        generator_algs: &[Alg],
+        target_patterns: &[KPattern],
+        always_check_reachability: bool,
+    ) -> Result<Self, SearchError> {
+        let Some(reference_pattern) = target_patterns.first() else {
+            return Err("At least one target pattern must be specified.".into());
+        };
+
+        let mut transformations: Vec<KTransformation> = vec![];
+        for r#move in generator_moves {
+            let Ok(transformation) = kpuzzle.transformation_from_move(r#move) else {
+                return Err(SearchError {
+                    description: format!("Could not get transformation for move: {}", r#move),
+                });
+            };
+            transformations.push(transformation);
+        }
+        for alg in generator_algs {
+            let Ok(transformation) = kpuzzle.transformation_from_alg(alg) else {
+                return Err(SearchError {
+                    description: format!("Could not get transformation for alg: {}", alg),
+                });
+            };
+            transformations.push(transformation);
+        }
+
+        let mut checked_orbits = vec![];
+        let mut num_distinct_orbits = 0;
+        let mut num_points = 0;
+        for orbit_info in kpuzzle.orbit_info_iter() {
+            let mut orientation_mods = (0..orbit_info.num_pieces).map(|i| {
+                effective_orientation_mod(
+                    orbit_info,
+                    reference_pattern
+                        .get_orientation_with_mod(orbit_info, i)
+                        .orientation_mod,
+                )
+            });
+            let orientation_mod = orientation_mods.next().unwrap_or(1);
+            if orientation_mods.any(|m| m != orientation_mod)
+                || orbit_info.num_orientations % orientation_mod != 0
+            {
+                checked_orbits.push(None);
+                continue;
+            }
+
+            let orientation_sum_is_preserved = transformations.iter().all(|transformation| {
+                (0..orbit_info.num_pieces)
+                    .map(|i| transformation.get_orientation_delta(orbit_info, i) as usize)
+                    .sum::<usize>()
+                    % orientation_mod as usize
+                    == 0
+            });
+
+            let mut pieces: Vec<u8> = (0..orbit_info.num_pieces)
+                .map(|i| reference_pattern.get_piece(orbit_info, i))
+                .collect();
+            pieces.sort();
+            pieces.dedup();
+            let distinct_pieces = if pieces.len() == orbit_info.num_pieces as usize {
+                let distinct_pieces = (num_distinct_orbits, num_points);
+                num_distinct_orbits += 1;
+                num_points += orbit_info.num_pieces as usize * orientation_mod as usize;
+                Some(distinct_pieces)
+            } else {
+                None
+            };
+
+            checked_orbits.push(Some(CheckedOrbit {
+                orientation_mod,
+                orientation_sum_is_preserved,
+                distinct_pieces,
+            }));
+        }
+
+        let mut parity_basis: Vec<(usize, Vec<bool>)> = vec![];
+        let mut orbits_with_odd_generators = vec![false; num_distinct_orbits];
+        for transformation in &transformations {
+            let mut parity_vector = vec![false; num_distinct_orbits];
+            for (orbit_info, checked_orbit) in kpuzzle.orbit_info_iter().zip(&checked_orbits) {
+                let Some(CheckedOrbit {
+                    distinct_pieces: Some((orbit_index, _)),
+                    ..
+                }) = checked_orbit
+                else {
+                    continue;
+                };
+                let permutation: Vec<u8> = (0..orbit_info.num_pieces)
+                    .map(|i| transformation.get_permutation_idx(orbit_info, i))
+                    .collect();
+                parity_vector[*orbit_index] = is_odd_permutation(&permutation);
+                orbits_with_odd_generators[*orbit_index] |= parity_vector[*orbit_index];
+            }
+            let parity_vector = reduce_parity_vector(&parity_basis, parity_vector);
+            if let Some(pivot) = parity_vector.iter().position(|entry| *entry) {
+                // Keep the basis reduced with respect to the new pivot.
+                for (_, basis_vector) in &mut parity_basis {
+                    if basis_vector[pivot] {
+                        for (entry, new_entry) in basis_vector.iter_mut().zip(&parity_vector) {
+                            *entry ^= new_entry;
+                        }
+                    }
+                }
+                parity_basis.push((pivot, parity_vector));
+            }
+        }
+
+        let mut checker = Self {
+            kpuzzle: kpuzzle.clone(),
+            target_patterns: target_patterns.to_vec(),
+            checked_orbits,
+            parity_basis,
+            orbits_with_odd_generators,
+            num_points,
+            stabilizer_chain: None,
+        };
+        if always_check_reachability || num_points <= MAX_AUTO_REACHABILITY_CHECK_NUM_POINTS {
+            let mut stabilizer_chain = StabilizerChain::new(num_points);
+            for transformation in &transformations {
+                stabilizer_chain.add_generator(checker.points_permutation(|_, orbit_info, i| {
+                    (
+                        transformation.get_permutation_idx(orbit_info, i),
+                        transformation.get_orientation_delta(orbit_info, i),
+                    )
+                }));
+            }
+            checker.stabilizer_chain = Some(stabilizer_chain);
+        }
+        Ok(checker)
+    }
+
+    /// Returns an error explaining why `pattern` cannot reach any of the target patterns, if it can't.
+    pub fn check(&self, pattern: &KPattern) -> Result<(), SearchError> {
+        let mut first_error = None;
+        for target_pattern in &self.target_patterns {
+            match self.check_against_target_pattern(pattern, target_pattern) {
+                Ok(()) => return Ok(()),
+                Err(description) => {
+                    first_error.get_or_insert(description);
+                }
+            }
+        }
+        Err(SearchError {
+            description: format!(
+                "The pattern cannot be solved using the generators: {}",
+                first_error.unwrap_or_default()
+            ),
+        })
+    }
+
+    fn check_against_target_pattern(
+        &self,
+        pattern: &KPattern,
+        target_pattern: &KPattern,
+    ) -> Result<(), String> {
+        let mut parity_vector = vec![false; self.orbits_with_odd_generators.len()];
+        // For each orbit, the slot of `pattern` that each slot of `target_pattern` gets its piece from.
+        let mut source_slots: Vec<Vec<u8>> = vec![];
+        for (orbit_info, checked_orbit) in self.kpuzzle.orbit_info_iter().zip(&self.checked_orbits)
+        {
+            let orbit_pieces = |pattern: &KPattern| {
+                let mut pieces: Vec<(u8, u8)> = (0..orbit_info.num_pieces)
+                    .map(|i| {
+                        (
+                            pattern.get_piece(orbit_info, i),
+                            effective_orientation_mod(
+                                orbit_info,
+                                pattern
+                                    .get_orientation_with_mod(orbit_info, i)
+                                    .orientation_mod,
+                            ),
+                        )
+                    })
+                    .collect();
+                pieces.sort();
+                pieces
+            };
+            if orbit_pieces(pattern) != orbit_pieces(target_pattern) {
+                return Err(format!(
+                    "orbit {} has different pieces than the target pattern",
+                    orbit_info.name
+                ));
+            }
+
+            let Some(checked_orbit) = checked_orbit else {
+                source_slots.push(vec![]);
+                continue;
+            };
+            let orientation_mod = checked_orbit.orientation_mod as usize;
+            if checked_orbit.orientation_sum_is_preserved {
+                let orientation_sum = |pattern: &KPattern| {
+                    (0..orbit_info.num_pieces)
+                        .map(|i| {
+                            pattern.get_orientation_with_mod(orbit_info, i).orientation as usize
+                        })
+                        .sum::<usize>()
+                        % orientation_mod
+                };
+                let (actual, expected) =
+                    (orientation_sum(pattern), orientation_sum(target_pattern));
+                if actual != expected {
+                    return Err(format!(
+                        "orientation sum of orbit {} is {} mod {} (expected {} mod {})",
+                        orbit_info.name, actual, orientation_mod, expected, orientation_mod
+                    ));
+                }
+            }
+
+            let Some((orbit_index, _)) = checked_orbit.distinct_pieces else {
+                source_slots.push(vec![]);
+                continue;
+            };
+            let mut slot_by_piece = vec![0; u8::MAX as usize + 1];
+            for i in 0..orbit_info.num_pieces {
+                slot_by_piece[pattern.get_piece(orbit_info, i) as usize] = i;
+            }
+            let orbit_source_slots: Vec<u8> = (0..orbit_info.num_pieces)
+                .map(|i| slot_by_piece[target_pattern.get_piece(orbit_info, i) as usize])
+                .collect();
+            parity_vector[orbit_index] = is_odd_permutation(&orbit_source_slots);
+            if parity_vector[orbit_index] && !self.orbits_with_odd_generators[orbit_index] {
+                return Err(format!(
+                    "permutation of orbit {} is odd, but the generators only make even permutations of it",
+                    orbit_info.name
+                ));
+            }
+            source_slots.push(orbit_source_slots);
+        }
+
+        if reduce_parity_vector(&self.parity_basis, parity_vector.clone())
+            .iter()
+            .any(|entry| *entry)
+        {
+            let odd_orbit_names: Vec<String> = self
+                .kpuzzle
+                .orbit_info_iter()
+                .zip(&self.checked_orbits)
+                .filter_map(|(orbit_info, checked_orbit)| {
+                    let (orbit_index, _) = checked_orbit.as_ref()?.distinct_pieces?;
+                    parity_vector[orbit_index].then(|| orbit_info.name.to_string())
+                })
+                .collect();
+            return Err(format!(
+                "the generators cannot reach this combination of permutation parities (odd: {})",
+                match odd_orbit_names.is_empty() {
+                    true => "none".to_owned(),
+                    false => odd_orbit_names.join(", "),
+                }
+            ));
+        }
+
+        if let Some(stabilizer_chain) = &self.stabilizer_chain {
+            let transformation = self.points_permutation(|orbit_position, orbit_info, i| {
+                let source_slot = source_slots[orbit_position][i as usize];
+                let num_orientations = orbit_info.num_orientations;
+                let orientation_delta = (target_pattern
+                    .get_orientation_with_mod(orbit_info, i)
+                    .orientation
+                    + num_orientations
+                    - pattern
+                        .get_orientation_with_mod(orbit_info, source_slot)
+                        .orientation)
+                    % num_orientations;
+                (source_slot, orientation_delta)
+            });
+            if !stabilizer_chain.contains(&transformation) {
+                return Err(
+                    "the pattern has no invalid parities or orientations, but the generators cannot reach it"
+                        .to_owned(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // The permutation of the (slot, orientation) points of the orbits with
+    // distinct pieces, where the piece in slot `source(…).0` moves to slot `i`
+    // and gains orientation `source(…).1`. The first argument of `source` is
+    // the position of the orbit in `orbit_info_iter()`.
+    fn points_permutation(
+        &self,
+        source: impl Fn(usize, &KPuzzleOrbitInfo, u8) -> (u8, u8),
+    ) -> Permutation {
+        let mut images = vec![0; self.num_points];
+        for (orbit_position, (orbit_info, checked_orbit)) in self
+            .kpuzzle
+            .orbit_info_iter()
+            .zip(&self.checked_orbits)
+            .enumerate()
+        {
+            let Some(CheckedOrbit {
+                orientation_mod,
+                distinct_pieces: Some((_, point_offset)),
+                ..
+            }) = checked_orbit
+            else {
+                continue;
+            };
+            let orientation_mod = *orientation_mod as usize;
+            for i in 0..orbit_info.num_pieces {
+                let (source_slot, orientation_delta) = source(orbit_position, orbit_info, i);
+                for orientation in 0..orientation_mod {
+                    let from = point_offset + source_slot as usize * orientation_mod + orientation;
+                    let to = point_offset
+                        + i as usize * orientation_mod
+                        + (orientation + orientation_delta as usize) % orientation_mod;
+                    images[from] = to as u32;
+                }
+            }
+        }
+        Permutation::from_images(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::{
+        alg::parse_alg,
+        kpuzzle::{KPattern, KPatternData, KPuzzleOrbitName},
+        puzzles::cube3x3x3_kpuzzle,
+    };
+
+    use super::KPuzzleSolvabilityChecker;
+
+    #[test]
+    fn kpuzzle_solvability_checker_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let generator_moves: Vec<_> = ["U", "L", "F", "R", "B", "D"]
+            .into_iter()
+            .map(|r#move| r#move.parse().unwrap())
+            .collect();
+        let checker = KPuzzleSolvabilityChecker::try_new(
+            kpuzzle,
+            &generator_moves,
+            &[],
+            &[kpuzzle.default_pattern()],
+            false,
+        )
+        .unwrap();
+
+        let scrambled = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U F' D2 L"))
+            .unwrap();
+        assert!(checker.check(&scrambled).is_ok());
+
+        let modified_pattern = |f: &dyn Fn(&mut KPatternData)| {
+            let mut data = kpuzzle.default_pattern().to_data();
+            f(&mut data);
+            KPattern::try_from_data(kpuzzle, &data).unwrap()
+        };
+        let twisted_corner = modified_pattern(&|data| {
+            data.get_mut(&KPuzzleOrbitName::from("CORNERS"))
+                .unwrap()
+                .orientation[0] = 1;
+        });
+        let description = checker.check(&twisted_corner).unwrap_err().description;
+        assert!(
+            description.ends_with("orientation sum of orbit CORNERS is 1 mod 3 (expected 0 mod 3)")
+        );
+
+        let swapped_edges = modified_pattern(&|data| {
+            data.get_mut(&KPuzzleOrbitName::from("EDGES"))
+                .unwrap()
+                .pieces
+                .swap(0, 1);
+        });
+        let description = checker.check(&swapped_edges).unwrap_err().description;
+        assert!(description.contains("permutation parities (odd: EDGES)"));
+
+        // `M` moves centers, which the face turns cannot do.
+        let slice_moved = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("M"))
+            .unwrap();
+        assert!(checker.check(&slice_moved).is_err());
+    }
+}
@@ -9,55 +9,93 @@ pub(crate) fn apply_mask(
     mask_pattern: &KPattern,
 ) -> Result<KPattern, PuzzleError> {
     let mut masked_pattern = mask_pattern.clone();
-    for orbit_info in source_pattern.kpuzzle().orbit_info_iter() {
-        for i in 0..orbit_info.num_pieces {
-            let old_piece = source_pattern.get_piece(orbit_info, i);
-            let old_piece_mapped = mask_pattern.get_piece(orbit_info, old_piece);
-            masked_pattern.set_piece(orbit_info, i, old_piece_mapped);
-
-            let source_orientation_with_mod =
-                source_pattern.get_orientation_with_mod(orbit_info, i);
-            let mask_orientation_with_mod =
-                mask_pattern.get_orientation_with_mod(orbit_info, old_piece_mapped);
-
-            if mask_orientation_with_mod.orientation != 0 {
-                return Err(PuzzleError {
-                    description: "Masks cannot currently have piece orientation".to_owned(),
-                });
-            };
-
-            let source_mod = source_orientation_with_mod.orientation_mod;
-            let source_mod = if source_mod == 0 {
-                orbit_info.num_orientations
-            } else {
-                source_mod
-            };
-
-            let mask_mod = mask_orientation_with_mod.orientation_mod;
-            let mask_mod = if mask_mod == 0 {
-                orbit_info.num_orientations
-            } else {
-                mask_mod
-            };
-
-            if source_mod % mask_mod != 0 && mask_mod % source_mod != 0 {
-                return Err(PuzzleError {
-                    description: "Incompatible orientation mod in mask".to_owned(),
-                });
-            };
-
-            let masked_mod = min(source_mod, mask_mod);
-            let orientation_with_mod = OrientationWithMod {
-                orientation: source_orientation_with_mod.orientation % masked_mod,
-                orientation_mod: if masked_mod == orbit_info.num_orientations {
-                    0
+    PrecomputedMask::new(mask_pattern).apply_into(source_pattern, &mut masked_pattern)?;
+    Ok(masked_pattern)
+}
+
+/// A mask pattern with the mapping for each piece computed ahead of time, so
+/// that it can be applied repeatedly (e.g. for every prune table lookup)
+/// without allocating.
+pub(crate) struct PrecomputedMask {
+    // For each orbit, indexed by the piece in the source pattern: the masked
+    // piece and the orientation mod of the mask for it (with `0` replaced by
+    // the number of orientations). `None` if the mask has an orientation for
+    // the masked piece.
+    orbits: Vec<Vec<Option<(u8, u8)>>>,
+}
+
+impl PrecomputedMask {
+    pub(crate) fn new(mask_pattern: &KPattern) -> Self {
+        let orbits = mask_pattern
+            .kpuzzle()
+            .orbit_info_iter()
+            .map(|orbit_info| {
+                (0..orbit_info.num_pieces)
+                    .map(|piece| {
+                        let masked_piece = mask_pattern.get_piece(orbit_info, piece);
+                        let mask_orientation_with_mod =
+                            mask_pattern.get_orientation_with_mod(orbit_info, masked_piece);
+                        if mask_orientation_with_mod.orientation != 0 {
+                            return None;
+                        }
+                        let mask_mod = match mask_orientation_with_mod.orientation_mod {
+                            0 => orbit_info.num_orientations,
+                            mask_mod => mask_mod,
+                        };
+                        Some((masked_piece, mask_mod))
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { orbits }
+    }
+
+    /// Writes the masked `source_pattern` into `masked_pattern` (which must be
+    /// a pattern of the same puzzle). Every piece of `masked_pattern` is overwritten.
+    pub(crate) fn apply_into(
+        &self,
+        source_pattern: &KPattern,
+        masked_pattern: &mut KPattern,
+    ) -> Result<(), PuzzleError> {
+        for (orbit_info, orbit_mask) in source_pattern.kpuzzle().orbit_info_iter().zip(&self.orbits)
+        {
+            for i in 0..orbit_info.num_pieces {
+                let old_piece = source_pattern.get_piece(orbit_info, i);
+                let Some((old_piece_mapped, mask_mod)) = orbit_mask[old_piece as usize] else {
+                    return Err(PuzzleError {
+                        description: "Masks cannot currently have piece orientation".to_owned(),
+                    });
+                };
+                masked_pattern.set_piece(orbit_info, i, old_piece_mapped);
+
+                let source_orientation_with_mod =
+                    source_pattern.get_orientation_with_mod(orbit_info, i);
+                let source_mod = source_orientation_with_mod.orientation_mod;
+                let source_mod = if source_mod == 0 {
+                    orbit_info.num_orientations
                 } else {
-                    masked_mod
-                },
-            };
+                    source_mod
+                };
+
+                if source_mod % mask_mod != 0 && mask_mod % source_mod != 0 {
+                    return Err(PuzzleError {
+                        description: "Incompatible orientation mod in mask".to_owned(),
+                    });
+                };
 
-            masked_pattern.set_orientation_with_mod(orbit_info, i, &orientation_with_mod);
+                let masked_mod = min(source_mod, mask_mod);
+                let orientation_with_mod = OrientationWithMod {
+                    orientation: source_orientation_with_mod.orientation % masked_mod,
+                    orientation_mod: if masked_mod == orbit_info.num_orientations {
+                        0
+                    } else {
+                        masked_mod
+                    },
+                };
+
+                masked_pattern.set_orientation_with_mod(orbit_info, i, &orientation_with_mod);
+            }
         }
+        Ok(())
     }
-    Ok(masked_pattern)
 }
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use std::cell::RefCell;
+
+use cubing::kpuzzle::{KPattern, KPuzzle};
+
+use crate::_internal::errors::SearchError;
+
+use super::hash_prune_table::{HashPruneTable, HashPruneTableOptions};
+use super::idf_search::idf_search::IDFSearchAPIData;
+use super::mask_pattern::{apply_mask, PrecomputedMask};
+use super::pattern_validity_checker::AlwaysValid;
+use super::prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions};
+use super::search_logger::SearchLogger;
+use super::search_statistics::PruneTableStatistics;
+
+thread_local! {
+    // Reused by `MaskedMaxPruneTable::lookup`, which is called for every node of the search.
+    static MASKED_PATTERN: RefCell<Option<KPattern>> = const { RefCell::new(None) };
+}
+
+/// Options that only apply to a [`MaskedMaxPruneTable`].
+#[derive(Clone, Debug, Default)]
+pub struct MaskedMaxPruneTableOptions {
+    /// Mask patterns (see `apply_mask`) for the subsets of the puzzle that get
+    /// their own tables.
+    pub masks: Vec<KPattern>,
+}
+
+struct MaskedPruneTable {
+    mask: PrecomputedMask,
+    prune_table: HashPruneTable<KPuzzle, AlwaysValid>,
+}
+
+/// A prune table that combines several tables, each of which is built on a
+/// subset of the puzzle (e.g. only the corners). Each subset is specified by a
+/// mask pattern (see [`MaskedMaxPruneTableOptions::masks`]).
+///
+/// Every table gives a lower bound for the distance to the target patterns, so
+/// a lookup returns the maximum of the lookups in all the tables.
+pub struct MaskedMaxPruneTable {
+    masked_prune_tables: Vec<MaskedPruneTable>,
+}
+
+impl PruneTable<KPuzzle> for MaskedMaxPruneTable {
+    type TableOptions = MaskedMaxPruneTableOptions;
+
+    fn new(
+        tpuzzle: KPuzzle,
+        search_api_data: Arc<IDFSearchAPIData<KPuzzle>>,
+        search_logger: Arc<SearchLogger>,
+        options: PruneTableConstructionOptions<MaskedMaxPruneTableOptions>,
+    ) -> Result<Self, SearchError> {
+        let masks = options.table_options.masks;
+        if masks.is_empty() {
+            return Err("A masked prune table needs at least one mask.".into());
+        }
+        // The memory is shared evenly between the tables.
+        let num_tables = masks.len();
+        let masked_options = PruneTableConstructionOptions {
+            min_size: options
+                .min_size
+                .map(|min_size| usize::max(1, min_size / num_tables)),
+            max_memory_bytes: options
+                .max_memory_bytes
+                .map(|max_memory_bytes| max_memory_bytes / num_tables),
+            persistence: options.persistence,
+            table_options: HashPruneTableOptions::default(),
+        };
+
+        let mut masked_prune_tables = vec![];
+        for (i, mask) in masks.iter().enumerate() {
+            let mut target_patterns: Vec<KPattern> = vec![];
+            for target_pattern in &search_api_data.target_patterns {
+                let masked_target_pattern =
+                    apply_mask(target_pattern, mask).map_err(|e| SearchError {
+                        description: format!("Invalid prune table mask: {}", e.description),
+                    })?;
+                if !target_patterns.contains(&masked_target_pattern) {
+                    target_patterns.push(masked_target_pattern);
+                }
+            }
+            search_logger.write_info(&format!(
+                "[Prune table] Building masked prune table #{}.",
+                i + 1
+            ));
+            let masked_search_api_data = Arc::new(IDFSearchAPIData {
+                search_generators: search_api_data.search_generators.clone(),
+                canonical_fsm: search_api_data.canonical_fsm.clone(),
+                tpuzzle: tpuzzle.clone(),
+                target_patterns,
+                search_logger: search_api_data.search_logger.clone(),
+                metric: search_api_data.metric.clone(),
+                num_threads: search_api_data.num_threads,
+                unit_move_costs: search_api_data.unit_move_costs,
+            });
+            let prune_table = HashPruneTable::new(
+                tpuzzle.clone(),
+                masked_search_api_data,
+                search_logger.clone(),
+                masked_options.clone(),
+            )?;
+            masked_prune_tables.push(MaskedPruneTable {
+                mask: PrecomputedMask::new(mask),
+                prune_table,
+            });
+        }
+        Ok(Self {
+            masked_prune_tables,
+        })
+    }
+
+    fn lookup(&self, pattern: &KPattern) -> Depth {
+        MASKED_PATTERN.with_borrow_mut(|masked_pattern| {
+            // Every piece is overwritten by each mask, so any pattern of the same puzzle works as a buffer.
+            let masked_pattern = match masked_pattern {
+                Some(masked_pattern)
+                    if Arc::ptr_eq(&masked_pattern.kpuzzle().data, &pattern.kpuzzle().data) =>
+                {
+                    masked_pattern
+                }
+                _ => masked_pattern.insert(pattern.clone()),
+            };
+            let mut depth = Depth(0);
+            for masked_prune_table in &self.masked_prune_tables {
+                // Masks are validated against the target patterns during construction, so this only
+                // fails for patterns with unusual orientation mods. `0` is always a valid lower bound.
+                if masked_prune_table
+                    .mask
+                    .apply_into(pattern, masked_pattern)
+                    .is_err()
+                {
+                    continue;
+                }
+                depth = Depth::max(depth, masked_prune_table.prune_table.lookup(masked_pattern));
+            }
+            depth
+        })
+    }
+
+    fn extend_for_search_depth(
+        &mut self,
+        search_depth: Depth,
+        approximate_num_entries: usize,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    ) {
+        let num_tables = self.masked_prune_tables.len();
+        for masked_prune_table in &mut self.masked_prune_tables {
+            masked_prune_table.prune_table.extend_for_search_depth(
+                search_depth,
+                approximate_num_entries / num_tables,
+                should_stop,
+            );
+        }
+    }
+
+    // The pruning depth is the smallest pruning depth of any of the tables.
+    fn statistics(&self) -> PruneTableStatistics {
+        let mut statistics = PruneTableStatistics {
+            pruning_depth: Depth(usize::MAX),
+            ..Default::default()
+        };
+        for masked_prune_table in &self.masked_prune_tables {
+            let masked_statistics = masked_prune_table.prune_table.statistics();
+            statistics.num_entries += masked_statistics.num_entries;
+            statistics.num_bytes += masked_statistics.num_bytes;
+            statistics.pruning_depth =
+                Depth::min(statistics.pruning_depth, masked_statistics.pruning_depth);
+        }
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cubing::{
+        alg::{parse_alg, parse_move},
+        kpuzzle::{KPattern, KPatternData, KPuzzle},
+        puzzles::cube2x2x2_kpuzzle,
+    };
+
+    use crate::_internal::search::{
+        idf_search::idf_search::{
+            IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions,
+        },
+        idf_search::search_adaptations::KPuzzleMaskedMaxPruneTableAdaptations,
+    };
+
+    use super::MaskedMaxPruneTableOptions;
+
+    #[test]
+    fn masked_max_prune_table_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        // Distinguish only the permutation (resp. orientation) of the corners.
+        let permutation_mask: KPatternData = serde_json::from_str(
+            r#"{ "CORNERS": { "pieces": [0, 1, 2, 3, 4, 5, 6, 7], "orientation": [0, 0, 0, 0, 0, 0, 0, 0], "orientationMod": [1, 1, 1, 1, 1, 1, 1, 1] } }"#,
+        )
+        .unwrap();
+        let orientation_mask: KPatternData = serde_json::from_str(
+            r#"{ "CORNERS": { "pieces": [0, 0, 0, 0, 0, 0, 0, 0], "orientation": [0, 0, 0, 0, 0, 0, 0, 0] } }"#,
+        )
+        .unwrap();
+        let masks = vec![
+            KPattern::try_from_data(kpuzzle, &permutation_mask).unwrap(),
+            KPattern::try_from_data(kpuzzle, &orientation_mask).unwrap(),
+        ];
+
+        let mut idf_search = <IDFSearch<KPuzzle, KPuzzleMaskedMaxPruneTableAdaptations>>::try_new_with_prune_table_options(
+            kpuzzle.clone(),
+            vec![parse_move!("U"), parse_move!("F"), parse_move!("R")],
+            vec![kpuzzle.default_pattern()],
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(Default::default()),
+                ..Default::default()
+            },
+            MaskedMaxPruneTableOptions { masks },
+        )
+        .map_err(|e| e.description)?;
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U' F R2"))
+            .unwrap();
+        let solution = idf_search
+            .search(&search_pattern, IndividualSearchOptions::default())
+            .next()
+            .unwrap();
+        assert_eq!(solution.nodes.len(), 4);
+        assert_eq!(
+            search_pattern.apply_alg(&solution).unwrap(),
+            kpuzzle.default_pattern()
+        );
+        Ok(())
+    }
+}
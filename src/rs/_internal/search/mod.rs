@@ -1,14 +1,20 @@
-pub mod pattern_validity_checker;
 pub mod coordinates;
-pub(crate) mod hash_prune_table;
+pub mod hash_prune_table;
 #[allow(clippy::module_inception)]
 pub mod idf_search;
 pub mod indexed_vec;
+pub mod kpuzzle_solvability_checker;
 pub(crate) mod mask_pattern;
+pub mod masked_max_prune_table;
 pub mod move_count;
 pub(crate) mod pattern_stack;
-pub(crate) mod prune_table_trait;
+pub mod pattern_validity_checker;
+pub mod perfect_index_prune_table;
+pub mod prune_table_entries;
+pub mod prune_table_persistence;
+pub mod prune_table_trait;
 pub(crate) mod recursion_filter_trait;
 pub(crate) mod recursive_work_tracker;
 pub mod search_logger;
+pub mod search_statistics;
 pub mod whole_number_newtype;
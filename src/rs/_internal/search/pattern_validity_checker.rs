@@ -1,6 +1,6 @@
 use crate::_internal::puzzle_traits::puzzle_traits::SemiGroupActionPuzzle;
 
-pub trait PatternValidityChecker<TPuzzle: SemiGroupActionPuzzle> {
+pub trait PatternValidityChecker<TPuzzle: SemiGroupActionPuzzle>: Send + Sync {
     fn is_valid(pattern: &TPuzzle::Pattern) -> bool;
 }
 
@@ -0,0 +1,770 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use cubing::kpuzzle::{KPattern, KPuzzle, KPuzzleOrbitInfo, OrientationWithMod};
+use thousands::Separable;
+
+use crate::_internal::errors::SearchError;
+
+use super::idf_search::idf_search::IDFSearchAPIData;
+use super::prune_table_trait::{Depth, PruneTable, PruneTableConstructionOptions};
+use super::search_logger::SearchLogger;
+use super::search_statistics::PruneTableStatistics;
+
+// Entries take one byte each, so this is also the default memory limit in bytes.
+const DEFAULT_MAX_PERFECT_INDEX_PRUNE_TABLE_SIZE: usize = 1 << 28;
+
+// The fill is split into chunks of this many entries, which are shared between
+// the threads. Stop requests are checked between chunks.
+const FILL_CHUNK_SIZE: usize = 1 << 16;
+
+// 0 is unreached, all other values are stored as 1+depth.
+const UNREACHED_SENTINEL: u8 = 0;
+
+// Patterns that cannot reach the target patterns using the generators.
+const UNREACHABLE_DEPTH: Depth = Depth(usize::MAX);
+
+struct OrbitIndexer {
+    // The slots that some generator changes (by moving or twisting the piece in it).
+    moved_slots: Vec<u8>,
+    // The contents of all other slots, which are the same as in the target patterns.
+    fixed_slots: Vec<(u8, u8, OrientationWithMod)>,
+    // The distinct piece labels in the moved slots (sorted), how many of each
+    // there are, and their orientation mod.
+    labels: Vec<u8>,
+    label_counts: Vec<usize>,
+    label_orientation_mods: Vec<u8>,
+    // Index into `labels` for each piece label.
+    label_indices: Vec<Option<usize>>,
+    // The number of values for each orientation digit.
+    orientation_radix: usize,
+    // If the generators preserve the sum of the orientations in this orbit, the
+    // last orientation is determined by the others and is not part of the index.
+    orientation_sum: Option<u8>,
+    num_arrangements: usize,
+    num_orientation_indices: usize,
+}
+
+impl OrbitIndexer {
+    fn num_orientation_digits(&self) -> usize {
+        match self.orientation_sum {
+            Some(_) => self.moved_slots.len() - 1,
+            None => self.moved_slots.len(),
+        }
+    }
+
+    fn size(&self) -> Option<usize> {
+        self.num_arrangements
+            .checked_mul(self.num_orientation_indices)
+    }
+
+    // Returns `None` if the pattern does not match the pieces of the target patterns.
+    #[inline]
+    fn rank(&self, orbit_info: &KPuzzleOrbitInfo, pattern: &KPattern) -> Option<(usize, usize)> {
+        for (slot, piece, orientation_with_mod) in &self.fixed_slots {
+            if pattern.get_piece(orbit_info, *slot) != *piece
+                || pattern.get_orientation_with_mod(orbit_info, *slot) != orientation_with_mod
+            {
+                return None;
+            }
+        }
+
+        // Multiset permutation rank: the number of arrangements that come before this one.
+        let mut counts = self.label_counts.clone();
+        let mut remaining = self.moved_slots.len();
+        let mut num_arrangements = self.num_arrangements;
+        let mut arrangement_index = 0;
+        let mut orientation_index = 0;
+        let mut orientation_sum = 0;
+        for (i, slot) in self.moved_slots.iter().enumerate() {
+            let label_index = self.label_indices[pattern.get_piece(orbit_info, *slot) as usize]?;
+            if counts[label_index] == 0 {
+                return None;
+            }
+            for count in &counts[..label_index] {
+                arrangement_index += num_arrangements * count / remaining;
+            }
+            num_arrangements = num_arrangements * counts[label_index] / remaining;
+            counts[label_index] -= 1;
+            remaining -= 1;
+
+            let orientation_with_mod = pattern.get_orientation_with_mod(orbit_info, *slot);
+            if orientation_with_mod.orientation_mod != self.label_orientation_mods[label_index] {
+                return None;
+            }
+            let orientation = orientation_with_mod.orientation;
+            if i < self.num_orientation_digits() {
+                orientation_index =
+                    orientation_index * self.orientation_radix + orientation as usize;
+                orientation_sum += orientation as usize;
+            } else if let Some(expected_sum) = self.orientation_sum {
+                if (orientation_sum + orientation as usize) % orbit_info.num_orientations as usize
+                    != expected_sum as usize
+                {
+                    return None;
+                }
+            }
+        }
+        Some((arrangement_index, orientation_index))
+    }
+
+    fn unrank(
+        &self,
+        orbit_info: &KPuzzleOrbitInfo,
+        mut arrangement_index: usize,
+        mut orientation_index: usize,
+        pattern: &mut KPattern,
+    ) {
+        let mut counts = self.label_counts.clone();
+        let mut remaining = self.moved_slots.len();
+        let mut num_arrangements = self.num_arrangements;
+        let mut labels_indices = Vec::with_capacity(remaining);
+        for _ in &self.moved_slots {
+            for (label_index, count) in counts.iter_mut().enumerate() {
+                let num_arrangements_with_label = num_arrangements * *count / remaining;
+                if arrangement_index < num_arrangements_with_label {
+                    num_arrangements = num_arrangements_with_label;
+                    *count -= 1;
+                    remaining -= 1;
+                    labels_indices.push(label_index);
+                    break;
+                }
+                arrangement_index -= num_arrangements_with_label;
+            }
+        }
+
+        let num_orientation_digits = self.num_orientation_digits();
+        let mut orientations = vec![0; self.moved_slots.len()];
+        for orientation in orientations[..num_orientation_digits].iter_mut().rev() {
+            *orientation = (orientation_index % self.orientation_radix) as u8;
+            orientation_index /= self.orientation_radix;
+        }
+        if let Some(orientation_sum) = self.orientation_sum {
+            let num_orientations = orbit_info.num_orientations as usize;
+            let sum_of_others: usize = orientations.iter().map(|o| *o as usize).sum();
+            orientations[num_orientation_digits] = ((orientation_sum as usize + num_orientations
+                - sum_of_others % num_orientations)
+                % num_orientations) as u8;
+        }
+
+        for ((slot, label_index), orientation) in self
+            .moved_slots
+            .iter()
+            .zip(labels_indices)
+            .zip(orientations)
+        {
+            pattern.set_piece(orbit_info, *slot, self.labels[label_index]);
+            pattern.set_orientation_with_mod(
+                orbit_info,
+                *slot,
+                &OrientationWithMod {
+                    orientation,
+                    orientation_mod: self.label_orientation_mods[label_index],
+                },
+            );
+        }
+    }
+}
+
+/// Ranks the patterns that can be reached from the target patterns to dense
+/// indices, using the orbits of the `KPuzzle` definition. The index combines a
+/// permutation rank and an orientation index for each orbit, and only covers
+/// the slots that the generators change.
+struct KPuzzlePerfectIndexer {
+    kpuzzle: KPuzzle,
+    orbit_indexers: Vec<OrbitIndexer>,
+    size: usize,
+}
+
+fn effective_orientation_mod(orbit_info: &KPuzzleOrbitInfo, orientation_mod: u8) -> u8 {
+    match orientation_mod {
+        0 => orbit_info.num_orientations,
+        orientation_mod => orientation_mod,
+    }
+}
+
+fn multinomial(counts: &[usize]) -> Option<usize> {
+    let mut result: usize = 1;
+    let mut total = 0;
+    for count in counts {
+        for i in 1..=*count {
+            total += 1;
+            // `result * total / i` is always a whole number.
+            result = result.checked_mul(total)? / i;
+        }
+    }
+    Some(result)
+}
+
+impl KPuzzlePerfectIndexer {
+    fn try_new(
+        kpuzzle: &KPuzzle,
+        search_api_data: &IDFSearchAPIData<KPuzzle>,
+        max_size: usize,
+    ) -> Result<Self, SearchError> {
+        let Some(reference_pattern) = search_api_data.target_patterns.first() else {
+            return Err("An exact prune table needs at least one target pattern.".into());
+        };
+        let transformations: Vec<_> = search_api_data
+            .search_generators
+            .flat
+            .iter()
+            .map(|(_, move_transformation_info)| &move_transformation_info.transformation)
+            .collect();
+
+        let mut orbit_indexers = vec![];
+        let mut size: Option<usize> = Some(1);
+        for orbit_info in kpuzzle.orbit_info_iter() {
+            let (moved_slots, fixed_slots): (Vec<u8>, Vec<u8>) = (0..orbit_info.num_pieces)
+                .partition(|slot| {
+                    transformations.iter().any(|transformation| {
+                        transformation.get_permutation_idx(orbit_info, *slot) != *slot
+                            || transformation.get_orientation_delta(orbit_info, *slot) != 0
+                    })
+                });
+            let fixed_slots = fixed_slots
+                .into_iter()
+                .map(|slot| {
+                    (
+                        slot,
+                        reference_pattern.get_piece(orbit_info, slot),
+                        *reference_pattern.get_orientation_with_mod(orbit_info, slot),
+                    )
+                })
+                .collect();
+
+            let mut labels: Vec<u8> = moved_slots
+                .iter()
+                .map(|slot| reference_pattern.get_piece(orbit_info, *slot))
+                .collect();
+            labels.sort();
+            labels.dedup();
+            let mut label_indices = vec![None; u8::MAX as usize + 1];
+            for (label_index, label) in labels.iter().enumerate() {
+                label_indices[*label as usize] = Some(label_index);
+            }
+            let mut label_counts = vec![0; labels.len()];
+            let mut label_orientation_mods: Vec<Option<u8>> = vec![None; labels.len()];
+            for slot in &moved_slots {
+                let label_index =
+                    label_indices[reference_pattern.get_piece(orbit_info, *slot) as usize].unwrap();
+                label_counts[label_index] += 1;
+                let orientation_mod = reference_pattern
+                    .get_orientation_with_mod(orbit_info, *slot)
+                    .orientation_mod;
+                match label_orientation_mods[label_index] {
+                    None => label_orientation_mods[label_index] = Some(orientation_mod),
+                    Some(existing) if existing == orientation_mod => {}
+                    Some(_) => {
+                        return Err(SearchError {
+                            description: format!(
+                                "Identical pieces must have the same orientation mod for an exact prune table (orbit: {})",
+                                orbit_info.name
+                            ),
+                        })
+                    }
+                }
+            }
+            let label_orientation_mods: Vec<u8> =
+                label_orientation_mods.into_iter().flatten().collect();
+            let orientation_radix = label_orientation_mods
+                .iter()
+                .map(|orientation_mod| effective_orientation_mod(orbit_info, *orientation_mod))
+                .max()
+                .unwrap_or(1) as usize;
+
+            // The orientation sum is preserved if every generator changes it by a multiple of the
+            // number of orientations, and every piece uses all the orientations.
+            let num_orientations = orbit_info.num_orientations as usize;
+            let orientation_sum_of = |pattern: &KPattern| {
+                (moved_slots
+                    .iter()
+                    .map(|slot| {
+                        pattern
+                            .get_orientation_with_mod(orbit_info, *slot)
+                            .orientation as usize
+                    })
+                    .sum::<usize>()
+                    % num_orientations) as u8
+            };
+            let reference_orientation_sum = orientation_sum_of(reference_pattern);
+            let orientation_sum_is_preserved = !moved_slots.is_empty()
+                && orientation_radix == num_orientations
+                && label_orientation_mods.iter().all(|orientation_mod| {
+                    effective_orientation_mod(orbit_info, *orientation_mod) as usize
+                        == num_orientations
+                })
+                && transformations.iter().all(|transformation| {
+                    moved_slots
+                        .iter()
+                        .map(|slot| {
+                            transformation.get_orientation_delta(orbit_info, *slot) as usize
+                        })
+                        .sum::<usize>()
+                        % num_orientations
+                        == 0
+                })
+                && search_api_data
+                    .target_patterns
+                    .iter()
+                    .all(|target_pattern| {
+                        orientation_sum_of(target_pattern) == reference_orientation_sum
+                    });
+
+            let mut orbit_indexer = OrbitIndexer {
+                moved_slots,
+                fixed_slots,
+                labels,
+                num_arrangements: multinomial(&label_counts).unwrap_or(usize::MAX),
+                label_counts,
+                label_orientation_mods,
+                label_indices,
+                orientation_radix,
+                orientation_sum: orientation_sum_is_preserved.then_some(reference_orientation_sum),
+                num_orientation_indices: 0,
+            };
+            orbit_indexer.num_orientation_indices =
+                u32::try_from(orbit_indexer.num_orientation_digits())
+                    .ok()
+                    .and_then(|num_digits| orientation_radix.checked_pow(num_digits))
+                    .unwrap_or(usize::MAX);
+            size = size
+                .zip(orbit_indexer.size())
+                .and_then(|(size, orbit_size)| size.checked_mul(orbit_size));
+            orbit_indexers.push(orbit_indexer);
+        }
+        let Some(size) = size.filter(|size| *size <= max_size) else {
+            return Err(SearchError {
+                description: format!(
+                    "An exact prune table would need {} entries, which exceeds the limit of {} entries.",
+                    size.map_or("too many".to_owned(), |size| size.separate_with_underscores()),
+                    max_size.separate_with_underscores()
+                ),
+            });
+        };
+
+        let indexer = Self {
+            kpuzzle: kpuzzle.clone(),
+            orbit_indexers,
+            size,
+        };
+        for target_pattern in &search_api_data.target_patterns {
+            if indexer.rank(target_pattern).is_none() {
+                return Err("The target patterns for an exact prune table must have the same pieces (and the same pieces in the slots that the generators do not change).".into());
+            }
+        }
+        Ok(indexer)
+    }
+
+    #[inline]
+    fn rank(&self, pattern: &KPattern) -> Option<usize> {
+        let mut index = 0;
+        for (orbit_info, orbit_indexer) in self.kpuzzle.orbit_info_iter().zip(&self.orbit_indexers)
+        {
+            let (arrangement_index, orientation_index) = orbit_indexer.rank(orbit_info, pattern)?;
+            index = (index * orbit_indexer.num_arrangements + arrangement_index)
+                * orbit_indexer.num_orientation_indices
+                + orientation_index;
+        }
+        Some(index)
+    }
+
+    // `pattern` must match the target patterns outside the moved slots.
+    fn unrank(&self, mut index: usize, pattern: &mut KPattern) {
+        for (orbit_info, orbit_indexer) in self
+            .kpuzzle
+            .orbit_info_iter()
+            .zip(&self.orbit_indexers)
+            .rev()
+        {
+            let orientation_index = index % orbit_indexer.num_orientation_indices;
+            index /= orbit_indexer.num_orientation_indices;
+            let arrangement_index = index % orbit_indexer.num_arrangements;
+            index /= orbit_indexer.num_arrangements;
+            orbit_indexer.unrank(orbit_info, arrangement_index, orientation_index, pattern);
+        }
+    }
+}
+
+/// A prune table that stores the exact distance to the target patterns for
+/// every pattern that can be reached from them, without any collisions. This
+/// is only feasible for small puzzles (e.g. 2x2x2, Pyraminx, Skewb).
+///
+/// Patterns are ranked to a dense index (see [`KPuzzlePerfectIndexer`]), and the
+/// table is filled completely the first time it is extended for a search
+/// (unless the search is stopped first). Once it is filled, lookups never
+/// underestimate the distance and the search never explores a pattern that
+/// cannot lead to a solution within the remaining depth. Until then, lookups
+/// return a lower bound.
+pub struct PerfectIndexPruneTable {
+    search_api_data: Arc<IDFSearchAPIData<KPuzzle>>,
+    search_logger: Arc<SearchLogger>,
+    indexer: KPuzzlePerfectIndexer,
+    // 0 is unreached, all other values are stored as 1+depth.
+    entries: Vec<AtomicU8>,
+    // All entries with a smaller value have been expanded, so every pattern
+    // whose distance is less than this value has its exact distance.
+    next_entry_value: u8,
+    // The largest entry value so far. With move costs, an entry can be set more
+    // than one depth ahead (and lowered later).
+    max_entry_value: u8,
+    num_filled_entries: usize,
+    // Set once all entries have been expanded, after which unreached patterns are unreachable.
+    is_filled: bool,
+}
+
+// The result of expanding some of the entries with the same value.
+#[derive(Default)]
+struct FillChunkResult {
+    num_expanded_entries: usize,
+    num_filled_entries: usize,
+    max_entry_value: u8,
+    // Set if an entry value did not fit in a byte.
+    overflowed: bool,
+}
+
+impl FillChunkResult {
+    fn add(&mut self, other: FillChunkResult) {
+        self.num_expanded_entries += other.num_expanded_entries;
+        self.num_filled_entries += other.num_filled_entries;
+        self.max_entry_value = u8::max(self.max_entry_value, other.max_entry_value);
+        self.overflowed |= other.overflowed;
+    }
+}
+
+impl PerfectIndexPruneTable {
+    // Expands every entry with `entry_value` in `indices`.
+    fn fill_chunk(&self, entry_value: u8, indices: Range<usize>) -> FillChunkResult {
+        let mut result = FillChunkResult::default();
+        // The targets were validated by the indexer, so there is at least one.
+        let reference_pattern = &self.search_api_data.target_patterns[0];
+        let mut pattern = reference_pattern.clone();
+        let mut next_pattern = reference_pattern.clone();
+        for index in indices {
+            if self.entries[index].load(Ordering::Relaxed) != entry_value {
+                continue;
+            }
+            result.num_expanded_entries += 1;
+            self.indexer.unrank(index, &mut pattern);
+            for (_, move_transformation_info) in self.search_api_data.search_generators.flat.iter()
+            {
+                pattern.apply_transformation_into(
+                    &move_transformation_info.transformation,
+                    &mut next_pattern,
+                );
+                let Some(next_index) = self.indexer.rank(&next_pattern) else {
+                    continue;
+                };
+                let Some(next_entry_value) = u8::try_from(move_transformation_info.cost.0)
+                    .ok()
+                    .and_then(|cost| entry_value.checked_add(cost))
+                else {
+                    result.overflowed = true;
+                    continue;
+                };
+                // Entries are only ever lowered (or filled), and never to
+                // `entry_value`, so other threads expanding the same value are unaffected.
+                let update = self.entries[next_index].fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    |existing_entry_value| {
+                        (existing_entry_value == UNREACHED_SENTINEL
+                            || existing_entry_value > next_entry_value)
+                            .then_some(next_entry_value)
+                    },
+                );
+                if let Ok(existing_entry_value) = update {
+                    if existing_entry_value == UNREACHED_SENTINEL {
+                        result.num_filled_entries += 1;
+                    }
+                    result.max_entry_value = u8::max(result.max_entry_value, next_entry_value);
+                }
+            }
+        }
+        result
+    }
+
+    // Expands every entry with `entry_value`, using a pool of threads. Returns
+    // `None` if this was stopped before it finished.
+    fn fill_entry_value(
+        &self,
+        entry_value: u8,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    ) -> Option<FillChunkResult> {
+        let num_chunks = self.entries.len().div_ceil(FILL_CHUNK_SIZE);
+        let next_chunk_index = AtomicUsize::new(0);
+        let fill_chunks = || {
+            let mut result = FillChunkResult::default();
+            loop {
+                let chunk_index = next_chunk_index.fetch_add(1, Ordering::Relaxed);
+                if chunk_index >= num_chunks {
+                    return Some(result);
+                }
+                if should_stop() {
+                    return None;
+                }
+                let start = chunk_index * FILL_CHUNK_SIZE;
+                let end = usize::min(start + FILL_CHUNK_SIZE, self.entries.len());
+                result.add(self.fill_chunk(entry_value, start..end));
+            }
+        };
+
+        let num_threads = usize::min(self.search_api_data.num_threads, num_chunks);
+        if num_threads <= 1 {
+            return fill_chunks();
+        }
+        std::thread::scope(|scope| {
+            let thread_handles: Vec<_> =
+                (0..num_threads).map(|_| scope.spawn(fill_chunks)).collect();
+            let mut result = Some(FillChunkResult::default());
+            for thread_handle in thread_handles {
+                let thread_result = thread_handle
+                    .join()
+                    .expect("Internal error: prune table thread panicked");
+                result = result
+                    .zip(thread_result)
+                    .map(|(mut result, thread_result)| {
+                        result.add(thread_result);
+                        result
+                    });
+            }
+            result
+        })
+    }
+
+    // Fills the table completely, unless `should_stop` returns `true` first.
+    fn fill(&mut self, should_stop: &(dyn Fn() -> bool + Sync)) {
+        while !self.is_filled {
+            let entry_value = self.next_entry_value;
+            let Some(result) = self.fill_entry_value(entry_value, should_stop) else {
+                // The entries that were set so far are kept, and the entry value is expanded again next time.
+                return;
+            };
+            self.num_filled_entries += result.num_filled_entries;
+            self.max_entry_value = u8::max(self.max_entry_value, result.max_entry_value);
+            self.search_logger.write_info(&format!(
+                "[Prune table] Depth {}: {} patterns",
+                entry_value - 1,
+                result.num_expanded_entries.separate_with_underscores()
+            ));
+            if result.overflowed {
+                // The table stays a lower bound, but cannot be completed.
+                self.search_logger.write_error(
+                    "[Prune table] The distances are too large for an exact prune table, so it will not be filled completely.",
+                );
+                self.next_entry_value = entry_value;
+                return;
+            }
+            if entry_value == self.max_entry_value || entry_value == u8::MAX {
+                self.is_filled = true;
+            } else {
+                self.next_entry_value = entry_value + 1;
+            }
+        }
+        self.search_logger.write_info(&format!(
+            "[Prune table] Reached {} of {} indexed patterns.",
+            self.num_filled_entries.separate_with_underscores(),
+            self.entries.len().separate_with_underscores()
+        ));
+    }
+}
+
+impl PruneTable<KPuzzle> for PerfectIndexPruneTable {
+    type TableOptions = ();
+
+    fn new(
+        tpuzzle: KPuzzle,
+        search_api_data: Arc<IDFSearchAPIData<KPuzzle>>,
+        search_logger: Arc<SearchLogger>,
+        options: PruneTableConstructionOptions<()>,
+    ) -> Result<Self, SearchError> {
+        let max_size = options
+            .max_memory_bytes
+            .unwrap_or(DEFAULT_MAX_PERFECT_INDEX_PRUNE_TABLE_SIZE);
+        let indexer = KPuzzlePerfectIndexer::try_new(&tpuzzle, &search_api_data, max_size)?;
+        search_logger.write_info(&format!(
+            "[Prune table] Building an exact prune table with {} entries.",
+            indexer.size.separate_with_underscores()
+        ));
+        let entries: Vec<AtomicU8> = (0..indexer.size)
+            .map(|_| AtomicU8::new(UNREACHED_SENTINEL))
+            .collect();
+        for target_pattern in &search_api_data.target_patterns {
+            // The targets were validated by the indexer.
+            let index = indexer.rank(target_pattern).unwrap();
+            entries[index].store(1, Ordering::Relaxed);
+        }
+        let num_filled_entries = search_api_data.target_patterns.len();
+        Ok(Self {
+            search_api_data,
+            search_logger,
+            indexer,
+            entries,
+            next_entry_value: 1,
+            max_entry_value: 1,
+            num_filled_entries,
+            is_filled: false,
+        })
+    }
+
+    fn lookup(&self, pattern: &KPattern) -> Depth {
+        let Some(index) = self.indexer.rank(pattern) else {
+            return UNREACHABLE_DEPTH;
+        };
+        let entry_value = self.entries[index].load(Ordering::Relaxed);
+        if self.is_filled {
+            return match entry_value {
+                UNREACHED_SENTINEL => UNREACHABLE_DEPTH,
+                entry_value => Depth(entry_value as usize - 1),
+            };
+        }
+        // Until the table is filled, only the entries below `next_entry_value` are exact.
+        if entry_value == UNREACHED_SENTINEL || entry_value > self.next_entry_value {
+            Depth(self.next_entry_value as usize)
+        } else {
+            Depth(entry_value as usize - 1)
+        }
+    }
+
+    // The table is filled completely the first time (regardless of the search depth).
+    fn extend_for_search_depth(
+        &mut self,
+        _search_depth: Depth,
+        _approximate_num_entries: usize,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    ) {
+        self.fill(should_stop);
+    }
+
+    fn statistics(&self) -> PruneTableStatistics {
+        PruneTableStatistics {
+            num_entries: self.entries.len(),
+            num_bytes: self.entries.len(),
+            pruning_depth: match self.is_filled {
+                true => Depth(self.max_entry_value as usize - 1),
+                false => Depth(self.next_entry_value as usize - 1),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cubing::{
+        alg::{parse_alg, parse_move},
+        kpuzzle::KPuzzle,
+        puzzles::cube2x2x2_kpuzzle,
+    };
+
+    use crate::_internal::search::{
+        idf_search::{
+            idf_search::{IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions},
+            search_adaptations::KPuzzlePerfectIndexPruneTableAdaptations,
+        },
+        prune_table_trait::{Depth, PruneTable},
+    };
+
+    use super::{
+        KPuzzlePerfectIndexer, PerfectIndexPruneTable, DEFAULT_MAX_PERFECT_INDEX_PRUNE_TABLE_SIZE,
+    };
+
+    #[test]
+    fn perfect_index_prune_table_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let mut idf_search =
+            <IDFSearch<KPuzzle, KPuzzlePerfectIndexPruneTableAdaptations>>::try_new(
+                kpuzzle.clone(),
+                vec![parse_move!("U"), parse_move!("R")],
+                kpuzzle.default_pattern(),
+                IDFSearchConstructionOptions {
+                    search_logger: Arc::new(Default::default()),
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| e.description)?;
+
+        // 6 corners move, and the last orientation is determined by the others.
+        let indexer = KPuzzlePerfectIndexer::try_new(
+            kpuzzle,
+            &idf_search.api_data,
+            DEFAULT_MAX_PERFECT_INDEX_PRUNE_TABLE_SIZE,
+        )
+        .map_err(|e| e.description)?;
+        assert_eq!(indexer.size, 720 * 243);
+        let mut pattern = kpuzzle.default_pattern();
+        for index in [0, 1, 1234, 174959] {
+            indexer.unrank(index, &mut pattern);
+            assert_eq!(indexer.rank(&pattern), Some(index));
+        }
+
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U2 R' U R2 U'"))
+            .unwrap();
+        let solution = idf_search
+            .search(&search_pattern, IndividualSearchOptions::default())
+            .next()
+            .unwrap();
+        assert_eq!(
+            search_pattern.apply_alg(&solution).unwrap(),
+            kpuzzle.default_pattern()
+        );
+        // The table has the exact distance.
+        let prune_table = idf_search.prune_table.lock().unwrap();
+        assert_eq!(
+            prune_table.lookup(&search_pattern),
+            Depth(solution.nodes.len())
+        );
+        // `F` cannot be undone using `U` and `R`.
+        let unreachable_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("F"))
+            .unwrap();
+        assert_eq!(prune_table.lookup(&unreachable_pattern), Depth(usize::MAX));
+        Ok(())
+    }
+
+    #[test]
+    fn perfect_index_prune_table_stopped_fill_test() -> Result<(), String> {
+        let kpuzzle = cube2x2x2_kpuzzle();
+        let idf_search = <IDFSearch<KPuzzle, KPuzzlePerfectIndexPruneTableAdaptations>>::try_new(
+            kpuzzle.clone(),
+            vec![parse_move!("U"), parse_move!("R")],
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(Default::default()),
+                ..Default::default()
+            },
+        )
+        .map_err(|e| e.description)?;
+        let mut prune_table = PerfectIndexPruneTable::new(
+            kpuzzle.clone(),
+            idf_search.api_data.clone(),
+            Arc::new(Default::default()),
+            Default::default(),
+        )
+        .map_err(|e| e.description)?;
+
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U2 R' U R2 U'"))
+            .unwrap();
+        // A stopped fill keeps the lookups admissible.
+        prune_table.extend_for_search_depth(Depth(0), 0, &|| true);
+        assert_eq!(prune_table.statistics().pruning_depth, Depth(0));
+        assert_eq!(prune_table.lookup(&kpuzzle.default_pattern()), Depth(0));
+        assert_eq!(prune_table.lookup(&search_pattern), Depth(1));
+
+        // The fill resumes where it stopped.
+        prune_table.extend_for_search_depth(Depth(0), 0, &|| false);
+        assert_eq!(prune_table.lookup(&search_pattern), Depth(6));
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use super::{hash_prune_table::DepthU8, prune_table_persistence::MappedPruneTable};
+
+/// How prune table entries are laid out in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PruneTableEntryPacking {
+    /// One entry per byte.
+    Byte,
+    /// Two entries per byte (4 bits each).
+    Nibble,
+}
+
+impl PruneTableEntryPacking {
+    pub fn entries_per_byte(self) -> usize {
+        match self {
+            PruneTableEntryPacking::Byte => 1,
+            PruneTableEntryPacking::Nibble => 2,
+        }
+    }
+
+    /// The largest value that fits in an entry.
+    pub fn max_entry_value(self) -> DepthU8 {
+        match self {
+            PruneTableEntryPacking::Byte => DepthU8(u8::MAX),
+            PruneTableEntryPacking::Nibble => DepthU8(0x0F),
+        }
+    }
+
+    fn num_bytes(self, num_entries: usize) -> usize {
+        num_entries.div_ceil(self.entries_per_byte())
+    }
+}
+
+fn into_atomic_bytes(bytes: Vec<u8>) -> Vec<AtomicU8> {
+    let mut bytes = ManuallyDrop::new(bytes);
+    // SAFETY: `AtomicU8` has the same size, alignment, and bit validity as `u8`.
+    unsafe {
+        Vec::from_raw_parts(
+            bytes.as_mut_ptr() as *mut AtomicU8,
+            bytes.len(),
+            bytes.capacity(),
+        )
+    }
+}
+
+enum PruneTableEntryBytes {
+    Owned(Vec<AtomicU8>),
+    // Read-only.
+    Mapped(MappedPruneTable),
+}
+
+/// A fixed-size array of prune table entries, packed according to a [`PruneTableEntryPacking`].
+///
+/// Entries can be updated through a shared reference (using atomic byte
+/// operations), so that several threads can fill the same table. Entries
+/// that are mapped from a file are read-only until [`PruneTableEntries::make_writable`] is called.
+pub(crate) struct PruneTableEntries {
+    packing: PruneTableEntryPacking,
+    bytes: PruneTableEntryBytes,
+}
+
+impl PruneTableEntries {
+    /// All entries start as 0.
+    pub fn new(packing: PruneTableEntryPacking, num_entries: usize) -> Self {
+        Self {
+            packing,
+            // This allows the memory to start as zeroed pages from the OS instead of being written up front.
+            bytes: PruneTableEntryBytes::Owned(into_atomic_bytes(vec![
+                0;
+                packing
+                    .num_bytes(num_entries)
+            ])),
+        }
+    }
+
+    /// Returns `None` if `bytes` has the wrong length for `num_entries`.
+    pub fn from_bytes(
+        packing: PruneTableEntryPacking,
+        num_entries: usize,
+        bytes: Vec<u8>,
+    ) -> Option<Self> {
+        if bytes.len() != packing.num_bytes(num_entries) {
+            return None;
+        }
+        Some(Self {
+            packing,
+            bytes: PruneTableEntryBytes::Owned(into_atomic_bytes(bytes)),
+        })
+    }
+
+    /// Returns `None` if the mapped table has the wrong length for `num_entries`.
+    pub fn from_mapped(
+        packing: PruneTableEntryPacking,
+        num_entries: usize,
+        mapped: MappedPruneTable,
+    ) -> Option<Self> {
+        if mapped.entry_bytes().len() != packing.num_bytes(num_entries) {
+            return None;
+        }
+        Some(Self {
+            packing,
+            bytes: PruneTableEntryBytes::Mapped(mapped),
+        })
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.bytes, PruneTableEntryBytes::Mapped(_))
+    }
+
+    /// Copies read-only entries into memory, so that they can be updated.
+    pub fn make_writable(&mut self) {
+        if let PruneTableEntryBytes::Mapped(mapped) = &self.bytes {
+            self.bytes =
+                PruneTableEntryBytes::Owned(into_atomic_bytes(mapped.entry_bytes().to_vec()));
+        }
+    }
+
+    pub fn packing(&self) -> PruneTableEntryPacking {
+        self.packing
+    }
+
+    /// This takes `&mut self` so that no entries can change while the bytes are borrowed.
+    pub fn as_bytes(&mut self) -> &[u8] {
+        match &self.bytes {
+            // SAFETY: `AtomicU8` has the same size, alignment, and bit validity as
+            // `u8`, and we have exclusive access.
+            PruneTableEntryBytes::Owned(bytes) => unsafe {
+                std::slice::from_raw_parts(bytes.as_ptr() as *const u8, bytes.len())
+            },
+            PruneTableEntryBytes::Mapped(mapped) => mapped.entry_bytes(),
+        }
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        match &self.bytes {
+            PruneTableEntryBytes::Owned(bytes) => bytes.len(),
+            PruneTableEntryBytes::Mapped(mapped) => mapped.entry_bytes().len(),
+        }
+    }
+
+    #[inline]
+    fn byte(&self, byte_index: usize) -> u8 {
+        match &self.bytes {
+            PruneTableEntryBytes::Owned(bytes) => bytes[byte_index].load(Ordering::Relaxed),
+            PruneTableEntryBytes::Mapped(mapped) => mapped.entry_bytes()[byte_index],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> DepthU8 {
+        match self.packing {
+            PruneTableEntryPacking::Byte => DepthU8(self.byte(index)),
+            PruneTableEntryPacking::Nibble => {
+                DepthU8((self.byte(index >> 1) >> ((index & 1) << 2)) & 0x0F)
+            }
+        }
+    }
+
+    /// Atomically sets the entry to `value` if `should_replace` returns `true`
+    /// for its current value.
+    #[inline]
+    pub fn set_if(&self, index: usize, value: DepthU8, should_replace: impl Fn(DepthU8) -> bool) {
+        debug_assert!(value <= self.packing.max_entry_value());
+        let PruneTableEntryBytes::Owned(bytes) = &self.bytes else {
+            panic!("Internal error: tried to modify a read-only prune table");
+        };
+        // `fetch_update` returns `Err` when we decide not to replace the value, which is fine.
+        let _ = match self.packing {
+            PruneTableEntryPacking::Byte => {
+                bytes[index].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |byte| {
+                    should_replace(DepthU8(byte)).then_some(value.0)
+                })
+            }
+            PruneTableEntryPacking::Nibble => {
+                let shift = (index & 1) << 2;
+                bytes[index >> 1].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |byte| {
+                    should_replace(DepthU8((byte >> shift) & 0x0F))
+                        .then_some((byte & !(0x0F << shift)) | (value.0 << shift))
+                })
+            }
+        };
+    }
+
+    /// Counts the non-zero entries.
+    pub fn num_nonzero_entries(&self) -> usize {
+        let bytes = (0..self.num_bytes()).map(|byte_index| self.byte(byte_index));
+        match self.packing {
+            PruneTableEntryPacking::Byte => bytes.filter(|byte| *byte != 0).count(),
+            PruneTableEntryPacking::Nibble => bytes
+                .map(|byte| (byte & 0x0F != 0) as usize + (byte >> 4 != 0) as usize)
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal::search::hash_prune_table::DepthU8;
+
+    use super::{PruneTableEntries, PruneTableEntryPacking};
+
+    #[test]
+    fn prune_table_entries_nibble_test() {
+        let mut entries = PruneTableEntries::new(PruneTableEntryPacking::Nibble, 8);
+        assert_eq!(entries.as_bytes().len(), 4);
+        entries.set_if(2, DepthU8(3), |_| true);
+        entries.set_if(3, DepthU8(15), |_| true);
+        entries.set_if(5, DepthU8(1), |_| true);
+        entries.set_if(3, DepthU8(7), |value| value == DepthU8(15));
+        entries.set_if(5, DepthU8(2), |value| value == DepthU8(0));
+        let values: Vec<u8> = (0..8).map(|i| entries.get(i).0).collect();
+        assert_eq!(values, vec![0, 0, 3, 7, 0, 1, 0, 0]);
+        assert_eq!(entries.num_nonzero_entries(), 3);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    cmp::Ordering,
+    env,
+    fs::{create_dir_all, rename, File},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use memmap2::Mmap;
+
+use crate::_internal::{
+    cli::args::{EnableAutoAlwaysNeverValueEnum, MetricEnum},
+    errors::SearchError,
+};
+
+use super::hash_prune_table::DepthU8;
+
+const PRUNE_TABLE_FILE_MAGIC: &[u8; 8] = b"TWSPRUNE";
+// Increment this whenever the file format or table semantics change, so that old files are rejected.
+const PRUNE_TABLE_FILE_FORMAT_VERSION: u32 = 2;
+const PRUNE_TABLE_FILE_CHUNK_SIZE: usize = 1 << 20;
+// Magic, version, key, table size, pruning depth, entries checksum, and header checksum.
+const PRUNE_TABLE_FILE_HEADER_SIZE: usize = 8 + 4 + 8 + 8 + 1 + 8 + 8;
+// The header checksum covers everything before it.
+const PRUNE_TABLE_FILE_HEADER_CHECKSUM_OFFSET: usize = PRUNE_TABLE_FILE_HEADER_SIZE - 8;
+
+/// In `auto` mode, tables are only written if they took at least this long to generate.
+const AUTO_WRITE_MIN_GENERATION_DURATION: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct PruneTablePersistenceOptions {
+    pub cache_dir: PathBuf,
+    pub write_prune_tables: EnableAutoAlwaysNeverValueEnum,
+    /// Map prune table files into memory (read-only) instead of reading them.
+    pub mmap_prune_tables: bool,
+}
+
+impl PruneTablePersistenceOptions {
+    pub(crate) fn should_write(&self, generation_duration: Duration) -> bool {
+        match self.write_prune_tables {
+            EnableAutoAlwaysNeverValueEnum::Auto => {
+                generation_duration >= AUTO_WRITE_MIN_GENERATION_DURATION
+            }
+            EnableAutoAlwaysNeverValueEnum::Never => false,
+            EnableAutoAlwaysNeverValueEnum::Always => true,
+        }
+    }
+}
+
+/// Follows the XDG convention (`$XDG_CACHE_HOME/twsearch`, falling back to `~/.cache/twsearch`).
+pub fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(xdg_cache_home) = env::var_os("XDG_CACHE_HOME").filter(|s| !s.is_empty()) {
+        return Some(PathBuf::from(xdg_cache_home).join("twsearch"));
+    }
+    env::var_os("HOME")
+        .filter(|s| !s.is_empty())
+        .map(|home| PathBuf::from(home).join(".cache").join("twsearch"))
+}
+
+/// Everything that determines the contents of a prune table.
+pub(crate) struct PruneTableCacheKeyData<'a> {
+    pub puzzle_definition_hash: u64,
+    pub generator_moves: Vec<String>,
+    pub metric: &'a MetricEnum,
+    pub symmetry_moves: Vec<String>,
+    pub target_pattern_hashes: Vec<u64>,
+    pub prune_table_size: usize,
+    pub entries_per_byte: usize,
+    pub pattern_validity_checker_name: &'a str,
+}
+
+impl PruneTableCacheKeyData<'_> {
+    pub fn key(mut self) -> u64 {
+        // The generator order can vary (e.g. with `--random-start`), but does not affect the table.
+        // The same goes for the order of the target patterns.
+        self.generator_moves.sort();
+        self.target_pattern_hashes.sort();
+        self.symmetry_moves.sort();
+        let mut key_string = format!(
+            "definition:{:016x}\ngenerators:{}\nmetric:{}\ntarget:{}\nsize:{}\nvalidity:{}",
+            self.puzzle_definition_hash,
+            self.generator_moves.join(","),
+            self.metric,
+            self.target_pattern_hashes
+                .iter()
+                .map(|target_pattern_hash| format!("{:016x}", target_pattern_hash))
+                .collect::<Vec<String>>()
+                .join(","),
+            self.prune_table_size,
+            self.pattern_validity_checker_name,
+        );
+        // Only included when set, so that the keys of tables without symmetries are unchanged.
+        if !self.symmetry_moves.is_empty() {
+            key_string += &format!("\nsymmetries:{}", self.symmetry_moves.join(","));
+        }
+        // Likewise for tables that use one byte per entry.
+        if self.entries_per_byte != 1 {
+            key_string += &format!("\nentries-per-byte:{}", self.entries_per_byte);
+        }
+        cityhasher::hash(key_string)
+    }
+}
+
+pub(crate) fn prune_table_cache_file_path(cache_dir: &Path, key: u64) -> PathBuf {
+    cache_dir.join(format!("prune-table-{:016x}.bin", key))
+}
+
+pub(crate) struct PersistedPruneTable {
+    pub pruning_depth: DepthU8,
+    /// The packed entries (see `PruneTableEntries`).
+    pub entry_bytes: Vec<u8>,
+}
+
+fn corrupt(path: &Path, reason: &str) -> SearchError {
+    SearchError {
+        description: format!(
+            "Prune table file is stale or corrupt ({}): {}",
+            reason,
+            path.display()
+        ),
+    }
+}
+
+fn read_exact_or_corrupt(
+    reader: &mut impl Read,
+    buf: &mut [u8],
+    path: &Path,
+) -> Result<(), SearchError> {
+    reader
+        .read_exact(buf)
+        .map_err(|_| corrupt(path, "file is truncated"))
+}
+
+fn read_u64(reader: &mut impl Read, path: &Path) -> Result<u64, SearchError> {
+    let mut buf = [0; 8];
+    read_exact_or_corrupt(reader, &mut buf, path)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+// Equivalent to hashing the entries chunk by chunk while reading them (see `read_prune_table`).
+fn entry_bytes_checksum(entry_bytes: &[u8]) -> u64 {
+    let mut checksum: u64 = 0;
+    for chunk in entry_bytes.chunks(PRUNE_TABLE_FILE_CHUNK_SIZE) {
+        checksum = cityhasher::hash_with_seed(chunk, checksum);
+    }
+    checksum
+}
+
+// Returns `Ok(None)` if the file does not exist.
+fn open_prune_table_file(path: &Path) -> Result<Option<File>, SearchError> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(SearchError {
+            description: format!("Could not open prune table file {}: {}", path.display(), e),
+        }),
+    }
+}
+
+// Reads everything before the entries, and returns the pruning depth and the entries checksum.
+fn read_header(
+    reader: &mut impl Read,
+    path: &Path,
+    key: u64,
+    num_entry_bytes: usize,
+) -> Result<(DepthU8, u64), SearchError> {
+    let mut header = [0; PRUNE_TABLE_FILE_HEADER_SIZE];
+    read_exact_or_corrupt(reader, &mut header, path)?;
+    let header_reader = &mut &header[..];
+
+    let mut magic = [0; 8];
+    read_exact_or_corrupt(header_reader, &mut magic, path)?;
+    if &magic != PRUNE_TABLE_FILE_MAGIC {
+        return Err(corrupt(path, "not a prune table file"));
+    }
+    let mut version = [0; 4];
+    read_exact_or_corrupt(header_reader, &mut version, path)?;
+    let version = u32::from_le_bytes(version);
+    if version != PRUNE_TABLE_FILE_FORMAT_VERSION {
+        return Err(corrupt(
+            path,
+            &format!(
+                "file format version {} does not match the expected version {}",
+                version, PRUNE_TABLE_FILE_FORMAT_VERSION
+            ),
+        ));
+    }
+    let expected_header_checksum = u64::from_le_bytes(
+        header[PRUNE_TABLE_FILE_HEADER_CHECKSUM_OFFSET..]
+            .try_into()
+            .unwrap(),
+    );
+    if cityhasher::hash::<u64>(&header[..PRUNE_TABLE_FILE_HEADER_CHECKSUM_OFFSET])
+        != expected_header_checksum
+    {
+        return Err(corrupt(path, "header checksum does not match"));
+    }
+    if read_u64(header_reader, path)? != key {
+        return Err(corrupt(
+            path,
+            "file was generated for a different puzzle, generators, metric, or target pattern",
+        ));
+    }
+    if read_u64(header_reader, path)? != num_entry_bytes as u64 {
+        return Err(corrupt(path, "table size does not match"));
+    }
+    let mut pruning_depth = [0; 1];
+    read_exact_or_corrupt(header_reader, &mut pruning_depth, path)?;
+    let checksum = read_u64(header_reader, path)?;
+    Ok((DepthU8(pruning_depth[0]), checksum))
+}
+
+/// Returns `Ok(None)` if there is no file for this key.
+pub(crate) fn read_prune_table(
+    path: &Path,
+    key: u64,
+    num_entry_bytes: usize,
+) -> Result<Option<PersistedPruneTable>, SearchError> {
+    let Some(file) = open_prune_table_file(path)? else {
+        return Ok(None);
+    };
+    let mut reader = BufReader::new(file);
+    let (pruning_depth, expected_checksum) = read_header(&mut reader, path, key, num_entry_bytes)?;
+
+    let mut entry_bytes = vec![0; num_entry_bytes];
+    let mut checksum: u64 = 0;
+    for chunk in entry_bytes.chunks_mut(PRUNE_TABLE_FILE_CHUNK_SIZE) {
+        read_exact_or_corrupt(&mut reader, chunk, path)?;
+        checksum = cityhasher::hash_with_seed(&chunk, checksum);
+    }
+    if reader
+        .read(&mut [0; 1])
+        .map_err(|_| corrupt(path, "read error"))?
+        != 0
+    {
+        return Err(corrupt(path, "file has trailing data"));
+    }
+    if checksum != expected_checksum {
+        return Err(corrupt(path, "checksum does not match"));
+    }
+
+    Ok(Some(PersistedPruneTable {
+        pruning_depth,
+        entry_bytes,
+    }))
+}
+
+/// A prune table file that is mapped into memory (read-only), so that its
+/// pages can be shared by all the processes that use it.
+pub(crate) struct MappedPruneTable {
+    pub pruning_depth: DepthU8,
+    mmap: Mmap,
+}
+
+impl MappedPruneTable {
+    /// The packed entries (see `PruneTableEntries`).
+    pub fn entry_bytes(&self) -> &[u8] {
+        &self.mmap[PRUNE_TABLE_FILE_HEADER_SIZE..]
+    }
+}
+
+/// Like [`read_prune_table`], but maps the file into memory instead of reading
+/// it. Only the header and the file size are verified: checksumming the entries
+/// would read every page up front, which defeats loading them lazily. Files are
+/// only ever written whole (see [`write_prune_table`]), and the entries checksum
+/// is still verified whenever the file is read instead of mapped.
+pub(crate) fn map_prune_table(
+    path: &Path,
+    key: u64,
+    num_entry_bytes: usize,
+) -> Result<Option<MappedPruneTable>, SearchError> {
+    let Some(file) = open_prune_table_file(path)? else {
+        return Ok(None);
+    };
+    // SAFETY: Prune table files are never modified in place (see `write_prune_table`).
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| SearchError {
+        description: format!("Could not map prune table file {}: {}", path.display(), e),
+    })?;
+    let (pruning_depth, _) = read_header(&mut &mmap[..], path, key, num_entry_bytes)?;
+    match mmap
+        .len()
+        .cmp(&(PRUNE_TABLE_FILE_HEADER_SIZE + num_entry_bytes))
+    {
+        Ordering::Less => return Err(corrupt(path, "file is truncated")),
+        Ordering::Greater => return Err(corrupt(path, "file has trailing data")),
+        Ordering::Equal => {}
+    }
+    Ok(Some(MappedPruneTable {
+        pruning_depth,
+        mmap,
+    }))
+}
+
+pub(crate) fn write_prune_table(
+    path: &Path,
+    key: u64,
+    pruning_depth: DepthU8,
+    entry_bytes: &[u8],
+) -> Result<(), SearchError> {
+    let io_error = |e: std::io::Error| SearchError {
+        description: format!("Could not write prune table file {}: {}", path.display(), e),
+    };
+
+    let checksum = entry_bytes_checksum(entry_bytes);
+
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(io_error)?;
+    }
+    // Write to a temporary file first, so that concurrent runs never see a partially written table.
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let mut header = Vec::with_capacity(PRUNE_TABLE_FILE_HEADER_SIZE);
+    header.extend_from_slice(PRUNE_TABLE_FILE_MAGIC);
+    header.extend_from_slice(&PRUNE_TABLE_FILE_FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&key.to_le_bytes());
+    header.extend_from_slice(&(entry_bytes.len() as u64).to_le_bytes());
+    header.push(pruning_depth.0);
+    header.extend_from_slice(&checksum.to_le_bytes());
+    let header_checksum: u64 = cityhasher::hash(&header);
+    header.extend_from_slice(&header_checksum.to_le_bytes());
+
+    let mut writer = BufWriter::new(File::create(&temp_path).map_err(io_error)?);
+    writer.write_all(&header).map_err(io_error)?;
+    for chunk in entry_bytes.chunks(PRUNE_TABLE_FILE_CHUNK_SIZE) {
+        writer.write_all(chunk).map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)?;
+    drop(writer);
+    rename(&temp_path, path).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::_internal::search::hash_prune_table::DepthU8;
+
+    use super::{
+        map_prune_table, read_prune_table, write_prune_table,
+        PRUNE_TABLE_FILE_HEADER_CHECKSUM_OFFSET,
+    };
+
+    #[test]
+    fn prune_table_persistence_round_trip_test() {
+        let dir = std::env::temp_dir().join(format!(
+            "twsearch-prune-table-persistence-test-{}",
+            std::process::id()
+        ));
+        let path = dir.join("prune-table.bin");
+        let entries: Vec<u8> = (0..3000u32).map(|i| (i % 7) as u8).collect();
+        write_prune_table(&path, 42, DepthU8(5), &entries).unwrap();
+
+        let persisted = read_prune_table(&path, 42, entries.len()).unwrap().unwrap();
+        assert_eq!(persisted.pruning_depth, DepthU8(5));
+        assert_eq!(persisted.entry_bytes, entries);
+
+        let mapped = map_prune_table(&path, 42, entries.len()).unwrap().unwrap();
+        assert_eq!(mapped.pruning_depth, DepthU8(5));
+        assert_eq!(mapped.entry_bytes(), entries);
+        assert!(map_prune_table(&path, 42, entries.len() * 2).is_err());
+
+        // Different key, different size.
+        assert!(read_prune_table(&path, 43, entries.len()).is_err());
+        assert!(read_prune_table(&path, 42, entries.len() * 2).is_err());
+
+        // Corrupt entry (only detected when the entries are read).
+        let mut bytes = fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        assert!(read_prune_table(&path, 42, entries.len()).is_err());
+        assert!(map_prune_table(&path, 42, entries.len()).is_ok());
+
+        // Corrupt pruning depth.
+        bytes[PRUNE_TABLE_FILE_HEADER_CHECKSUM_OFFSET - 9] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        assert!(read_prune_table(&path, 42, entries.len()).is_err());
+        assert!(map_prune_table(&path, 42, entries.len()).is_err());
+
+        // Missing file.
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(read_prune_table(&path, 42, entries.len())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,22 +1,56 @@
 use std::sync::Arc;
 
-use crate::{_internal::puzzle_traits::puzzle_traits::SemiGroupActionPuzzle, whole_number_newtype};
+use crate::{
+    _internal::{errors::SearchError, puzzle_traits::puzzle_traits::SemiGroupActionPuzzle},
+    whole_number_newtype,
+};
 
-use super::{idf_search::idf_search::IDFSearchAPIData, search_logger::SearchLogger};
+use super::{
+    idf_search::idf_search::IDFSearchAPIData,
+    prune_table_persistence::PruneTablePersistenceOptions, search_logger::SearchLogger,
+    search_statistics::PruneTableStatistics,
+};
 
 whole_number_newtype!(Depth, usize);
 
-pub trait PruneTable<TPuzzle: SemiGroupActionPuzzle> {
+#[derive(Clone, Debug, Default)]
+pub struct PruneTableConstructionOptions<TTableOptions> {
+    pub min_size: Option<usize>,
+    /// Upper bound on prune table memory usage. Takes precedence over `min_size`.
+    pub max_memory_bytes: Option<usize>,
+    /// Only used by prune tables that support persistence (currently: `HashPruneTable`).
+    pub persistence: Option<PruneTablePersistenceOptions>,
+    /// Options that are specific to the type of prune table (see [`PruneTable::TableOptions`]).
+    pub table_options: TTableOptions,
+}
+
+pub trait PruneTable<TPuzzle: SemiGroupActionPuzzle>: Send + Sync {
+    /// Options that only apply to this type of prune table (e.g. the masks of
+    /// a [`MaskedMaxPruneTable`](super::masked_max_prune_table::MaskedMaxPruneTable)).
+    type TableOptions: Default;
+
     // TODO: design a proper API. The args here are currently inherited from `HashPruneTable`
     fn new(
         tpuzzle: TPuzzle,
         search_api_data: Arc<IDFSearchAPIData<TPuzzle>>,
         search_logger: Arc<SearchLogger>,
-        min_size: Option<usize>,
-    ) -> Self;
+        options: PruneTableConstructionOptions<Self::TableOptions>,
+    ) -> Result<Self, SearchError>
+    where
+        Self: Sized;
 
     fn lookup(&self, pattern: &TPuzzle::Pattern) -> Depth;
 
     // TODO
-    fn extend_for_search_depth(&mut self, search_depth: Depth, approximate_num_entries: usize);
+    /// Filling stops early once `should_stop` returns `true` (e.g. when the
+    /// search is cancelled or times out). The table then stays at the last
+    /// depth that was filled completely.
+    fn extend_for_search_depth(
+        &mut self,
+        search_depth: Depth,
+        approximate_num_entries: usize,
+        should_stop: &(dyn Fn() -> bool + Sync),
+    );
+
+    fn statistics(&self) -> PruneTableStatistics;
 }
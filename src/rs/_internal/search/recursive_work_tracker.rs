@@ -1,12 +1,12 @@
 use std::{sync::Arc, time::Duration};
 
-use thousands::Separable;
-
-use super::{prune_table_trait::Depth, search_logger::SearchLogger};
+use super::{
+    prune_table_trait::Depth,
+    search_logger::{SearchLogEvent, SearchLogger},
+};
 
 pub(crate) struct RecursiveWorkTracker {
     work_name: String,
-    // TODO: support custom writes intead of sending to stdout/stderr
     latest_depth: Depth,
     latest_depth_num_recursive_calls: usize,
     latest_depth_start_time: instant::Instant,
@@ -37,7 +37,7 @@ impl RecursiveWorkTracker {
             .write_info(&format!("[{}] {}", self.work_name, message));
     }
 
-    // Pass `None` as the message to avoid printing anything.
+    // Pass `None` as the message to avoid printing anything (the start is still logged as an event).
     pub fn start_depth(&mut self, depth: Depth, message: Option<&str>) {
         self.latest_depth_start_time = instant::Instant::now();
 
@@ -48,12 +48,11 @@ impl RecursiveWorkTracker {
         self.previous_depth_num_recursive_calls = self.latest_depth_num_recursive_calls;
         self.latest_depth_num_recursive_calls = 0;
 
-        if let Some(message) = message {
-            self.search_logger.write_info(&format!(
-                "[{}][Depth {:?}] {}",
-                self.work_name, self.latest_depth, message,
-            ));
-        }
+        self.search_logger.log(SearchLogEvent::DepthStarted {
+            work_name: &self.work_name,
+            depth,
+            message,
+        });
     }
 
     pub fn finish_latest_depth(&mut self) {
@@ -64,22 +63,22 @@ impl RecursiveWorkTracker {
             ));
         }
         self.latest_depth_duration = instant::Instant::now() - self.latest_depth_start_time;
-        let rate = (self.latest_depth_num_recursive_calls as f64
-            / (self.latest_depth_duration).as_secs_f64()) as usize;
-        self.search_logger.write_info(&format!(
-            "[{}][Depth {:?}] {} recursive calls ({:?}) ({} calls/s)",
-            self.work_name,
-            self.latest_depth,
-            self.latest_depth_num_recursive_calls
-                .separate_with_underscores(),
-            self.latest_depth_duration,
-            rate.separate_with_underscores()
-        ));
+        self.search_logger.log(SearchLogEvent::DepthFinished {
+            work_name: &self.work_name,
+            depth: self.latest_depth,
+            num_recursive_calls: self.latest_depth_num_recursive_calls,
+            duration: self.latest_depth_duration,
+        });
         self.latest_depth_finished = true;
     }
 
-    pub fn record_recursive_call(&mut self) {
-        self.latest_depth_num_recursive_calls += 1;
+    // For work that is counted separately (e.g. by multiple threads) and reported afterwards.
+    pub fn record_recursive_calls(&mut self, num_recursive_calls: usize) {
+        self.latest_depth_num_recursive_calls += num_recursive_calls;
+    }
+
+    pub fn latest_depth_duration(&self) -> Duration {
+        self.latest_depth_duration
     }
 
     pub fn estimate_next_level_num_recursive_calls(&self) -> usize {
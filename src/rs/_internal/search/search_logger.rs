@@ -1,44 +1,244 @@
+use std::{sync::Arc, time::Duration};
+
+use cubing::alg::Alg;
+use thousands::Separable;
+
 use crate::_internal::cli::args::VerbosityLevel;
 
+use super::{prune_table_entries::PruneTableEntryPacking, prune_table_trait::Depth};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchLogLevel {
+    Error,
+    Warning,
+    Info,
+}
+
+impl SearchLogLevel {
+    fn is_enabled(self, verbosity: VerbosityLevel) -> bool {
+        match verbosity {
+            VerbosityLevel::Silent => false,
+            VerbosityLevel::Error => self == SearchLogLevel::Error,
+            VerbosityLevel::Warning => self != SearchLogLevel::Info,
+            VerbosityLevel::Info => true,
+        }
+    }
+}
+
+/// Something that happened during a search (or while building its prune table).
+#[derive(Clone, Debug)]
+pub enum SearchLogEvent<'a> {
+    Message {
+        level: SearchLogLevel,
+        message: &'a str,
+    },
+    /// `work_name` says what is being searched, e.g. `"Search"` or `"Prune table"`.
+    DepthStarted {
+        work_name: &'a str,
+        depth: Depth,
+        message: Option<&'a str>,
+    },
+    DepthFinished {
+        work_name: &'a str,
+        depth: Depth,
+        num_recursive_calls: usize,
+        duration: Duration,
+    },
+    /// The prune table was reallocated (which discards its entries) so that it can be filled to `pruning_depth`.
+    PruneTableResized {
+        num_entries: usize,
+        entry_packing: PruneTableEntryPacking,
+        pruning_depth: Depth,
+    },
+    SolutionFound {
+        solution: &'a Alg,
+        /// Starts at 1.
+        solution_index: usize,
+    },
+}
+
+impl SearchLogEvent<'_> {
+    pub fn level(&self) -> SearchLogLevel {
+        match self {
+            SearchLogEvent::Message { level, .. } => *level,
+            _ => SearchLogLevel::Info,
+        }
+    }
+
+    /// The line that is printed for this event when a [`SearchLogger`] has no sink.
+    /// Returns `None` for events that are not printed.
+    pub fn message(&self) -> Option<String> {
+        match self {
+            SearchLogEvent::Message { message, .. } => Some(message.to_string()),
+            SearchLogEvent::DepthStarted {
+                work_name,
+                depth,
+                message,
+            } => message.map(|message| format!("[{}][Depth {:?}] {}", work_name, depth, message)),
+            SearchLogEvent::DepthFinished {
+                work_name,
+                depth,
+                num_recursive_calls,
+                duration,
+            } => {
+                let rate = (*num_recursive_calls as f64 / duration.as_secs_f64()) as usize;
+                Some(format!(
+                    "[{}][Depth {:?}] {} recursive calls ({:?}) ({} calls/s)",
+                    work_name,
+                    depth,
+                    num_recursive_calls.separate_with_underscores(),
+                    duration,
+                    rate.separate_with_underscores()
+                ))
+            }
+            SearchLogEvent::PruneTableResized {
+                num_entries,
+                entry_packing,
+                pruning_depth,
+            } => Some(format!(
+                "[Prune table] Resizing to {} entries ({} per byte) for depth {}…",
+                num_entries.separate_with_underscores(),
+                entry_packing.entries_per_byte(),
+                pruning_depth.0
+            )),
+            // Solutions are returned to the caller, so there is no need to print them.
+            SearchLogEvent::SolutionFound { .. } => None,
+        }
+    }
+}
+
+/// Receives the events of a [`SearchLogger`], e.g. to show progress in a UI.
+/// Sinks can be called from any search thread.
+pub trait SearchLogSink: Send + Sync {
+    fn log(&self, event: &SearchLogEvent);
+}
+
 // TODO: replace this with something less custom (ideally from the stdlib?)
 #[derive(Clone, Default)]
 pub struct SearchLogger {
-    // TODO: writers for logs and error
     pub verbosity: VerbosityLevel,
+    /// Receives all events that are enabled by `verbosity`. If this is `None`,
+    /// events are printed to stderr instead.
+    pub sink: Option<Arc<dyn SearchLogSink>>,
 }
 
 impl SearchLogger {
+    pub fn log(&self, event: SearchLogEvent) {
+        if !event.level().is_enabled(self.verbosity) {
+            return;
+        }
+        match &self.sink {
+            Some(sink) => sink.log(&event),
+            None => {
+                if let Some(message) = event.message() {
+                    eprintln!("{}", message);
+                }
+            }
+        }
+    }
+
     // TODO: support using the `write!` macro to avoid unnecessary string formatting in the caller when nothing is actually logged.
     pub fn write_info(&self, s: &str) {
-        if match self.verbosity {
-            VerbosityLevel::Silent => false,
-            VerbosityLevel::Error => false,
-            VerbosityLevel::Warning => false,
-            VerbosityLevel::Info => true,
-        } {
-            eprintln!("{}", s)
-        }
+        self.log(SearchLogEvent::Message {
+            level: SearchLogLevel::Info,
+            message: s,
+        });
     }
 
     pub fn write_warning(&self, s: &str) {
-        if match self.verbosity {
-            VerbosityLevel::Silent => false,
-            VerbosityLevel::Error => false,
-            VerbosityLevel::Warning => true,
-            VerbosityLevel::Info => true,
-        } {
-            eprintln!("{}", s);
-        }
+        self.log(SearchLogEvent::Message {
+            level: SearchLogLevel::Warning,
+            message: s,
+        });
     }
 
     pub fn write_error(&self, s: &str) {
-        if match self.verbosity {
-            VerbosityLevel::Silent => false,
-            VerbosityLevel::Error => true,
-            VerbosityLevel::Warning => true,
-            VerbosityLevel::Info => true,
-        } {
-            eprintln!("{}", s);
+        self.log(SearchLogEvent::Message {
+            level: SearchLogLevel::Error,
+            message: s,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use cubing::{alg::parse_alg, kpuzzle::KPuzzle, puzzles::cube3x3x3_kpuzzle};
+
+    use crate::_internal::{
+        cli::args::VerbosityLevel,
+        search::idf_search::idf_search::{IDFSearch, IDFSearchConstructionOptions},
+    };
+
+    use super::{SearchLogEvent, SearchLogSink, SearchLogger};
+
+    #[derive(Default)]
+    struct CollectingSink {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl SearchLogSink for CollectingSink {
+        fn log(&self, event: &SearchLogEvent) {
+            let summary = match event {
+                SearchLogEvent::Message { .. } => return,
+                SearchLogEvent::DepthStarted {
+                    work_name, depth, ..
+                } => format!("start {} {}", work_name, depth.0),
+                SearchLogEvent::DepthFinished {
+                    work_name, depth, ..
+                } => format!("finish {} {}", work_name, depth.0),
+                SearchLogEvent::PruneTableResized { .. } => "resize".to_owned(),
+                SearchLogEvent::SolutionFound {
+                    solution,
+                    solution_index,
+                } => format!("solution #{}: {}", solution_index, solution),
+            };
+            self.events.lock().unwrap().push(summary);
         }
     }
+
+    fn search_events(verbosity: VerbosityLevel) -> Vec<String> {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let sink = Arc::new(CollectingSink::default());
+        let mut idf_search = <IDFSearch<KPuzzle>>::try_new(
+            kpuzzle.clone(),
+            ["U", "R"]
+                .into_iter()
+                .map(|r#move| r#move.parse().unwrap())
+                .collect(),
+            kpuzzle.default_pattern(),
+            IDFSearchConstructionOptions {
+                search_logger: Arc::new(SearchLogger {
+                    verbosity,
+                    sink: Some(sink.clone()),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U"))
+            .unwrap();
+        let solutions: Vec<_> = idf_search
+            .search(&search_pattern, Default::default())
+            .collect();
+        assert_eq!(solutions.len(), 1);
+        let events = sink.events.lock().unwrap();
+        events.clone()
+    }
+
+    #[test]
+    fn search_logger_sink_test() {
+        let events = search_events(VerbosityLevel::Info);
+        assert!(events.contains(&"start Prune table 1".to_owned()));
+        assert!(events.ends_with(&[
+            "start Search 2".to_owned(),
+            "solution #1: U' R'".to_owned(),
+            "finish Search 2".to_owned(),
+        ]));
+
+        assert_eq!(search_events(VerbosityLevel::Warning), Vec::<String>::new());
+    }
 }
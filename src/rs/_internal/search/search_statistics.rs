@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+use super::prune_table_trait::Depth;
+
+fn serialize_duration_as_seconds<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// The size of a prune table at the end of a search.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneTableStatistics {
+    /// The number of entries in the table (filled or not).
+    pub num_entries: usize,
+    pub num_bytes: usize,
+    /// The depth up to which the table has been filled.
+    pub pruning_depth: Depth,
+}
+
+/// Statistics for the search of a single depth.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDepthStatistics {
+    pub depth: Depth,
+    /// The number of nodes of the search tree that were expanded.
+    pub num_recursive_calls: usize,
+    pub num_prune_table_lookups: usize,
+    /// The number of prune table lookups that cut off the search at that node.
+    pub num_prune_table_hits: usize,
+    /// The number of prune table lookups that returned each depth (indexed by
+    /// depth). The last entry counts all lookups that returned a depth larger
+    /// than `depth`.
+    pub prune_table_lookup_depths: Vec<usize>,
+    #[serde(
+        rename = "durationSeconds",
+        serialize_with = "serialize_duration_as_seconds"
+    )]
+    pub duration: Duration,
+}
+
+impl SearchDepthStatistics {
+    pub(crate) fn new(depth: Depth) -> Self {
+        Self {
+            depth,
+            prune_table_lookup_depths: vec![0; depth.0 + 2],
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_prune_table_lookup(&mut self, lookup_depth: Depth, is_hit: bool) {
+        self.num_prune_table_lookups += 1;
+        if is_hit {
+            self.num_prune_table_hits += 1;
+        }
+        let index = usize::min(lookup_depth.0, self.prune_table_lookup_depths.len() - 1);
+        self.prune_table_lookup_depths[index] += 1;
+    }
+
+    // For statistics that are counted separately by multiple threads.
+    pub(crate) fn add_counts(&mut self, other: &SearchDepthStatistics) {
+        self.num_recursive_calls += other.num_recursive_calls;
+        self.num_prune_table_lookups += other.num_prune_table_lookups;
+        self.num_prune_table_hits += other.num_prune_table_hits;
+        for (count, other_count) in self
+            .prune_table_lookup_depths
+            .iter_mut()
+            .zip(&other.prune_table_lookup_depths)
+        {
+            *count += other_count;
+        }
+    }
+}
+
+/// Statistics for a finished search. See [`SearchSolutions::statistics`](super::idf_search::idf_search::SearchSolutions::statistics).
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchStatistics {
+    /// One entry for each depth that was searched (including any depth at which the search stopped early).
+    pub depths: Vec<SearchDepthStatistics>,
+    /// This is `None` for searches that do not use a prune table.
+    pub prune_table: Option<PruneTableStatistics>,
+}
+
+impl SearchStatistics {
+    pub fn num_recursive_calls(&self) -> usize {
+        self.depths
+            .iter()
+            .map(|depth_statistics| depth_statistics.num_recursive_calls)
+            .sum()
+    }
+}
@@ -61,6 +61,7 @@ pub fn main() {
         ],
         Some(SearchLogger {
             verbosity: VerbosityLevel::Info,
+            ..Default::default()
         }),
     )
     .unwrap();
@@ -53,6 +53,7 @@ impl KociembaTwoPhase {
                 ],
                 Some(SearchLogger {
                     verbosity: VerbosityLevel::Info,
+                    ..Default::default()
                 }),
             )
             .unwrap(),
@@ -12,6 +12,9 @@ pub use search_api::search;
 mod gods_algorithm_api;
 pub use gods_algorithm_api::gods_algorithm;
 
+mod schreier_sims_api;
+pub use schreier_sims_api::schreier_sims;
+
 mod simple_mask_multiphase_search;
 pub use simple_mask_multiphase_search::{
     SimpleMaskMultiphaseSearch, SimpleMaskPhase, SimpleMaskPhaseInfo,
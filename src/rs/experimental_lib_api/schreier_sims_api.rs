@@ -0,0 +1,67 @@
+use cubing::kpuzzle::KPuzzle;
+
+use crate::_internal::{
+    cli::args::GeneratorArgs, errors::CommandError, schreier_sims::kpuzzle_group::KPuzzleGroup,
+};
+
+/// Calculates the group generated by the given moves and algs (all moves in the
+/// puzzle definition by default) using the Schreier-Sims algorithm.
+///
+/// Note that the group order does not account for identical pieces. See
+/// [`KPuzzleGroup::orbits_with_identical_pieces`].
+///
+/// Usage example:
+///
+/// ```
+/// use cubing::puzzles::cube2x2x2_kpuzzle;
+/// use twsearch::{
+///     _internal::cli::args::GeneratorArgs, // TODO
+///     experimental_lib_api::schreier_sims,
+/// };
+///
+/// let group = schreier_sims(
+///     cube2x2x2_kpuzzle(),
+///     &GeneratorArgs {
+///         generator_moves_string: Some("U,F,R".to_owned()), // TODO: make this semantic
+///         ..Default::default()
+///     },
+/// )
+/// .unwrap();
+/// assert_eq!(group.order().to_string(), "3674160");
+/// ```
+pub fn schreier_sims(
+    kpuzzle: &KPuzzle,
+    generator_args: &GeneratorArgs,
+) -> Result<KPuzzleGroup, CommandError> {
+    let generators = generator_args.parse();
+    Ok(KPuzzleGroup::try_new(
+        kpuzzle,
+        &generators.enumerate_moves_for_kpuzzle(kpuzzle),
+        &generators.algs(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use cubing::puzzles::cube3x3x3_kpuzzle;
+
+    use crate::{_internal::cli::args::GeneratorArgs, experimental_lib_api::schreier_sims};
+
+    #[test]
+    fn schreier_sims_api_test() {
+        let group = schreier_sims(
+            cube3x3x3_kpuzzle(),
+            &GeneratorArgs {
+                generator_moves_string: Some("U,L,F,R,B,D".to_owned()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // The 3x3x3 definition tracks center orientations, which are ignored in the default pattern.
+        assert_eq!(
+            group.orbits_with_identical_pieces,
+            vec!["CENTERS".to_owned()]
+        );
+        assert_eq!(group.order().to_string(), "88580102706155225088000");
+    }
+}
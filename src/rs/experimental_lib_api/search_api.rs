@@ -1,12 +1,25 @@
 use std::sync::Arc;
 
 use crate::_internal::{
-    cli::args::{SearchCommandOptionalArgs, VerbosityLevel},
-    errors::CommandError,
+    cli::args::{
+        parse_move_costs, parse_symmetry_moves, parse_time_limit_seconds,
+        EnableAutoAlwaysNeverValueEnum, SearchCommandOptionalArgs, VerbosityLevel,
+    },
+    errors::{ArgumentError, CommandError},
     search::{
-        idf_search::idf_search::{
-            IDFSearch, IDFSearchConstructionOptions, IndividualSearchOptions, SearchSolutions,
+        hash_prune_table::HashPruneTableOptions,
+        idf_search::{
+            bidirectional_search::BidirectionalSearch,
+            idf_search::{
+                default_num_threads, IDFSearch, IDFSearchConstructionOptions,
+                IndividualSearchOptions, SearchSolutions,
+            },
+            search_adaptations::{
+                KPuzzleMaskedMaxPruneTableAdaptations, KPuzzlePerfectIndexPruneTableAdaptations,
+            },
         },
+        kpuzzle_solvability_checker::KPuzzleSolvabilityChecker,
+        masked_max_prune_table::MaskedMaxPruneTableOptions,
         search_logger::SearchLogger,
     },
 };
@@ -38,47 +51,133 @@ pub fn search(
     search_pattern: &KPattern,
     search_command_optional_args: SearchCommandOptionalArgs,
 ) -> Result<SearchSolutions, CommandError> {
-    if search_command_optional_args.search_args.all_optimal {
-        eprintln!("⚠️ --all-optimal was specified, but is not currently implemented. Ignoring.");
+    let experimental_target_patterns = search_command_optional_args
+        .scramble_and_target_pattern_optional_args
+        .experimental_target_pattern;
+    let target_patterns = if experimental_target_patterns.is_empty() {
+        vec![kpuzzle.default_pattern()]
+    } else {
+        experimental_target_patterns
+            .into_iter()
+            .map(|path_buf| PatternSource::FilePath(path_buf).pattern(kpuzzle))
+            .collect::<Result<Vec<KPattern>, CommandError>>()?
+    };
+
+    let prune_table_masks = search_command_optional_args
+        .search_args
+        .prune_table_mask
+        .into_iter()
+        .map(|path_buf| PatternSource::FilePath(path_buf).pattern(kpuzzle))
+        .collect::<Result<Vec<KPattern>, CommandError>>()?;
+
+    let generators = search_command_optional_args.generator_args.parse();
+    let generator_moves = generators.enumerate_moves_for_kpuzzle(kpuzzle);
+    let check_before_solve = search_command_optional_args
+        .search_args
+        .check_before_solve
+        .clone()
+        .unwrap_or(EnableAutoAlwaysNeverValueEnum::Auto);
+    if !matches!(check_before_solve, EnableAutoAlwaysNeverValueEnum::Never) {
+        KPuzzleSolvabilityChecker::try_new(
+            kpuzzle,
+            &generator_moves,
+            &generators.algs(),
+            &target_patterns,
+            matches!(check_before_solve, EnableAutoAlwaysNeverValueEnum::Always),
+        )?
+        .check(search_pattern)?;
     }
 
-    let target_pattern = match search_command_optional_args
-        .scramble_and_target_pattern_optional_args
-        .experimental_target_pattern
+    let use_masked_prune_table = !prune_table_masks.is_empty();
+    let use_exact_prune_table = search_command_optional_args.search_args.exact_prune_table;
+    let use_bidirectional_search = search_command_optional_args.search_args.bidirectional;
+    let symmetry_moves =
+        parse_symmetry_moves(&search_command_optional_args.search_args.symmetry_moves)?;
+    if !symmetry_moves.is_empty()
+        && (use_masked_prune_table || use_exact_prune_table || use_bidirectional_search)
     {
-        Some(path_buf) => PatternSource::FilePath(path_buf).pattern(kpuzzle)?,
-        None => kpuzzle.default_pattern(),
+        return Err(CommandError::ArgumentError(ArgumentError {
+            description: "Symmetry moves are only supported with the default prune table."
+                .to_owned(),
+        }));
+    }
+    let construction_options = IDFSearchConstructionOptions {
+        search_logger: Arc::new(SearchLogger {
+            verbosity: search_command_optional_args
+                .verbosity_args
+                .verbosity
+                .unwrap_or(VerbosityLevel::Error),
+            ..Default::default()
+        }),
+        metric: search_command_optional_args.metric_args.metric,
+        random_start: search_command_optional_args.search_args.random_start,
+        max_prune_table_memory_bytes: search_command_optional_args
+            .search_args
+            .performance_args
+            .memory_args
+            .memory_bytes(),
+        prune_table_persistence: search_command_optional_args
+            .search_persistence_args
+            .prune_table_persistence_options()?,
+        num_threads: Some(
+            search_command_optional_args
+                .search_args
+                .performance_args
+                .num_threads
+                .unwrap_or_else(default_num_threads),
+        ),
+        generator_algs: generators.algs(),
+        move_costs: parse_move_costs(&search_command_optional_args.search_args.move_costs)?,
+        ..Default::default()
+    };
+    let individual_search_options = IndividualSearchOptions {
+        min_num_solutions: search_command_optional_args.min_num_solutions,
+        min_depth: search_command_optional_args.search_args.min_depth,
+        max_depth: search_command_optional_args.search_args.max_depth,
+        all_optimal: Some(search_command_optional_args.search_args.all_optimal),
+        time_limit: parse_time_limit_seconds(
+            search_command_optional_args.search_args.time_limit_seconds,
+        )?,
+        ..Default::default()
     };
 
-    let mut idf_search = <IDFSearch<KPuzzle>>::try_new(
-        kpuzzle.clone(),
-        search_command_optional_args
-            .generator_args
-            .parse()
-            .enumerate_moves_for_kpuzzle(kpuzzle),
-        target_pattern,
-        IDFSearchConstructionOptions {
-            search_logger: Arc::new(SearchLogger {
-                verbosity: search_command_optional_args
-                    .verbosity_args
-                    .verbosity
-                    .unwrap_or(VerbosityLevel::Error),
-            }),
-            metric: search_command_optional_args.metric_args.metric,
-            random_start: search_command_optional_args.search_args.random_start,
-            ..Default::default()
-        },
-    )?;
-
-    let solutions = idf_search.search(
-        search_pattern,
-        IndividualSearchOptions {
-            min_num_solutions: search_command_optional_args.min_num_solutions,
-            min_depth: search_command_optional_args.search_args.min_depth,
-            max_depth: search_command_optional_args.search_args.max_depth,
-            ..Default::default()
-        },
-    );
+    let solutions = if use_bidirectional_search {
+        BidirectionalSearch::try_new_with_target_patterns(
+            kpuzzle.clone(),
+            generator_moves,
+            target_patterns,
+            construction_options,
+        )?
+        .search(search_pattern, individual_search_options)
+    } else if use_masked_prune_table {
+        <IDFSearch<KPuzzle, KPuzzleMaskedMaxPruneTableAdaptations>>::try_new_with_prune_table_options(
+            kpuzzle.clone(),
+            generator_moves,
+            target_patterns,
+            construction_options,
+            MaskedMaxPruneTableOptions {
+                masks: prune_table_masks,
+            },
+        )?
+        .search(search_pattern, individual_search_options)
+    } else if use_exact_prune_table {
+        <IDFSearch<KPuzzle, KPuzzlePerfectIndexPruneTableAdaptations>>::try_new_with_target_patterns(
+            kpuzzle.clone(),
+            generator_moves,
+            target_patterns,
+            construction_options,
+        )?
+        .search(search_pattern, individual_search_options)
+    } else {
+        <IDFSearch<KPuzzle>>::try_new_with_prune_table_options(
+            kpuzzle.clone(),
+            generator_moves,
+            target_patterns,
+            construction_options,
+            HashPruneTableOptions { symmetry_moves },
+        )?
+        .search(search_pattern, individual_search_options)
+    };
 
     Ok(solutions)
 }
@@ -88,7 +187,13 @@ mod tests {
     use cubing::{alg::parse_alg, puzzles::cube3x3x3_kpuzzle};
 
     use crate::{
-        _internal::cli::args::{GeneratorArgs, SearchCommandOptionalArgs},
+        _internal::{
+            cli::args::{
+                CommonSearchArgs, EnableAutoAlwaysNeverValueEnum, GeneratorArgs, PerformanceArgs,
+                SearchCommandOptionalArgs,
+            },
+            search::{idf_search::idf_search::SearchEndReason, prune_table_trait::Depth},
+        },
         experimental_lib_api::search,
     };
 
@@ -117,4 +222,265 @@ mod tests {
         .unwrap();
         assert_eq!(solutions.next().unwrap().nodes.len(), 3);
     }
+
+    #[test]
+    fn search_api_alg_generators_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U R' U' R U R' U' F"))
+            .expect("Invalid alg for puzzle.");
+        let mut solutions = search(
+            kpuzzle,
+            &search_pattern,
+            SearchCommandOptionalArgs {
+                generator_args: GeneratorArgs {
+                    generator_moves_string: Some("F".to_owned()),
+                    generator_algs: Some("R U R' U'".to_owned()),
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // `R U R' U'` has order 6, so it only takes a single macro move to undo it twice.
+        assert_eq!(solutions.next().unwrap().to_string(), "F' (R U R' U')2'");
+    }
+
+    #[test]
+    fn search_api_move_costs_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("r"))
+            .expect("Invalid alg for puzzle.");
+        let mut solutions = search(
+            kpuzzle,
+            &search_pattern,
+            SearchCommandOptionalArgs {
+                generator_args: GeneratorArgs {
+                    generator_moves_string: Some("R,M,r".to_owned()),
+                    ..Default::default()
+                },
+                search_args: CommonSearchArgs {
+                    move_costs: Some("r=5".to_owned()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // `r'` costs 5, so two cheaper moves are used instead.
+        assert_eq!(solutions.next().unwrap().to_string(), "R' M");
+    }
+
+    #[test]
+    fn search_api_inconsistent_move_costs_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R' U"))
+            .expect("Invalid alg for puzzle.");
+        let search_with_move_costs = |move_costs: &str| {
+            search(
+                kpuzzle,
+                &search_pattern,
+                SearchCommandOptionalArgs {
+                    generator_args: GeneratorArgs {
+                        generator_moves_string: Some("R,U".to_owned()),
+                        ..Default::default()
+                    },
+                    search_args: CommonSearchArgs {
+                        move_costs: Some(move_costs.to_owned()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+        // The prune table would charge 3 for undoing `R'` with `R`, so the
+        // solution `U' R` would be pruned.
+        assert!(search_with_move_costs("R=1,R'=3").is_err());
+        // `R R` is never searched, even though it is cheaper than `R2`.
+        assert!(search_with_move_costs("R=1,R2=5").is_err());
+        assert_eq!(
+            search_with_move_costs("R=1,R2=2")
+                .unwrap()
+                .next()
+                .unwrap()
+                .to_string(),
+            "U' R"
+        );
+    }
+
+    #[test]
+    fn search_api_all_optimal_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R2 U2 R2 U2 R2 U2"))
+            .expect("Invalid alg for puzzle.");
+        let solutions = search(
+            kpuzzle,
+            &search_pattern,
+            SearchCommandOptionalArgs {
+                generator_args: GeneratorArgs {
+                    generator_moves_string: Some("R,U".to_owned()), // TODO: make this semantic
+                    ..Default::default()
+                },
+                search_args: CommonSearchArgs {
+                    all_optimal: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let solutions: Vec<_> = solutions.collect();
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().all(|solution| solution.nodes.len() == 6));
+    }
+
+    #[test]
+    fn search_api_multithreaded_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R2 U2 R2 U2 R2 U2"))
+            .expect("Invalid alg for puzzle.");
+        let solutions_with_num_threads = |num_threads: usize| -> Vec<String> {
+            let mut solutions: Vec<String> = search(
+                kpuzzle,
+                &search_pattern,
+                SearchCommandOptionalArgs {
+                    generator_args: GeneratorArgs {
+                        generator_moves_string: Some("R,U".to_owned()), // TODO: make this semantic
+                        ..Default::default()
+                    },
+                    search_args: CommonSearchArgs {
+                        all_optimal: true,
+                        performance_args: PerformanceArgs {
+                            num_threads: Some(num_threads),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .map(|solution| solution.to_string())
+            .collect();
+            solutions.sort();
+            solutions
+        };
+        assert_eq!(solutions_with_num_threads(4), solutions_with_num_threads(1));
+    }
+
+    #[test]
+    fn search_api_time_limit_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U2 F' L D' B2 R' U F2 L' D B' U2 R F'"))
+            .expect("Invalid alg for puzzle.");
+        let mut solutions = search(
+            kpuzzle,
+            &search_pattern,
+            SearchCommandOptionalArgs {
+                search_args: CommonSearchArgs {
+                    time_limit_seconds: Some(0.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(solutions.next().is_none());
+        assert_eq!(solutions.end_reason(), Some(SearchEndReason::TimedOut));
+    }
+
+    #[test]
+    fn search_api_cancellation_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U2 F' L D' B2 R' U F2 L' D B' U2 R F'"))
+            .expect("Invalid alg for puzzle.");
+        let mut solutions = search(kpuzzle, &search_pattern, Default::default()).unwrap();
+        solutions.cancellation_handle().cancel();
+        assert!(solutions.next().is_none());
+        assert_eq!(solutions.end_reason(), Some(SearchEndReason::Cancelled));
+    }
+
+    #[test]
+    fn search_api_end_reason_depth_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        // `R` alone cannot solve this, and the canonical FSM keeps each depth tiny.
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("F"))
+            .expect("Invalid alg for puzzle.");
+        let end_reason_with_max_depth = |max_depth: Option<Depth>| {
+            let mut solutions = search(
+                kpuzzle,
+                &search_pattern,
+                SearchCommandOptionalArgs {
+                    generator_args: GeneratorArgs {
+                        generator_moves_string: Some("R".to_owned()),
+                        ..Default::default()
+                    },
+                    search_args: CommonSearchArgs {
+                        check_before_solve: Some(EnableAutoAlwaysNeverValueEnum::Never),
+                        max_depth,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(solutions.next().is_none());
+            solutions.end_reason()
+        };
+        assert_eq!(
+            end_reason_with_max_depth(Some(Depth(3))),
+            Some(SearchEndReason::ExhaustedMaxDepth)
+        );
+        assert_eq!(
+            end_reason_with_max_depth(None),
+            Some(SearchEndReason::ExceededMaxSupportedDepth)
+        );
+    }
+
+    #[test]
+    fn search_api_statistics_test() {
+        let kpuzzle = cube3x3x3_kpuzzle();
+        let search_pattern = kpuzzle
+            .default_pattern()
+            .apply_alg(&parse_alg!("R U' F2 D"))
+            .expect("Invalid alg for puzzle.");
+        let mut solutions = search(kpuzzle, &search_pattern, Default::default()).unwrap();
+        assert!(solutions.statistics().is_none());
+        assert_eq!(solutions.next().unwrap().to_string(), "D' F2 U R'");
+        assert!(solutions.next().is_none());
+        let statistics = solutions.statistics().unwrap();
+        assert_eq!(
+            statistics
+                .depths
+                .iter()
+                .map(|depth_statistics| depth_statistics.depth)
+                .collect::<Vec<_>>(),
+            (0..5).map(Depth).collect::<Vec<_>>()
+        );
+        for depth_statistics in &statistics.depths {
+            assert!(depth_statistics.num_recursive_calls > 0);
+            assert_eq!(
+                depth_statistics
+                    .prune_table_lookup_depths
+                    .iter()
+                    .sum::<usize>(),
+                depth_statistics.num_prune_table_lookups
+            );
+        }
+        assert!(statistics.prune_table.as_ref().unwrap().num_entries > 0);
+    }
 }